@@ -169,3 +169,59 @@ impl<AccountId, AssetId, Price, BlockNumber> ParamsValidator<AccountId, AssetId,
         Ok(())
     }
 }
+
+/// Provides a time-varying exchange rate of a derivative asset to its base asset,
+/// e.g. a liquid staking token accruing rewards: Price[XDOT] = rate * Price[DOT]
+pub trait RateProvider<AssetId, Rate> {
+    /// Returns current rate of `asset` or `None` if the provider doesn't know the asset
+    fn get_rate(asset: &AssetId) -> Option<Rate>;
+}
+
+/// The first provider which knows the asset is used
+#[impl_trait_for_tuples::impl_for_tuples(5)]
+impl<AssetId, Rate> RateProvider<AssetId, Rate> for Tuple {
+    fn get_rate(asset: &AssetId) -> Option<Rate> {
+        for_tuples!(
+            #(
+                if let Some(rate) = Tuple::get_rate(asset) {
+                    return Some(rate);
+                }
+            )*
+        );
+        None
+    }
+}
+
+/// State of a liquidity pool used for LP token pricing
+#[derive(Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct LpPoolState<AssetId, Balance> {
+    /// Assets of the pool
+    pub assets: Vec<AssetId>,
+    /// Reserves of the pool, in the same order as `assets`
+    pub reserves: Vec<Balance>,
+    /// Pool invariant D, i.e. total pool value when all assets have the same price
+    pub invariant: Balance,
+    /// Total supply of the LP token
+    pub lp_supply: Balance,
+}
+
+/// Reports state of curve-like pools for LP token pricing
+pub trait LpPriceProvider<AssetId, Balance> {
+    /// Returns state of the pool if `lp_asset` is its LP token
+    fn get_pool_state(lp_asset: &AssetId) -> Option<LpPoolState<AssetId, Balance>>;
+}
+
+/// The first provider which knows the LP token is used
+#[impl_trait_for_tuples::impl_for_tuples(5)]
+impl<AssetId, Balance> LpPriceProvider<AssetId, Balance> for Tuple {
+    fn get_pool_state(lp_asset: &AssetId) -> Option<LpPoolState<AssetId, Balance>> {
+        for_tuples!(
+            #(
+                if let Some(state) = Tuple::get_pool_state(lp_asset) {
+                    return Some(state);
+                }
+            )*
+        );
+        None
+    }
+}
@@ -0,0 +1,37 @@
+//! Run with `cargo +nightly fuzz run parse_decimal` from `eq-utils`
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sp_runtime::{FixedI128, FixedI64, FixedPointNumber, FixedU128};
+use utils::fixed::{parse_decimal, DecimalError};
+
+fuzz_target!(|data: &[u8]| {
+    let value = match core::str::from_utf8(data) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+
+    let price_i64 = parse_decimal::<FixedI64>(value);
+    let price_i128 = parse_decimal::<FixedI128>(value);
+    let price_u128 = parse_decimal::<FixedU128>(value);
+
+    // the format doesn't depend on the type
+    assert_eq!(
+        price_i64 == Err(DecimalError::Invalid),
+        price_i128 == Err(DecimalError::Invalid)
+    );
+    assert_eq!(
+        price_i128 == Err(DecimalError::Invalid),
+        price_u128 == Err(DecimalError::Invalid)
+    );
+
+    // types with the same accuracy have the same value where both fit
+    if let (Ok(signed), Ok(unsigned)) = (price_i128, price_u128) {
+        assert_eq!(signed.into_inner() as u128, unsigned.into_inner());
+    }
+    // less accurate value is the more accurate one truncated
+    if let (Ok(less), Ok(more)) = (price_i64, price_i128) {
+        assert_eq!(less.into_inner() as i128, more.into_inner() / 1_000_000_000);
+    }
+});
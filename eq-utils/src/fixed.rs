@@ -0,0 +1,102 @@
+use core::convert::TryFrom;
+use sp_runtime::{traits::UniqueSaturatedInto, FixedPointNumber};
+
+/// Reason why a decimal string can't be parsed into a fixed point number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecimalError {
+    /// Not a decimal number, e.g. an empty string, `NaN` or `inf`
+    Invalid,
+    /// Value doesn't fit the fixed point type
+    Overflow,
+}
+
+/// Parses a decimal string like `-123.456` or `1.5e-7` into a fixed point number exactly,
+/// without `f64` rounding. Digits beyond the accuracy of `F` are truncated towards zero.
+/// JSON numbers are exact only if they are passed as their source text,
+/// e.g. `serde_json::Number` with `arbitrary_precision`, not after a conversion to `f64`
+pub fn parse_decimal<F: FixedPointNumber>(value: &str) -> Result<F, DecimalError> {
+    let (negative, unsigned) = match value.as_bytes().first() {
+        Some(b'-') => (true, &value[1..]),
+        Some(b'+') => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let (mantissa, exponent) = match unsigned.find(&['e', 'E'][..]) {
+        Some(e) => (&unsigned[..e], parse_exponent(&unsigned[e + 1..])?),
+        None => (unsigned, 0),
+    };
+    let (integral, fractional) = match mantissa.find('.') {
+        Some(dot) => (&mantissa[..dot], &mantissa[dot + 1..]),
+        None => (mantissa, ""),
+    };
+    if integral.is_empty() && fractional.is_empty() {
+        return Err(DecimalError::Invalid);
+    }
+    if !integral
+        .bytes()
+        .chain(fractional.bytes())
+        .all(|digit| digit.is_ascii_digit())
+    {
+        return Err(DecimalError::Invalid);
+    }
+
+    // value multiplied by `10^accuracy` is an integer with digits above `10^-accuracy`
+    let accuracy = decimal_accuracy::<F>();
+    let digits_len = integral.len() + fractional.len();
+    let scaled_len = (integral.len() as i64)
+        .saturating_add(exponent)
+        .saturating_add(accuracy as i64);
+    let kept = scaled_len.clamp(0, digits_len as i64) as usize;
+
+    let mut numerator: u128 = 0;
+    for digit in integral.bytes().chain(fractional.bytes()).take(kept) {
+        numerator = numerator
+            .checked_mul(10)
+            .and_then(|n| n.checked_add((digit - b'0') as u128))
+            .ok_or(DecimalError::Overflow)?;
+    }
+    if numerator != 0 {
+        let zeros = u32::try_from(scaled_len - kept as i64).map_err(|_| DecimalError::Overflow)?;
+        numerator = 10u128
+            .checked_pow(zeros)
+            .and_then(|scale| numerator.checked_mul(scale))
+            .ok_or(DecimalError::Overflow)?;
+    }
+
+    let denominator = 10u128.pow(accuracy);
+    let result = if negative {
+        let numerator = i128::try_from(numerator).map_err(|_| DecimalError::Overflow)?;
+        F::checked_from_rational(-numerator, denominator)
+    } else {
+        F::checked_from_rational(numerator, denominator)
+    };
+    result.ok_or(DecimalError::Overflow)
+}
+
+/// Parses an exponent with an optional sign, saturating on very long exponents
+fn parse_exponent(exponent: &str) -> Result<i64, DecimalError> {
+    let (negative, digits) = match exponent.as_bytes().first() {
+        Some(b'-') => (true, &exponent[1..]),
+        Some(b'+') => (false, &exponent[1..]),
+        _ => (false, exponent),
+    };
+    if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+        return Err(DecimalError::Invalid);
+    }
+
+    let value = digits.bytes().fold(0i64, |value, digit| {
+        value
+            .saturating_mul(10)
+            .saturating_add((digit - b'0') as i64)
+    });
+    Ok(if negative { -value } else { value })
+}
+
+/// Amount of decimal digits needed to represent the accuracy of `F`
+fn decimal_accuracy<F: FixedPointNumber>() -> u32 {
+    let div: u128 = F::DIV.unique_saturated_into();
+    let mut accuracy = 0;
+    while accuracy < 38 && 10u128.pow(accuracy) < div {
+        accuracy += 1;
+    }
+    accuracy
+}
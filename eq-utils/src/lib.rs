@@ -1,2 +1,7 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod fixed;
 pub mod log;
 pub mod offchain;
+
+mod tests;
@@ -0,0 +1,102 @@
+#![cfg(test)]
+
+use crate::fixed::{parse_decimal, DecimalError};
+use core::convert::TryFrom;
+use num_bigint::BigInt;
+use proptest::prelude::*;
+use sp_runtime::{FixedI128, FixedI64, FixedPointNumber, FixedU128};
+
+/// Exact `value * 10^accuracy` truncated towards zero, computed with big integers
+fn reference_inner(
+    negative: bool,
+    integral: &str,
+    fractional: &str,
+    exponent: i32,
+    accuracy: u32,
+) -> BigInt {
+    let digits = format!("0{}{}", integral, fractional);
+    let mantissa = BigInt::parse_bytes(digits.as_bytes(), 10).unwrap();
+    let scale = exponent as i64 - fractional.len() as i64 + accuracy as i64;
+    let inner = if scale >= 0 {
+        mantissa * BigInt::from(10).pow(scale as u32)
+    } else {
+        mantissa / BigInt::from(10).pow(-scale as u32)
+    };
+    if negative {
+        -inner
+    } else {
+        inner
+    }
+}
+
+fn format_decimal(
+    negative: bool,
+    integral: &str,
+    fractional: &str,
+    exponent: Option<i32>,
+) -> String {
+    let mut value = String::from(if negative { "-" } else { "" });
+    value.push_str(integral);
+    if !fractional.is_empty() {
+        value.push('.');
+        value.push_str(fractional);
+    }
+    if let Some(exponent) = exponent {
+        value.push_str(&format!("e{}", exponent));
+    }
+    value
+}
+
+proptest! {
+    #[test]
+    fn parse_decimal_matches_reference(
+        negative in any::<bool>(),
+        integral in "[0-9]{0,30}",
+        fractional in "[0-9]{0,30}",
+        exponent in proptest::option::of(-45i32..45),
+    ) {
+        prop_assume!(!integral.is_empty() || !fractional.is_empty());
+        let value = format_decimal(negative, &integral, &fractional, exponent);
+        let reference = |accuracy| {
+            reference_inner(negative, &integral, &fractional, exponent.unwrap_or(0), accuracy)
+        };
+
+        prop_assert_eq!(
+            parse_decimal::<FixedI64>(&value),
+            i64::try_from(&reference(9))
+                .map(FixedI64::from_inner)
+                .map_err(|_| DecimalError::Overflow)
+        );
+        prop_assert_eq!(
+            parse_decimal::<FixedI128>(&value),
+            i128::try_from(&reference(18))
+                .map(FixedI128::from_inner)
+                .map_err(|_| DecimalError::Overflow)
+        );
+        prop_assert_eq!(
+            parse_decimal::<FixedU128>(&value),
+            u128::try_from(&reference(18))
+                .map(FixedU128::from_inner)
+                .map_err(|_| DecimalError::Overflow)
+        );
+    }
+
+    #[test]
+    fn parse_decimal_round_trips_inner(inner in any::<i64>()) {
+        let price = FixedI64::from_inner(inner);
+        let magnitude = (inner as i128).abs();
+        let value = format!(
+            "{}{}.{:09}",
+            if inner < 0 { "-" } else { "" },
+            magnitude / 1_000_000_000,
+            magnitude % 1_000_000_000
+        );
+        prop_assert_eq!(parse_decimal::<FixedI64>(&value), Ok(price));
+    }
+
+    #[test]
+    fn parse_decimal_rejects_other_strings(value in "\\PC{0,40}") {
+        prop_assume!(!value.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c)));
+        prop_assert_eq!(parse_decimal::<FixedI64>(&value), Err(DecimalError::Invalid));
+    }
+}
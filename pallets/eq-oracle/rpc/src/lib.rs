@@ -0,0 +1,197 @@
+//! RPC interface for the oracle pallet.
+//!
+//! Read methods are served by `EqOracleApi` runtime API.
+//! `oracle_setOffchainConfig` is an operator-only (unsafe) method, it validates
+//! `OracleOffchainConfig` and writes it into the persistent offchain storage of the node,
+//! so the offchain worker picks it up on the next run.
+
+use codec::Codec;
+use eq_oracle::{
+    offchain_storage::{storage_keys, ConfigError, OracleOffchainConfig},
+    runtime_api::EqOracleApi as EqOracleRuntimeApi,
+    FeederSubmission, PriceDataView,
+};
+use jsonrpsee::{
+    core::{async_trait, Error as JsonRpseeError, RpcResult},
+    proc_macros::rpc,
+    types::error::{CallError, ErrorObject},
+};
+use parking_lot::RwLock;
+use sc_rpc_api::DenyUnsafe;
+use serde::{de::DeserializeOwned, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::offchain::OffchainStorage;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::{marker::PhantomData, sync::Arc};
+
+#[rpc(client, server)]
+pub trait OracleApi<BlockHash, AccountId, BlockNumber, AssetId, Price> {
+    /// Returns current reference (median) price of an asset
+    #[method(name = "oracle_getPrice")]
+    fn get_price(&self, asset: AssetId, at: Option<BlockHash>) -> RpcResult<Price>;
+
+    /// Returns aggregated price data of an asset with all its price points
+    #[method(name = "oracle_getPriceData")]
+    fn get_price_data(
+        &self,
+        asset: AssetId,
+        at: Option<BlockHash>,
+    ) -> RpcResult<Option<PriceDataView<AccountId, BlockNumber, Price>>>;
+
+    /// Returns all assets known to the oracle
+    #[method(name = "oracle_listAssets")]
+    fn list_assets(&self, at: Option<BlockHash>) -> RpcResult<Vec<AssetId>>;
+
+    /// Returns the last submission of a feeder for every asset
+    #[method(name = "oracle_feederStatus")]
+    fn feeder_status(
+        &self,
+        who: AccountId,
+        at: Option<BlockHash>,
+    ) -> RpcResult<Vec<(AssetId, FeederSubmission<BlockNumber, Price>)>>;
+
+    /// Validates and stores offchain config of the oracle feeder on this node
+    #[method(name = "oracle_setOffchainConfig")]
+    fn set_offchain_config(&self, config: OracleOffchainConfig) -> RpcResult<()>;
+}
+
+/// Error type of this RPC api
+pub enum Error {
+    /// The call to runtime failed
+    RuntimeError,
+    /// Price is not available
+    PriceError,
+    /// Offchain config is invalid
+    ConfigError,
+    /// Offchain storage is disabled on this node
+    OffchainStorageUnavailable,
+}
+
+impl From<Error> for i32 {
+    fn from(e: Error) -> i32 {
+        match e {
+            Error::RuntimeError => 1,
+            Error::PriceError => 2,
+            Error::ConfigError => 3,
+            Error::OffchainStorageUnavailable => 4,
+        }
+    }
+}
+
+fn call_error(error: Error, message: &str, data: Option<String>) -> JsonRpseeError {
+    CallError::Custom(ErrorObject::owned(error.into(), message, data)).into()
+}
+
+fn runtime_error(e: impl std::fmt::Debug) -> JsonRpseeError {
+    call_error(
+        Error::RuntimeError,
+        "Unable to query oracle state",
+        Some(format!("{:?}", e)),
+    )
+}
+
+/// Provides RPC methods to query oracle state and configure the feeder
+pub struct Oracle<C, Block, S> {
+    client: Arc<C>,
+    offchain_storage: Option<Arc<RwLock<S>>>,
+    deny_unsafe: DenyUnsafe,
+    _marker: PhantomData<Block>,
+}
+
+impl<C, Block, S> Oracle<C, Block, S> {
+    /// Creates a new instance of the oracle RPC handler.
+    /// `offchain_storage` is `None` if the node runs without offchain storage
+    pub fn new(client: Arc<C>, offchain_storage: Option<S>, deny_unsafe: DenyUnsafe) -> Self {
+        Self {
+            client,
+            offchain_storage: offchain_storage.map(|storage| Arc::new(RwLock::new(storage))),
+            deny_unsafe,
+            _marker: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl<C, Block, S, AccountId, BlockNumber, AssetId, Price>
+    OracleApiServer<<Block as BlockT>::Hash, AccountId, BlockNumber, AssetId, Price>
+    for Oracle<C, Block, S>
+where
+    Block: BlockT,
+    C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+    C::Api: EqOracleRuntimeApi<Block, AccountId, BlockNumber, AssetId, Price>,
+    S: OffchainStorage + 'static,
+    AccountId: Codec + Serialize + DeserializeOwned + Send + Sync + 'static,
+    BlockNumber: Codec + Serialize + DeserializeOwned + Send + Sync + 'static,
+    AssetId: Codec + Serialize + DeserializeOwned + Send + Sync + 'static,
+    Price: Codec + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn get_price(&self, asset: AssetId, at: Option<Block::Hash>) -> RpcResult<Price> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+        api.get_price(&at, asset)
+            .map_err(runtime_error)?
+            .map_err(|e| {
+                call_error(
+                    Error::PriceError,
+                    "Price is not available",
+                    Some(format!("{:?}", e)),
+                )
+            })
+    }
+
+    fn get_price_data(
+        &self,
+        asset: AssetId,
+        at: Option<Block::Hash>,
+    ) -> RpcResult<Option<PriceDataView<AccountId, BlockNumber, Price>>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+        api.get_price_data(&at, asset).map_err(runtime_error)
+    }
+
+    fn list_assets(&self, at: Option<Block::Hash>) -> RpcResult<Vec<AssetId>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+        api.list_assets(&at).map_err(runtime_error)
+    }
+
+    fn feeder_status(
+        &self,
+        who: AccountId,
+        at: Option<Block::Hash>,
+    ) -> RpcResult<Vec<(AssetId, FeederSubmission<BlockNumber, Price>)>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+        api.feeder_status(&at, who).map_err(runtime_error)
+    }
+
+    fn set_offchain_config(&self, config: OracleOffchainConfig) -> RpcResult<()> {
+        self.deny_unsafe.check_if_safe()?;
+
+        let storage = self.offchain_storage.as_ref().ok_or_else(|| {
+            call_error(
+                Error::OffchainStorageUnavailable,
+                "Offchain storage is disabled on this node",
+                None,
+            )
+        })?;
+        let raw = config.to_json().map_err(|e: ConfigError| {
+            call_error(
+                Error::ConfigError,
+                "Invalid offchain config",
+                Some(format!("{:?}", e)),
+            )
+        })?;
+
+        storage
+            .write()
+            .set(sp_offchain::STORAGE_PREFIX, storage_keys::CONFIG, &raw);
+
+        Ok(())
+    }
+}
@@ -4,56 +4,279 @@
 use super::*;
 use crate::Call;
 use frame_benchmarking::{account, benchmarks, whitelisted_caller};
+use frame_support::traits::{EnsureOrigin, Everything, Hooks};
+use frame_support::unsigned::ValidateUnsigned;
 use frame_system::RawOrigin;
-use sp_runtime::{traits::One, FixedI64, FixedPointNumber};
+use sp_runtime::{
+    traits::One, transaction_validity::TransactionSource, DispatchError, FixedI64,
+    FixedPointNumber, MultiSignature, MultiSigner,
+};
+
+/// Upper bounds of `on_initialize` components for special, correlated and LP assets,
+/// all of them fit into the lower bound of the total amount of assets
+const MAX_SPECIAL: u32 = 50;
+const MAX_CORRELATED: u32 = 50;
+const MAX_LP: u32 = 20;
+
+/// Key of amounts of (fed, special, correlated, LP) assets known to `AssetGetterMock`
+const ASSET_COUNTS_KEY: &[u8] = b"eq-oracle-benchmarking::asset_counts";
+
+/// Assets are numbered in a row: fed assets, then assets with special prices,
+/// then assets correlated with the first fed asset, then LP tokens of pools with
+/// the first fed asset
+fn asset_counts() -> (u64, u64, u64, u64) {
+    frame_support::storage::unhashed::get(ASSET_COUNTS_KEY).unwrap_or((1, 0, 0, 0))
+}
+
+fn set_asset_counts(fed: u32, special: u32, correlated: u32, lp: u32) {
+    frame_support::storage::unhashed::put(
+        ASSET_COUNTS_KEY,
+        &(fed as u64, special as u64, correlated as u64, lp as u64),
+    );
+}
+
+fn assets_total() -> u64 {
+    let (fed, special, correlated, lp) = asset_counts();
+    fed + special + correlated + lp
+}
 
 pub struct AssetGetterMock;
 impl AssetGetter for AssetGetterMock {
     type AssetId = u64;
     type AssetData = ();
 
-    fn get(asset: &u64) -> Option<()> {
-        (asset == &0x01234567).then(|| ())
+    fn get_asset_data(asset: u64) -> Result<(), DispatchError> {
+        Self::exists(asset)
+            .then(|| ())
+            .ok_or(DispatchError::Other("No asset"))
     }
 
-    fn get_assets() -> Vec<Self::AssetId> {
-        vec![0x01234567]
+    fn exists(asset: u64) -> bool {
+        asset < assets_total()
     }
 
     fn get_assets_data() -> Vec<(Self::AssetId, Self::AssetData)> {
-        vec![(0x01234567, ())]
+        Self::get_assets().into_iter().map(|a| (a, ())).collect()
+    }
+
+    fn get_assets() -> Vec<Self::AssetId> {
+        (0..assets_total()).collect()
+    }
+
+    fn get_main_asset() -> Self::AssetId {
+        0
+    }
+}
+
+pub struct SpecialPricesMock;
+impl<'a> Convert<(&'a u64, &'a ()), Option<FixedI64>> for SpecialPricesMock {
+    fn convert((asset, _): (&'a u64, &'a ())) -> Option<FixedI64> {
+        let (fed, special, _, _) = asset_counts();
+        (*asset >= fed && *asset < fed + special).then(FixedI64::one)
+    }
+}
+
+pub struct PriceCorrelationMock;
+impl<'a> Convert<(&'a u64, &'a ()), Option<Correlation<u64, FixedI64>>> for PriceCorrelationMock {
+    fn convert((asset, _): (&'a u64, &'a ())) -> Option<Correlation<u64, FixedI64>> {
+        let (fed, special, correlated, _) = asset_counts();
+        (*asset >= fed + special && *asset < fed + special + correlated)
+            .then(|| Correlation::direct(0, FixedI64::one()))
+    }
+}
+
+pub struct LpPriceProviderMock;
+impl LpPriceProvider<u64, u128> for LpPriceProviderMock {
+    fn get_pool_state(asset: &u64) -> Option<LpPoolState<u64, u128>> {
+        let (fed, special, correlated, _) = asset_counts();
+        (*asset >= fed + special + correlated && *asset < assets_total()).then(|| LpPoolState {
+            assets: vec![0, 0],
+            reserves: vec![1_000_000_000_000, 1_000_000_000_000],
+            invariant: 2_000_000_000_000,
+            lp_supply: 1_900_000_000_000,
+        })
     }
 }
 
 pub struct Module<T: Config>(crate::Pallet<T>);
 pub trait Config:
     crate::Config<
-    Whitelist = Everything,
-    AssetId = u64,
-    Price = FixedI64,
-    AssetGetter = AssetGetterMock,
->
+        Whitelist = Everything,
+        AssetId = u64,
+        Price = FixedI64,
+        AssetGetter = AssetGetterMock,
+        SpecialPrices = SpecialPricesMock,
+        PriceCorrelation = PriceCorrelationMock,
+        LpPriceProvider = LpPriceProviderMock,
+    > + SigningTypes<Public = MultiSigner, Signature = MultiSignature>
 {
 }
 
+/// Adds `count` price points from different feeders
+fn add_price_points<T: Config>(asset: u64, count: u32) {
+    for i in 0..count {
+        let price_setter: T::AccountId = account("price_setter", i, 0);
+        Pallet::<T>::set_price(
+            RawOrigin::Signed(price_setter).into(),
+            asset,
+            FixedI64::one(),
+        )
+        .unwrap();
+    }
+}
+
+/// Payload built for a key in the keystore and signed with it
+fn signed_payload<P: Encode>(payload: impl FnOnce(MultiSigner) -> P) -> (P, MultiSignature) {
+    let public = sp_io::crypto::sr25519_generate(KEY_TYPE, None);
+    let payload = payload(MultiSigner::from(public));
+    let signature = sp_io::crypto::sr25519_sign(KEY_TYPE, &public, &payload.encode())
+        .expect("Key is generated above");
+
+    (payload, MultiSignature::from(signature))
+}
+
 benchmarks! {
     set_price {
-        let b in 1 .. 20;
+        let b in 1 .. T::MaxFeeders::get().saturating_sub(1).max(1);
 
-        for i in 0..b {
-            let price_setter: T::AccountId = account("price_setter", i, 0);
-            Pallet::<T>::set_price(
-                RawOrigin::Signed(price_setter).into(),
-                0x01234567,
-                FixedI64::one()
-            ).unwrap();
-        }
+        set_asset_counts(1, 0, 0, 0);
+        add_price_points::<T>(0, b);
 
         let caller: T::AccountId = whitelisted_caller();
     }: _ (
         RawOrigin::Signed(caller),
-        0x01234567,
+        0,
         FixedI64::one()
     )
-    verify {}
+    verify {
+        assert_eq!(
+            Pallet::<T>::price_points(0).unwrap().price_points.len() as u32,
+            (b + 1).min(T::MaxFeeders::get())
+        );
+    }
+
+    set_price_unsigned {
+        let b in 1 .. T::MaxFeeders::get().saturating_sub(1).max(1);
+
+        set_asset_counts(1, 0, 0, 0);
+        frame_system::Pallet::<T>::set_block_number(1u32.into());
+        add_price_points::<T>(0, b);
+
+        let (payload, signature) = signed_payload(|public| LegacyPricePayload {
+            public,
+            asset: 0,
+            price: FixedI64::one(),
+            block_number: frame_system::Pallet::<T>::block_number(),
+        });
+    }: _ (
+        RawOrigin::None,
+        payload,
+        signature
+    )
+    verify {
+        assert_eq!(
+            Pallet::<T>::price_points(0).unwrap().price_points.len() as u32,
+            (b + 1).min(T::MaxFeeders::get())
+        );
+    }
+
+    set_price_unsigned_v2 {
+        let b in 1 .. T::MaxFeeders::get().saturating_sub(1).max(1);
+
+        set_asset_counts(1, 0, 0, 0);
+        frame_system::Pallet::<T>::set_block_number(1u32.into());
+        add_price_points::<T>(0, b);
+
+        let (payload, signature) = signed_payload(|public| PricePayload {
+            public,
+            asset: 0,
+            price: FixedI64::one(),
+            block_number: frame_system::Pallet::<T>::block_number(),
+            timestamp: T::UnixTime::now().as_secs(),
+        });
+    }: _ (
+        RawOrigin::None,
+        payload,
+        signature
+    )
+    verify {
+        assert_eq!(
+            Pallet::<T>::price_points(0).unwrap().price_points.len() as u32,
+            (b + 1).min(T::MaxFeeders::get())
+        );
+    }
+
+    validate_unsigned {
+        set_asset_counts(1, 0, 0, 0);
+        frame_system::Pallet::<T>::set_block_number(1u32.into());
+
+        let (payload, signature) = signed_payload(|public| PricePayload {
+            public,
+            asset: 0,
+            price: FixedI64::one(),
+            block_number: frame_system::Pallet::<T>::block_number(),
+            timestamp: T::UnixTime::now().as_secs(),
+        });
+        let call = Call::<T>::set_price_unsigned_v2 { payload, signature };
+    }: {
+        Pallet::<T>::validate_unsigned(TransactionSource::External, &call)
+            .map_err(|_| "Transaction is not valid")?;
+    }
+
+    on_initialize {
+        let a in (1 + MAX_SPECIAL + MAX_CORRELATED + MAX_LP) .. 300;
+        let s in 0 .. MAX_SPECIAL;
+        let c in 0 .. MAX_CORRELATED;
+        let l in 0 .. MAX_LP;
+
+        // `a` assets as the hook sees them: one fed asset, `c` correlated assets, `l` LP tokens
+        // and special prices, of which all but `s` are up to date and are only read
+        let special = a - 1 - c - l;
+        set_asset_counts(1, special, c, l);
+        for asset in 1..=(special - s) as u64 {
+            Pallet::<T>::set_the_only_price(asset, FixedI64::one(), T::UnixTime::now().as_secs());
+        }
+        add_price_points::<T>(0, 1);
+    }: {
+        Pallet::<T>::on_initialize(1u32.into());
+        Pallet::<T>::on_finalize(1u32.into());
+    }
+    verify {
+        let (fed, _, _, _) = asset_counts();
+        for asset in fed..assets_total() {
+            assert!(Pallet::<T>::price_points(asset).is_some());
+        }
+    }
+
+    set_rate {
+        set_asset_counts(1, 0, 0, 0);
+        let origin = T::RateOrigin::successful_origin();
+        let rate = FixedI64::saturating_from_rational(11, 10);
+    }: _ <T::Origin>(origin, 0, Some(rate))
+    verify {
+        assert_eq!(Pallet::<T>::rates(0), Some(rate));
+    }
+
+    filter_prices_from {
+        let a in 1 .. 50;
+
+        set_asset_counts(a, 0, 0, 0);
+        let caller: T::AccountId = whitelisted_caller();
+        for asset in 0..a as u64 {
+            add_price_points::<T>(asset, T::MaxFeeders::get().saturating_sub(1));
+            Pallet::<T>::set_price(
+                RawOrigin::Signed(caller.clone()).into(),
+                asset,
+                FixedI64::one(),
+            )
+            .unwrap();
+        }
+    }: {
+        Pallet::<T>::filter_prices_from(&caller);
+    }
+    verify {
+        for asset in 0..a as u64 {
+            assert!(Pallet::<T>::price_points_of(&asset, &caller).is_none());
+        }
+    }
 }
@@ -0,0 +1,148 @@
+//! Price correlations between assets
+
+use codec::Encode;
+use sp_arithmetic::FixedPointNumber;
+use sp_core::RuntimeDebug;
+use sp_runtime::{ArithmeticError, DispatchError};
+use sp_std::{collections::btree_map::BTreeMap, prelude::*};
+
+/// Price of an asset derived from other prices:
+/// `Price[asset] = multiplier * Price[numerator[0]] * ... / Price[denominator[0]] / ...`.
+/// Assets in `numerator` and `denominator` may be correlated too.
+/// If `with_rate` is set, the price is also multiplied by the current rate of the asset
+/// and is recalculated every block.
+#[derive(Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Correlation<AssetId, Price> {
+    pub multiplier: Price,
+    pub numerator: Vec<AssetId>,
+    pub denominator: Vec<AssetId>,
+    pub with_rate: bool,
+}
+
+/// Reason why a set of correlations can't be evaluated
+#[derive(Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum CorrelationError<AssetId> {
+    /// Asset price depends on a cycle of derived prices, e.g. on itself
+    Cycle(AssetId),
+    /// Correlation refers to an asset that is not known to `AssetGetter`
+    UnknownAsset(AssetId),
+    /// There are more correlated assets and LP tokens than `MaxDerivedPrices`
+    TooManyDerivedPrices,
+}
+
+impl<AssetId, Price> Correlation<AssetId, Price> {
+    /// `Price[asset] = multiplier * Price[base]`
+    pub fn direct(base: AssetId, multiplier: Price) -> Self {
+        Correlation {
+            multiplier,
+            numerator: vec![base],
+            denominator: Vec::new(),
+            with_rate: false,
+        }
+    }
+
+    /// `Price[asset] = multiplier * Price[numerator[0]] * ... / Price[denominator[0]] / ...`
+    pub fn new(multiplier: Price, numerator: Vec<AssetId>, denominator: Vec<AssetId>) -> Self {
+        Correlation {
+            multiplier,
+            numerator,
+            denominator,
+            with_rate: false,
+        }
+    }
+
+    /// All assets the price depends on
+    pub fn dependencies(&self) -> impl Iterator<Item = &AssetId> {
+        self.numerator.iter().chain(self.denominator.iter())
+    }
+}
+
+impl<AssetId, Price: FixedPointNumber> Correlation<AssetId, Price> {
+    /// `Price[asset] = rate * Price[base]`, where rate is a current rate of the asset
+    pub fn with_rate(base: AssetId) -> Self {
+        Correlation {
+            multiplier: Price::one(),
+            numerator: vec![base],
+            denominator: Vec::new(),
+            with_rate: true,
+        }
+    }
+
+    /// Calculates a price with prices of dependencies.
+    /// `rate` is a current rate of the asset, it is required only if `with_rate` is set
+    pub fn evaluate(
+        &self,
+        rate: Option<Price>,
+        mut get_price: impl FnMut(&AssetId) -> Result<Price, DispatchError>,
+    ) -> Result<Price, DispatchError> {
+        let mut price = self.multiplier;
+        if self.with_rate {
+            let rate = rate.ok_or(DispatchError::Other("Rate is not available"))?;
+            price = price.checked_mul(&rate).ok_or(ArithmeticError::Overflow)?;
+        }
+        for asset in &self.numerator {
+            price = price
+                .checked_mul(&get_price(asset)?)
+                .ok_or(ArithmeticError::Overflow)?;
+        }
+        for asset in &self.denominator {
+            let divisor = get_price(asset)?;
+            if divisor.is_zero() {
+                return Err(ArithmeticError::DivisionByZero.into());
+            }
+            price = price
+                .checked_div(&divisor)
+                .ok_or(ArithmeticError::Overflow)?;
+        }
+
+        Ok(price)
+    }
+}
+
+/// Orders assets so that every asset goes after the assets of the list it depends on.
+/// Takes assets with their dependencies, dependencies out of the list are not ordered.
+/// Runs in linear time of assets and dependencies count.
+/// Returns an error if some assets can't be ordered because of a cycle.
+pub fn sort_by_dependencies<AssetId: Encode + Clone>(
+    dependencies: Vec<(AssetId, Vec<AssetId>)>,
+) -> Result<Vec<AssetId>, CorrelationError<AssetId>> {
+    let positions: BTreeMap<Vec<u8>, usize> = dependencies
+        .iter()
+        .enumerate()
+        .map(|(position, (asset, _))| (asset.encode(), position))
+        .collect();
+    // count of not yet sorted dependencies and list of dependent assets for every asset
+    let mut pending = vec![0usize; dependencies.len()];
+    let mut dependents = vec![Vec::new(); dependencies.len()];
+    for (position, (_, asset_dependencies)) in dependencies.iter().enumerate() {
+        for dependency in asset_dependencies {
+            if let Some(&dependency_position) = positions.get(&dependency.encode()) {
+                pending[position] += 1;
+                dependents[dependency_position].push(position);
+            }
+        }
+    }
+
+    // sorted positions are also a queue of assets which dependents are not processed yet
+    let mut sorted: Vec<usize> = (0..dependencies.len())
+        .filter(|&position| pending[position] == 0)
+        .collect();
+    let mut next = 0;
+    while let Some(&position) = sorted.get(next) {
+        next += 1;
+        for &dependent in &dependents[position] {
+            pending[dependent] -= 1;
+            if pending[dependent] == 0 {
+                sorted.push(dependent);
+            }
+        }
+    }
+
+    match pending.iter().position(|&count| count != 0) {
+        Some(position) => Err(CorrelationError::Cycle(dependencies[position].0.clone())),
+        None => Ok(sorted
+            .into_iter()
+            .map(|position| dependencies[position].0.clone())
+            .collect()),
+    }
+}
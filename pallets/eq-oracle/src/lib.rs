@@ -2,16 +2,28 @@
 //!
 //! 1. Various price sources are supported.
 //! PriceSource - source of received price data points
-//! Custom - custom data source, url template is used.
+//! Custom - custom data source, url template is used. Requests of all assets are sent at once
+//! and awaited with one deadline.
 //! Pancake - price source that provides information for LP token price calculation (not our curve LP tokens!) .
 //! JSON path expressions are being parsed to retrieve price data.
 //! Once the price source is set up, prices for all currencies supported in the blockchain are fed from it.
-//! The price source can be changed on the fly: the validator (node) who feeds the price can do it via an RPC call.
+//! The price source can be changed on the fly: the validator (node) who feeds the price can do it via an RPC call
+//! (`oracle_setOffchainConfig` of `eq-oracle-rpc`).
 
 //! 2. Pancake price source gets data from pancake swap contract and calculate price for token.
-//! It requires: BCS/ETH node url, contract address, asset settings in offchain storage and prices of pool tokens stored onchain.
-//! It calls read methods on smart-contract and receives addresses of both pool tokens,
-//! total supply of LP token, calculate LP token price and returns it.
+//! It requires: BCS/ETH node url and pool contracts in the `pancake` section of offchain config and prices of pool tokens stored onchain.
+//! It calls read methods on smart-contract and receives reserves (`getReserves`), decimals of both pool tokens,
+//! total supply of LP token, calculate LP token price by fair reserves `2 * sqrt(r0 * r1 * p0 * p1) / total_supply` and returns it.
+
+//! EVM price source reads prices with view functions of contracts (e.g. Chainlink aggregators) with `eth_call`,
+//! `price_source::evm` module provides ABI encoding and decoding of static types for EVM sources.
+//! Chainlink price source reads `latestRoundData()` of aggregator contracts and rejects stale and incomplete rounds.
+//! Substrate price source mirrors prices from other Substrate chains: it reads SCALE encoded pool reserves or prices
+//! with `state_getStorage` or `state_call` of a remote node.
+//! GraphQL price source posts query templates to GraphQL endpoints, e.g. DEX subgraphs,
+//! and extracts prices from responses with a JSON path.
+//! Price sources send requests with `price_source::http_client::HttpTransport`, the offchain worker http api
+//! by default, so their requests can be replayed in tests.
 
 //! 3. Adjustable frequency of price points, it may be changed on the fly. Prices may be fed no faster than once per block.
 
@@ -24,6 +36,8 @@
 //! There is a PriceTimeout parameter which acts as a time-rolling window and shows
 //! which data points from which feeders should be taken into account when calculating a reference (median) price.
 //! No different data points from the same feeder are used in the reference price calculation.
+//! Time of a data point is the time its price was observed by the price source,
+//! offchain workers send it with `set_price_unsigned_v2` within `PriceTimestampTolerance` of the block time.
 
 //! Example:
 
@@ -33,8 +47,8 @@
 //! 2. someone feeds price in 40 seconds - it is used in calculation.
 //! 3. someone feeds the price in 65 seconds - my price from step 1 is not used in the calculation.
 
-//! There is a MedianPriceTimeout parameter - if the reference (median) price is not updated more than this timeout,
-//! anyone willing to obtain the price will receive an error.
+//! There is a MedianPriceTimeout parameter - if the latest data point of the reference (median) price
+//! was observed more than this timeout ago, anyone willing to obtain the price will receive an error.
 
 //! 5. Oracle is implemented using offchain workers (implements Substrate’s offchain worker).
 
@@ -42,29 +56,32 @@
 #![deny(warnings)]
 
 extern crate alloc;
-use alloc::string::String;
 use core::convert::TryInto;
 
 use frame_support::pallet_prelude::DispatchResultWithPostInfo;
 #[cfg(feature = "std")]
 use frame_support::traits::GenesisBuild;
 use frame_support::{
-    codec::{Decode, Encode},
+    codec::{Decode, Encode, MaxEncodedLen},
     dispatch::DispatchResult,
-    traits::{Contains, Get, UnixTime},
-    weights::TransactionPriority,
+    traits::{Contains, Get, StorageVersion, UnixTime},
+    weights::{TransactionPriority, Weight},
+    BoundedVec, CloneNoBound, PartialEqNoBound, RuntimeDebugNoBound,
 };
 use frame_system::offchain::{
     AppCrypto, CreateSignedTransaction, ForAll, SendUnsignedTransaction, SignedPayload, Signer,
     SigningTypes,
 };
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
 use sp_arithmetic::FixedPointNumber;
 use sp_core::{crypto::KeyTypeId, RuntimeDebug};
 use sp_runtime::{
     traits::{Convert, IdentifyAccount, TrailingZeroInput},
-    RuntimeAppPublic,
+    transaction_validity::InvalidTransaction,
+    ArithmeticError, DispatchError, RuntimeAppPublic,
 };
-use sp_std::{iter::Iterator, prelude::*};
+use sp_std::{collections::btree_map::BTreeMap, fmt::Debug, iter::Iterator, prelude::*};
 use utils::log;
 
 use codec::FullCodec;
@@ -76,13 +93,24 @@ pub mod weights;
 pub use weights::WeightInfo;
 
 mod regex_offsets;
-use primitives::{AssetGetter, OnPriceSet, ParamsValidator};
+use primitives::{
+    AssetGetter, LpPoolState, LpPriceProvider, OnPriceSet, ParamsValidator, PriceGetter,
+    RateProvider,
+};
 pub mod crypto;
 pub mod offchain_storage;
+use offchain_storage::OracleOffchainConfig;
 
 pub mod price_source;
 use price_source::PriceSourcePeeker;
 
+pub mod runtime_api;
+
+pub mod correlation;
+use correlation::{Correlation, CorrelationError};
+
+pub mod migrations;
+
 pub mod benchmarking;
 mod mock;
 mod tests;
@@ -94,6 +122,9 @@ pub use primitives;
 pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"orac");
 const ORACLE_PREFIX: &[u8] = b"eq-orac/";
 
+/// The current storage version
+const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
 /// Payload for a price setting with an unsigned transaction
 #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, scale_info::TypeInfo)]
 pub struct PricePayload<Public, BlockNumber, AssetId, Price> {
@@ -101,6 +132,8 @@ pub struct PricePayload<Public, BlockNumber, AssetId, Price> {
     asset: AssetId,
     price: Price,
     block_number: BlockNumber,
+    /// Unix time in seconds when the price was observed by the price source
+    timestamp: u64,
 }
 
 impl<T: SigningTypes, AssetId: Encode, Price: Encode> SignedPayload<T>
@@ -111,28 +144,82 @@ impl<T: SigningTypes, AssetId: Encode, Price: Encode> SignedPayload<T>
     }
 }
 
+/// Payload of `set_price_unsigned` without an observation timestamp,
+/// the price is considered observed at the block time
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, scale_info::TypeInfo)]
+pub struct LegacyPricePayload<Public, BlockNumber, AssetId, Price> {
+    public: Public,
+    asset: AssetId,
+    price: Price,
+    block_number: BlockNumber,
+}
+
+impl<T: SigningTypes, AssetId: Encode, Price: Encode> SignedPayload<T>
+    for LegacyPricePayload<T::Public, T::BlockNumber, AssetId, Price>
+{
+    fn public(&self) -> T::Public {
+        self.public.clone()
+    }
+}
+
 /// Struct for storing added asset price data from one source
-#[derive(Encode, Decode, Clone, Default, PartialEq, RuntimeDebug, scale_info::TypeInfo)]
+#[derive(
+    Encode, Decode, Clone, Default, PartialEq, RuntimeDebug, MaxEncodedLen, scale_info::TypeInfo,
+)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct PricePoint<AccountId, BlockNumber, Price> {
-    block_number: BlockNumber,
-    timestamp: u64,
-    price: Price,
-    account_id: AccountId,
+    /// Block number of price adding
+    pub block_number: BlockNumber,
+    /// Timestamp of price observation, seconds. Block time for signed
+    /// and legacy unsigned transactions
+    pub timestamp: u64,
+    /// Price value
+    pub price: Price,
+    /// Feeder's account id
+    pub account_id: AccountId,
 }
 
-/// Struct for storing aggregated asset price data
-#[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, scale_info::TypeInfo)]
-pub struct PriceData<AccountId, BlockNumber, Price> {
+/// Struct for storing aggregated asset price data.
+/// Holds no more than `MaxFeeders` price points
+#[derive(
+    Encode,
+    Decode,
+    CloneNoBound,
+    PartialEqNoBound,
+    RuntimeDebugNoBound,
+    MaxEncodedLen,
+    scale_info::TypeInfo,
+)]
+#[codec(mel_bound(AccountId: MaxEncodedLen, BlockNumber: MaxEncodedLen, Price: MaxEncodedLen))]
+#[scale_info(skip_type_params(MaxFeeders))]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "std",
+    serde(bound(
+        serialize = "AccountId: Serialize, BlockNumber: Serialize, Price: Serialize",
+        deserialize = "AccountId: Deserialize<'de>, BlockNumber: Deserialize<'de>, Price: Deserialize<'de>"
+    ))
+)]
+pub struct PriceData<
+    AccountId: Clone + PartialEq + Debug,
+    BlockNumber: Clone + PartialEq + Debug,
+    Price: Clone + PartialEq + Debug,
+    MaxFeeders: Get<u32>,
+> {
     pub block_number: BlockNumber,
     pub timestamp: u64,
     pub price: Price,
-    pub price_points: Vec<PricePoint<AccountId, BlockNumber, Price>>,
+    pub price_points: BoundedVec<PricePoint<AccountId, BlockNumber, Price>, MaxFeeders>,
 }
 
-impl<AccountId, BlockNumber: Default, Price: Default> Default
-    for PriceData<AccountId, BlockNumber, Price>
+impl<
+        AccountId: Clone + PartialEq + Debug,
+        BlockNumber: Clone + PartialEq + Debug + Default,
+        Price: Clone + PartialEq + Debug + Default,
+        MaxFeeders: Get<u32>,
+    > Default for PriceData<AccountId, BlockNumber, Price, MaxFeeders>
 {
-    fn default() -> PriceData<AccountId, BlockNumber, Price> {
+    fn default() -> PriceData<AccountId, BlockNumber, Price, MaxFeeders> {
         PriceData {
             block_number: Default::default(),
             timestamp: Default::default(),
@@ -142,10 +229,77 @@ impl<AccountId, BlockNumber: Default, Price: Default> Default
     }
 }
 
+/// Aggregated asset price data with all its price points, not bounded by `MaxFeeders`
+#[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, scale_info::TypeInfo)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct PriceDataView<AccountId, BlockNumber, Price> {
+    pub block_number: BlockNumber,
+    pub timestamp: u64,
+    pub price: Price,
+    /// Price points ordered by price
+    pub price_points: Vec<PricePoint<AccountId, BlockNumber, Price>>,
+}
+
+impl<
+        AccountId: Clone + PartialEq + Debug,
+        BlockNumber: Clone + PartialEq + Debug,
+        Price: Clone + PartialEq + Debug,
+        MaxFeeders: Get<u32>,
+    > From<PriceData<AccountId, BlockNumber, Price, MaxFeeders>>
+    for PriceDataView<AccountId, BlockNumber, Price>
+{
+    fn from(price_data: PriceData<AccountId, BlockNumber, Price, MaxFeeders>) -> Self {
+        PriceDataView {
+            block_number: price_data.block_number,
+            timestamp: price_data.timestamp,
+            price: price_data.price,
+            price_points: price_data.price_points.into_inner(),
+        }
+    }
+}
+
+/// Last price submitted by a feeder for an asset
+#[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, scale_info::TypeInfo)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct FeederSubmission<BlockNumber, Price> {
+    pub block_number: BlockNumber,
+    pub timestamp: u64,
+    pub price: Price,
+    /// Submission is older than `PriceTimeout` and is not used in the median anymore
+    pub is_stale: bool,
+}
+
+/// Source of a price derived from prices of other assets
+enum DerivedPrice<AssetId, Price> {
+    Correlated(Correlation<AssetId, Price>),
+    Lp(LpPoolState<AssetId, u128>),
+}
+
+impl<AssetId: Clone, Price> DerivedPrice<AssetId, Price> {
+    /// Assets the price depends on
+    fn dependencies(&self) -> Vec<AssetId> {
+        match self {
+            DerivedPrice::Correlated(correlation) => correlation.dependencies().cloned().collect(),
+            DerivedPrice::Lp(pool) => pool.assets.clone(),
+        }
+    }
+}
+
 /// UnsignedPriorityPair = (TransactionPriority, MinTransactionWeight)
 /// Unsigned priority = TransactionPriority + block_number % MinTransactionWeight
 pub type UnsignedPriorityPair = (TransactionPriority, u64);
 pub type AssetDataOf<T> = <<T as pallet::Config>::AssetGetter as AssetGetter>::AssetData;
+pub type PricePointOf<T> = PricePoint<
+    <T as frame_system::Config>::AccountId,
+    <T as frame_system::Config>::BlockNumber,
+    <T as pallet::Config>::Price,
+>;
+pub type PriceDataOf<T> = PriceData<
+    <T as frame_system::Config>::AccountId,
+    <T as frame_system::Config>::BlockNumber,
+    <T as pallet::Config>::Price,
+    <T as pallet::Config>::MaxFeeders,
+>;
 
 #[frame_support::pallet]
 pub mod pallet {
@@ -155,7 +309,7 @@ pub mod pallet {
 
     #[pallet::pallet]
     #[pallet::generate_store(pub(super) trait Store)]
-    #[pallet::without_storage_info]
+    #[pallet::storage_version(STORAGE_VERSION)]
     pub struct Pallet<T>(_);
 
     #[pallet::config]
@@ -169,7 +323,7 @@ pub mod pallet {
         /// Whitelist checks for price setters
         type Whitelist: Contains<Self::AccountId>;
         /// Asset id that could be represented as query string
-        type AssetId: Parameter + Member + MaybeSerializeDeserialize + FullCodec;
+        type AssetId: Parameter + Member + MaybeSerializeDeserialize + FullCodec + MaxEncodedLen;
         /// Used to deal with Assets
         type AssetGetter: AssetGetter<AssetId = Self::AssetId>;
         /// Additional validator for setting prices
@@ -185,14 +339,37 @@ pub mod pallet {
         /// Pallet setting representing amount of time for which price point is valid
         #[pallet::constant]
         type PriceTimeout: Get<u64>;
+        /// Maximum amount of price points stored for an asset, i.e. amount of feeders
+        /// whose prices are used in the median at the same time
+        #[pallet::constant]
+        type MaxFeeders: Get<u32>;
+        /// Maximum amount of correlated assets and LP tokens,
+        /// their order by dependencies is stored
+        #[pallet::constant]
+        type MaxDerivedPrices: Get<u32>;
+        /// Amount of time after which special and correlated prices are rewritten
+        /// even if they are not changed, should be less than `MedianPriceTimeout`
+        #[pallet::constant]
+        type PriceRefreshInterval: Get<u64>;
+        /// Max difference in seconds between the time a price from an unsigned transaction
+        /// was observed at and the block time
+        #[pallet::constant]
+        type PriceTimestampTolerance: Get<u64>;
         /// Type of fetched prices
-        type Price: Parameter + Member + MaybeSerializeDeserialize + FixedPointNumber + FullCodec;
+        type Price: Parameter
+            + Member
+            + MaybeSerializeDeserialize
+            + FixedPointNumber
+            + FullCodec
+            + MaxEncodedLen;
         /// Custom price source for assets, could be a Tuple of price sources
         type PriceSource: PriceSourcePeeker<Self::AssetId, AssetDataOf<Self>>;
-        /// Direct correlation map between assets, e.g.: Price[XDOT] = 1.0 * Price[DOT]
-        type DirectPriceCorrelation: for<'a> Convert<
+        /// Correlations between assets, e.g.: Price[XDOT] = 1.0 * Price[DOT] or
+        /// Price[A] = Price[B] * Price[C] / Price[D]. Correlations may refer to correlated assets,
+        /// but must not form cycles
+        type PriceCorrelation: for<'a> Convert<
             (&'a Self::AssetId, &'a AssetDataOf<Self>),
-            Option<(Self::AssetId, Self::Price)>,
+            Option<Correlation<Self::AssetId, Self::Price>>,
         >;
         /// Prices known at runtime or constant prices, e.g.: Price[EQD] = 1.0
         type SpecialPrices: for<'a> Convert<
@@ -201,6 +378,14 @@ pub mod pallet {
         >;
         /// Interface for feeding new prices into other pallets
         type OnPriceSet: OnPriceSet<Self::AssetId, Self::Price>;
+        /// Rates of derivative assets for correlations `with_rate`, e.g. liquid staking tokens.
+        /// Rates stored with `set_rate` take precedence
+        type RateProvider: RateProvider<Self::AssetId, Self::Price>;
+        /// Origin allowed to store rates of derivative assets
+        type RateOrigin: EnsureOrigin<Self::Origin>;
+        /// Curve-like pools reporting their state, prices of their LP tokens are calculated
+        /// from it. Special and correlated prices take precedence
+        type LpPriceProvider: LpPriceProvider<Self::AssetId, u128>;
         /// For priority calculation of an unsigned transaction
         #[pallet::constant]
         type UnsignedPriority: Get<UnsignedPriorityPair>;
@@ -213,7 +398,10 @@ pub mod pallet {
 
     #[pallet::call]
     impl<T: Config> Pallet<T> {
-        #[pallet::weight((T::WeightInfo::set_price(10), DispatchClass::Operational))]
+        #[pallet::weight((
+            T::WeightInfo::set_price(T::MaxFeeders::get()),
+            DispatchClass::Operational
+        ))]
         /// Adds and saves a new `DataPoint` containing an asset price information. It
         /// would be used for the `PricePoint` calculation. Only whitelisted
         /// accounts can add `DataPoints`
@@ -226,20 +414,25 @@ pub mod pallet {
             let current_block = frame_system::Pallet::<T>::block_number();
 
             Self::validate_params(&who, &asset, &price, current_block)?;
-            Self::set_price_inner(who, asset, price)?;
+            Self::set_price_inner(who, asset, price, T::UnixTime::now().as_secs())?;
 
             Ok(Pays::No.into())
         }
 
-        #[pallet::weight((T::WeightInfo::set_price(10), DispatchClass::Operational))]
-        /// Adds new `DataPoint` from an unsigned transaction
+        #[pallet::weight((
+            T::WeightInfo::set_price_unsigned(T::MaxFeeders::get())
+                .saturating_add(T::WeightInfo::validate_unsigned()),
+            DispatchClass::Operational
+        ))]
+        /// Adds new `DataPoint` from an unsigned transaction of a feeder without observation
+        /// timestamps, the price is considered observed at the block time
         pub fn set_price_unsigned(
             origin: OriginFor<T>,
-            payload: PricePayload<T::Public, T::BlockNumber, T::AssetId, T::Price>,
+            payload: LegacyPricePayload<T::Public, T::BlockNumber, T::AssetId, T::Price>,
             _signature: T::Signature,
         ) -> DispatchResultWithPostInfo {
             ensure_none(origin)?;
-            let PricePayload {
+            let LegacyPricePayload {
                 public,
                 asset,
                 price,
@@ -247,7 +440,61 @@ pub mod pallet {
             } = payload;
             let who = public.into_account();
             Self::validate_params(&who, &asset, &price, payload.block_number)?;
-            Self::set_price_inner(who, asset, price)?;
+            Self::set_price_inner(who, asset, price, T::UnixTime::now().as_secs())?;
+
+            Ok(().into())
+        }
+
+        #[pallet::weight(T::WeightInfo::set_rate())]
+        /// Stores a rate of a derivative asset used by correlations `with_rate`,
+        /// it overrides a rate from `RateProvider`. `None` removes the stored rate
+        pub fn set_rate(
+            origin: OriginFor<T>,
+            asset: T::AssetId,
+            rate: Option<T::Price>,
+        ) -> DispatchResultWithPostInfo {
+            T::RateOrigin::ensure_origin(origin)?;
+            ensure!(
+                T::AssetGetter::exists(asset.clone()),
+                Error::<T>::CurrencyNotFound
+            );
+
+            match rate {
+                Some(rate) => {
+                    ensure!(rate.is_positive(), Error::<T>::InvalidRate);
+                    <Rates<T>>::insert(&asset, rate);
+                }
+                None => <Rates<T>>::remove(&asset),
+            }
+            Self::deposit_event(Event::RateSet(asset, rate));
+
+            Ok(().into())
+        }
+
+        #[pallet::weight((
+            T::WeightInfo::set_price_unsigned_v2(T::MaxFeeders::get())
+                .saturating_add(T::WeightInfo::validate_unsigned()),
+            DispatchClass::Operational
+        ))]
+        /// Adds new `DataPoint` from an unsigned transaction with the time the price
+        /// was observed at, it should be within `PriceTimestampTolerance` of the block time
+        pub fn set_price_unsigned_v2(
+            origin: OriginFor<T>,
+            payload: PricePayload<T::Public, T::BlockNumber, T::AssetId, T::Price>,
+            _signature: T::Signature,
+        ) -> DispatchResultWithPostInfo {
+            ensure_none(origin)?;
+            let PricePayload {
+                public,
+                asset,
+                price,
+                block_number,
+                timestamp,
+            } = payload;
+            let who = public.into_account();
+            Self::validate_params(&who, &asset, &price, block_number)?;
+            Self::validate_timestamp(timestamp).map_err(|_| Error::<T>::InvalidPriceTimestamp)?;
+            Self::set_price_inner(who, asset, price, timestamp)?;
 
             Ok(().into())
         }
@@ -281,34 +528,23 @@ pub mod pallet {
             }
             //acquire a lock
             let lock_res = utils::offchain::acquire_lock(ORACLE_PREFIX, || {
-                // All oracles must set their own price feeding frequency
-                // Oracle feeds prices every N blocks, where N = oracle::price_periodicity
-                let maybe_price_periodicity = offchain_storage::get_price_periodicity();
-                if maybe_price_periodicity.is_none() {
-                    log::warn!("Price periodicity setting doesn't exists");
-                    return;
-                }
-
-                let price_periodicity = maybe_price_periodicity.unwrap();
-                if price_periodicity < 1 {
-                    log::warn!(
-                        "Unexpected price periodicity {:?}, should be more or equal 1",
-                        price_periodicity
-                    );
-                    return;
-                }
+                // All oracles must set their own price feeding frequency and price source
+                // Oracle feeds prices every N blocks, where N = config.price_periodicity
+                let config = match offchain_storage::get_config() {
+                    Ok(config) => config,
+                    Err(err) => {
+                        log::warn!("Offchain config can't be used: {:?}", err);
+                        return;
+                    }
+                };
 
                 let counter = offchain_storage::get_counter().unwrap_or(0_u32);
                 let counter_next = counter + 1;
 
-                if counter_next == price_periodicity {
+                if counter_next == config.price_periodicity {
                     offchain_storage::set_counter(0_u32);
-
-                    // Prices source
-                    if let Some(source_type_name) = offchain_storage::get_source_type() {
-                        Self::update_prices(source_type_name, block_number, &signer);
-                    }
-                } else if counter_next > price_periodicity {
+                    Self::update_prices(&config, block_number, &signer);
+                } else if counter_next > config.price_periodicity {
                     offchain_storage::set_counter(0_u32);
                 } else {
                     offchain_storage::set_counter(counter_next);
@@ -318,25 +554,61 @@ pub mod pallet {
         }
 
         fn on_initialize(_: BlockNumberFor<T>) -> Weight {
-            for asset in T::AssetGetter::get_assets_data() {
+            let now = T::UnixTime::now().as_secs();
+            let is_outdated = |price_data: &PriceDataOf<T>| {
+                price_data.timestamp + T::PriceRefreshInterval::get() <= now
+            };
+            let assets = T::AssetGetter::get_assets_data();
+            let assets_count = assets.len() as u32;
+            let mut special_count = 0u32;
+            let mut correlated_count = 0u32;
+            let mut lp_count = 0u32;
+
+            for asset in assets {
                 if let Some(price) = T::SpecialPrices::convert((&asset.0, &asset.1)) {
-                    Self::set_the_only_price(asset.0.clone(), price);
+                    let should_update = <PricePoints<T>>::get(&asset.0)
+                        .map_or(true, |price_data| {
+                            price_data.price != price || is_outdated(&price_data)
+                        });
+                    if should_update {
+                        special_count += 1;
+                        Self::set_the_only_price(asset.0.clone(), price, now);
+                        <UpdatedAssets<T>>::insert(&asset.0, ());
+                    }
                     continue;
                 }
 
-                if let Some((corr_asset, correlation)) =
-                    T::DirectPriceCorrelation::convert((&asset.0, &asset.1))
-                {
-                    if let Ok(price) =
-                        <Self as primitives::PriceGetter>::get_price(corr_asset.clone())
-                    {
-                        Self::set_the_only_price(asset.0.clone(), correlation * price);
-                        continue;
-                    }
+                if T::PriceCorrelation::convert((&asset.0, &asset.1)).is_some() {
+                    correlated_count += 1;
+                } else if T::LpPriceProvider::get_pool_state(&asset.0).is_some() {
+                    lp_count += 1;
                 }
             }
 
-            10_000
+            // derived prices are updated in `on_finalize` with prices fed in this block
+            T::WeightInfo::on_initialize(assets_count, special_count, correlated_count, lp_count)
+        }
+
+        fn on_finalize(_: BlockNumberFor<T>) {
+            Self::update_derived_prices();
+        }
+
+        fn integrity_test() {
+            assert!(
+                T::MaxFeeders::get() > 0,
+                "MaxFeeders should allow at least one price point"
+            );
+            assert!(
+                T::PriceRefreshInterval::get() < T::MedianPriceTimeout::get(),
+                "PriceRefreshInterval should be less than MedianPriceTimeout"
+            );
+            assert!(
+                T::PriceTimestampTolerance::get() < T::PriceTimeout::get(),
+                "PriceTimestampTolerance should be less than PriceTimeout"
+            );
+            if let Err(err) = Self::validate_correlations() {
+                panic!("Invalid price correlations: {:?}", err);
+            }
         }
     }
 
@@ -348,6 +620,9 @@ pub mod pallet {
         /// aggregated price and `AccountId` of the price submitter
         /// \[asset, new_value, aggregated, submitter\]
         NewPrice(T::AssetId, T::Price, T::Price, T::AccountId),
+        /// Stored rate of a derivative asset is changed, `None` if removed
+        /// \[asset, rate\]
+        RateSet(T::AssetId, Option<T::Price>),
     }
 
     #[pallet::error]
@@ -368,22 +643,37 @@ pub mod pallet {
         PriceIsNegative,
         /// The price data point is too old and cannot be used
         PriceTimeout,
+        /// Amount of price points for the asset can't fit into `MaxFeeders`
+        TooManyPricePoints,
+        /// Rate should be positive
+        InvalidRate,
+        /// Pool state reported by `LpPriceProvider` is inconsistent
+        InvalidPoolState,
+        /// Price observation time differs from the block time more than `PriceTimestampTolerance`
+        InvalidPriceTimestamp,
     }
 
     /// Pallet storage for added price points
     #[pallet::storage]
     #[pallet::getter(fn price_points)]
-    pub(super) type PricePoints<T: Config> = StorageMap<
-        _,
-        Identity,
-        T::AssetId,
-        PriceData<
-            <T as frame_system::Config>::AccountId,
-            <T as frame_system::Config>::BlockNumber,
-            T::Price,
-        >,
-        OptionQuery,
-    >;
+    pub(super) type PricePoints<T: Config> =
+        StorageMap<_, Identity, T::AssetId, PriceDataOf<T>, OptionQuery>;
+
+    /// Assets with median price changed in the current block,
+    /// prices derived from them are recalculated in `on_finalize`
+    #[pallet::storage]
+    pub(super) type UpdatedAssets<T: Config> = StorageMap<_, Identity, T::AssetId, (), OptionQuery>;
+
+    /// Assets with correlated and LP token prices ordered so that every asset goes after
+    /// the assets its price depends on, with a hash of dependencies the order is calculated for
+    #[pallet::storage]
+    pub(super) type DerivedPriceOrder<T: Config> =
+        StorageValue<_, ([u8; 32], BoundedVec<T::AssetId, T::MaxDerivedPrices>), ValueQuery>;
+
+    /// Rates of derivative assets set by `RateOrigin`
+    #[pallet::storage]
+    #[pallet::getter(fn rates)]
+    pub(super) type Rates<T: Config> = StorageMap<_, Identity, T::AssetId, T::Price, OptionQuery>;
 
     #[pallet::genesis_config]
     pub struct GenesisConfig<T: Config> {
@@ -404,6 +694,11 @@ pub mod pallet {
     #[pallet::genesis_build]
     impl<T: Config> GenesisBuild<T> for GenesisConfig<T> {
         fn build(&self) {
+            // the order is stored in the first block
+            if let Err(err) = Pallet::<T>::validate_correlations() {
+                panic!("Invalid price correlations: {:?}", err);
+            }
+
             // with chain spec
             for asset in T::AssetGetter::get_assets() {
                 <PricePoints<T>>::insert(asset, PriceData::default());
@@ -426,80 +721,103 @@ pub mod pallet {
         type Call = Call<T>;
 
         fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
-            if let Call::set_price_unsigned { payload, signature } = call {
-                let signature_valid =
-                    SignedPayload::<T>::verify::<T::AuthorityId>(payload, signature.clone());
-                if !signature_valid {
-                    return InvalidTransaction::BadProof.into();
-                }
-
-                let current_block = <frame_system::Pallet<T>>::block_number();
-
-                if payload.block_number > current_block {
-                    // transaction in future?
-                    return InvalidTransaction::Stale.into();
-                } else if payload.block_number + T::UnsignedLifetimeInBlocks::get().into()
-                    < current_block
-                {
-                    // transaction was in pool for 5 blocks
-                    return InvalidTransaction::Stale.into();
-                }
-
-                let account = payload.public.clone().into_account();
-                Self::validate_params(
-                    &account,
+            let (public, asset, price, block_number, timestamp, signature_valid) = match call {
+                Call::set_price_unsigned { payload, signature } => (
+                    &payload.public,
                     &payload.asset,
                     &payload.price,
                     payload.block_number,
-                )
-                .map_err(|_| InvalidTransaction::Call)?;
+                    None,
+                    SignedPayload::<T>::verify::<T::AuthorityId>(payload, signature.clone()),
+                ),
+                Call::set_price_unsigned_v2 { payload, signature } => (
+                    &payload.public,
+                    &payload.asset,
+                    &payload.price,
+                    payload.block_number,
+                    Some(payload.timestamp),
+                    SignedPayload::<T>::verify::<T::AuthorityId>(payload, signature.clone()),
+                ),
+                _ => return InvalidTransaction::Call.into(),
+            };
+            if !signature_valid {
+                return InvalidTransaction::BadProof.into();
+            }
 
-                let (initial_priority, min_transaction_weight) = T::UnsignedPriority::get();
-                let priority = initial_priority.saturating_add(
-                    (TryInto::<u64>::try_into(payload.block_number).unwrap_or(0))
-                        % min_transaction_weight,
-                );
+            let current_block = <frame_system::Pallet<T>>::block_number();
 
-                ValidTransaction::with_tag_prefix("EqOracleSetPrice")
-                    .priority(priority)
-                    .and_provides((payload.public.clone(), &payload.asset))
-                    .longevity(5) // hotfix, transfer to config
-                    .propagate(true)
-                    .build()
-            } else {
-                InvalidTransaction::Call.into()
+            if block_number > current_block {
+                // transaction in future?
+                return InvalidTransaction::Stale.into();
+            } else if block_number + T::UnsignedLifetimeInBlocks::get().into() < current_block {
+                // transaction was in pool for 5 blocks
+                return InvalidTransaction::Stale.into();
             }
+
+            let account = public.clone().into_account();
+            Self::validate_params(&account, asset, price, block_number)
+                .map_err(|_| InvalidTransaction::Call)?;
+            if let Some(timestamp) = timestamp {
+                Self::validate_timestamp(timestamp)?;
+            }
+
+            let (initial_priority, min_transaction_weight) = T::UnsignedPriority::get();
+            let priority = initial_priority.saturating_add(
+                (TryInto::<u64>::try_into(block_number).unwrap_or(0)) % min_transaction_weight,
+            );
+
+            ValidTransaction::with_tag_prefix("EqOracleSetPrice")
+                .priority(priority)
+                .and_provides((public.clone(), asset))
+                .longevity(5) // hotfix, transfer to config
+                .propagate(true)
+                .build()
         }
     }
 }
 
 impl<T: Config> Pallet<T> {
     /// Initializes price source and gets prices
-    fn get_prices(source_type_name: String) -> Vec<(T::AssetId, Result<T::Price, &'static str>)> {
+    fn get_prices(
+        config: &OracleOffchainConfig,
+    ) -> Vec<(T::AssetId, Result<(T::Price, Option<u64>), &'static str>)> {
         let assets_data = T::AssetGetter::get_assets_data();
 
-        match T::PriceSource::get_prices(&source_type_name, &assets_data) {
+        match T::PriceSource::get_prices(config, &assets_data) {
             Ok(prices) => prices,
             Err(Some(err)) => {
                 log::error!("Error while creating price source: {:?}.", err);
                 Vec::new()
             }
             Err(None) => {
-                log::error!("Unexpected price resource type: {:?}.", source_type_name);
+                log::error!("Unexpected price resource type: {:?}.", config.source_type);
                 Vec::new()
             }
         }
     }
 
     fn update_prices(
-        source_type_name: String,
+        config: &OracleOffchainConfig,
         block_number: T::BlockNumber,
         signer: &Signer<T, T::AuthorityId, ForAll>,
     ) {
-        for (asset, price_result) in Self::get_prices(source_type_name) {
+        let now = sp_io::offchain::timestamp().unix_millis() / 1000;
+        for (asset, price_result) in Self::get_prices(config) {
             match price_result {
-                Ok(price) => {
-                    Self::submit_tx_update_price(asset, price, block_number, signer);
+                Ok((price, timestamp)) if !config.is_price_fresh(timestamp, now) => {
+                    log::error!(
+                        "Price is too old. Asset: {:?}, price: {:?}, timestamp: {:?}, now: {:?}, max age: {:?}",
+                        asset,
+                        price,
+                        timestamp,
+                        now,
+                        config.max_price_age,
+                    );
+                }
+                Ok((price, timestamp)) => {
+                    // prices of sources without timestamps are observed now
+                    let timestamp = timestamp.unwrap_or(now);
+                    Self::submit_tx_update_price(asset, price, timestamp, block_number, signer);
                 }
                 Err(err) => {
                     log::error!(
@@ -519,6 +837,7 @@ impl<T: Config> Pallet<T> {
     fn submit_tx_update_price(
         asset: T::AssetId,
         price: T::Price,
+        timestamp: u64,
         block_number: T::BlockNumber,
         signer: &Signer<T, T::AuthorityId, ForAll>,
     ) {
@@ -528,11 +847,40 @@ impl<T: Config> Pallet<T> {
                 asset: asset.clone(),
                 price,
                 block_number,
+                timestamp,
             },
-            |payload, signature| Call::set_price_unsigned { payload, signature },
+            |payload, signature| Call::set_price_unsigned_v2 { payload, signature },
         );
     }
 
+    /// Checks that a price observation timestamp is within `PriceTimestampTolerance`
+    /// of the block time
+    fn validate_timestamp(timestamp: u64) -> Result<(), InvalidTransaction> {
+        let now = T::UnixTime::now().as_secs();
+        let tolerance = T::PriceTimestampTolerance::get();
+        if timestamp > now.saturating_add(tolerance) {
+            log::error!(
+                target: "eq_oracle",
+                "Price timestamp is in the future. Timestamp: {:?}, now: {:?}, tolerance: {:?}.",
+                timestamp,
+                now,
+                tolerance
+            );
+            Err(InvalidTransaction::Future)
+        } else if timestamp.saturating_add(tolerance) < now {
+            log::error!(
+                target: "eq_oracle",
+                "Price timestamp is too old. Timestamp: {:?}, now: {:?}, tolerance: {:?}.",
+                timestamp,
+                now,
+                tolerance
+            );
+            Err(InvalidTransaction::Stale)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Validates the parameters fot setting price
     fn validate_params(
         who: &T::AccountId,
@@ -573,7 +921,8 @@ impl<T: Config> Pallet<T> {
             }
         };
         if T::SpecialPrices::convert((&asset, &asset_data)).is_some()
-            || T::DirectPriceCorrelation::convert((&asset, &asset_data)).is_some()
+            || T::PriceCorrelation::convert((&asset, &asset_data)).is_some()
+            || T::LpPriceProvider::get_pool_state(asset).is_some()
         {
             log::error!(
                 target: "eq_oracle",
@@ -621,23 +970,37 @@ impl<T: Config> Pallet<T> {
         return Ok(());
     }
 
-    /// A variant when a price is a single value
-    fn set_the_only_price(asset: T::AssetId, price: T::Price) {
+    /// A variant when a price is a single value observed at `timestamp`
+    fn set_the_only_price(asset: T::AssetId, price: T::Price, timestamp: u64) {
         let block_number = frame_system::Pallet::<T>::block_number();
-        let timestamp = T::UnixTime::now().as_secs();
         let account_id = T::AccountId::decode(&mut TrailingZeroInput::new(b"oracle::price_setter"))
             .expect("Correct default account");
 
+        let price_points = match vec![PricePoint {
+            price,
+            account_id: account_id.clone(),
+            block_number,
+            timestamp,
+        }]
+        .try_into()
+        {
+            Ok(price_points) => price_points,
+            Err(_) => {
+                // unreachable while `integrity_test` checks that `MaxFeeders` is positive
+                log::error!(
+                    target: "eq_oracle",
+                    "MaxFeeders doesn't allow a price point, price is not set. Asset: {:?}, price: {:?}.",
+                    asset,
+                    price
+                );
+                return;
+            }
+        };
         let price_point = PriceData {
             block_number,
             timestamp,
             price,
-            price_points: vec![PricePoint {
-                price,
-                account_id: account_id.clone(),
-                block_number,
-                timestamp,
-            }],
+            price_points,
         };
 
         <PricePoints<T>>::insert(&asset, price_point);
@@ -646,9 +1009,7 @@ impl<T: Config> Pallet<T> {
     }
 
     /// Calculate a median over **sorted** price points
-    fn calc_median_price(
-        data_points: &[PricePoint<T::AccountId, T::BlockNumber, T::Price>],
-    ) -> T::Price {
+    pub(crate) fn calc_median_price(data_points: &[PricePointOf<T>]) -> T::Price {
         let len = data_points.len();
         if len % 2 == 0 {
             (data_points[len / 2 - 1].price + data_points[len / 2].price)
@@ -658,14 +1019,20 @@ impl<T: Config> Pallet<T> {
         }
     }
 
-    fn set_price_inner(who: T::AccountId, asset: T::AssetId, price: T::Price) -> DispatchResult {
+    /// Adds a price observed at `timestamp` and recalculates the median
+    fn set_price_inner(
+        who: T::AccountId,
+        asset: T::AssetId,
+        price: T::Price,
+        timestamp: u64,
+    ) -> DispatchResult {
         let mut median_price = price;
 
         // mutate a price point in the storage by the asset
         <PricePoints<T>>::try_mutate(&asset, |maybe_price_data| -> DispatchResult {
             let mut price_data = maybe_price_data.clone().unwrap_or_default();
             let block_number = frame_system::Pallet::<T>::block_number();
-            let timestamp = T::UnixTime::now().as_secs(); // always same within block
+            let now = T::UnixTime::now().as_secs(); // always same within block
 
             if price_data.block_number == block_number
                 && price_data
@@ -683,12 +1050,41 @@ impl<T: Config> Pallet<T> {
                 frame_support::fail!(Error::<T>::PriceAlreadyAdded)
             }
 
-            // clear outdated price points
-            price_data.price_points.retain(|pp| {
-                pp.timestamp + T::PriceTimeout::get() > timestamp && pp.account_id != who
-            });
+            // clear price points observed too long ago
+            price_data
+                .price_points
+                .retain(|pp| pp.timestamp + T::PriceTimeout::get() > now && pp.account_id != who);
             price_data.block_number = block_number;
-            price_data.timestamp = timestamp;
+
+            // the oldest observation gives way to a new feeder unless the new one is even older
+            if price_data.price_points.len() >= T::MaxFeeders::get() as usize {
+                let oldest = price_data
+                    .price_points
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, pp)| pp.timestamp)
+                    .map(|(index, pp)| (index, pp.timestamp));
+                if let Some((oldest, oldest_timestamp)) = oldest {
+                    if timestamp < oldest_timestamp {
+                        log::warn!(
+                            target: "eq_oracle",
+                            "Too many price points, the price is observed before all of them. Who: {:?}, asset: {:?}, timestamp: {:?}.",
+                            who,
+                            asset,
+                            timestamp
+                        );
+                        frame_support::fail!(Error::<T>::PriceTimeout)
+                    }
+                    let evicted = price_data.price_points.remove(oldest);
+                    log::warn!(
+                        target: "eq_oracle",
+                        "Too many price points, the oldest is evicted. Asset: {:?}, evicted: {:?}, max: {:?}.",
+                        asset,
+                        evicted,
+                        T::MaxFeeders::get()
+                    );
+                }
+            }
 
             // add price point to price_point preserving order by price
             let data_point = PricePoint {
@@ -701,11 +1097,34 @@ impl<T: Config> Pallet<T> {
                 .price_points
                 .binary_search_by(|dp| dp.price.cmp(&price))
             {
-                Ok(pos) | Err(pos) => price_data.price_points.insert(pos, data_point),
+                Ok(pos) | Err(pos) => price_data
+                    .price_points
+                    .try_insert(pos, data_point)
+                    .map_err(|_| {
+                        log::error!(
+                            target: "eq_oracle",
+                            "Too many price points. Who: {:?}, asset: {:?}, max: {:?}.",
+                            who,
+                            asset,
+                            T::MaxFeeders::get()
+                        );
+                        Error::<T>::TooManyPricePoints
+                    })?,
             }
 
+            // the median is as recent as the latest observation it is calculated from
+            price_data.timestamp = price_data
+                .price_points
+                .iter()
+                .map(|pp| pp.timestamp)
+                .max()
+                .unwrap_or(timestamp);
+
             // calculate a median over price points for the moment
             median_price = Self::calc_median_price(&price_data.price_points);
+            if price_data.price != median_price {
+                <UpdatedAssets<T>>::insert(&asset, ());
+            }
             price_data.price = median_price;
 
             log::info!(
@@ -724,12 +1143,275 @@ impl<T: Config> Pallet<T> {
         Ok(())
     }
 
-    /// Remove prices from `who` and recalc median price for each asset
-    pub fn filter_prices_from(who: &T::AccountId) {
-        for asset in T::AssetGetter::get_assets() {
-            <PricePoints<T>>::mutate_exists(asset, |maybe_price_data| {
+    /// Checks that correlations refer only to known assets, that correlated and LP token
+    /// prices don't depend on each other in cycles and that there are no more of them than
+    /// `MaxDerivedPrices`. Returns these assets ordered by dependencies
+    pub fn validate_correlations() -> Result<Vec<T::AssetId>, CorrelationError<T::AssetId>> {
+        let derived_prices = Self::derived_prices();
+
+        for (_, derived_price) in &derived_prices {
+            if let DerivedPrice::Correlated(correlation) = derived_price {
+                if let Some(unknown) = correlation
+                    .dependencies()
+                    .find(|dependency| !T::AssetGetter::exists((*dependency).clone()))
+                {
+                    return Err(CorrelationError::UnknownAsset(unknown.clone()));
+                }
+            }
+        }
+
+        let sorted = correlation::sort_by_dependencies(
+            derived_prices
+                .into_iter()
+                .map(|(asset, derived_price)| (asset, derived_price.dependencies()))
+                .collect(),
+        )?;
+        if sorted.len() > T::MaxDerivedPrices::get() as usize {
+            return Err(CorrelationError::TooManyDerivedPrices);
+        }
+
+        Ok(sorted)
+    }
+
+    /// Correlations and LP pools of assets which prices are derived from other prices
+    fn derived_prices() -> Vec<(T::AssetId, DerivedPrice<T::AssetId, T::Price>)> {
+        T::AssetGetter::get_assets_data()
+            .into_iter()
+            .filter_map(|(asset, asset_data)| {
+                if T::SpecialPrices::convert((&asset, &asset_data)).is_some() {
+                    None
+                } else if let Some(correlation) =
+                    T::PriceCorrelation::convert((&asset, &asset_data))
+                {
+                    Some((asset, DerivedPrice::Correlated(correlation)))
+                } else {
+                    T::LpPriceProvider::get_pool_state(&asset)
+                        .map(|pool| (asset, DerivedPrice::Lp(pool)))
+                }
+            })
+            .collect()
+    }
+
+    /// Returns assets with derived prices ordered by dependencies. The order is stored and
+    /// recalculated only when dependencies change, the previous order is kept if they form a cycle
+    fn derived_price_order(dependencies: Vec<(T::AssetId, Vec<T::AssetId>)>) -> Vec<T::AssetId> {
+        let hash = sp_io::hashing::blake2_256(&dependencies.encode());
+        let (stored_hash, order) = <DerivedPriceOrder<T>>::get();
+        if stored_hash == hash {
+            return order.into_inner();
+        }
+
+        match correlation::sort_by_dependencies(dependencies) {
+            Ok(sorted) => {
+                match sorted.clone().try_into() {
+                    Ok(bounded) => <DerivedPriceOrder<T>>::put((hash, bounded)),
+                    Err(_) => log::error!(
+                        target: "eq_oracle",
+                        "Derived price order is not stored, there are {} derived prices, max is {}",
+                        sorted.len(),
+                        T::MaxDerivedPrices::get()
+                    ),
+                }
+                sorted
+            }
+            Err(err) => {
+                log::error!(
+                    target: "eq_oracle",
+                    "Derived prices can't be ordered, the previous order is used: {:?}",
+                    err
+                );
+                order.into_inner()
+            }
+        }
+    }
+
+    /// Observation time of a price derived from `dependencies`, the oldest of their prices
+    fn derived_timestamp(dependencies: &[T::AssetId]) -> u64 {
+        dependencies
+            .iter()
+            .filter_map(|dependency| <PricePoints<T>>::get(dependency).map(|pd| pd.timestamp))
+            .min()
+            .unwrap_or_else(|| T::UnixTime::now().as_secs())
+    }
+
+    /// Recalculates correlated and LP token prices depending on prices updated in this block.
+    /// Dependencies are evaluated first, so chains are resolved within a block
+    fn update_derived_prices() {
+        let now = T::UnixTime::now().as_secs();
+        // rewritten with the same price only if dependencies are observed later
+        let is_outdated = |price_data: &PriceDataOf<T>, timestamp: u64| {
+            price_data.timestamp + T::PriceRefreshInterval::get() <= now
+                && price_data.timestamp < timestamp
+        };
+        // assets with median price changed in this block
+        let mut updated_assets: Vec<T::AssetId> = <UpdatedAssets<T>>::drain()
+            .map(|(asset, _)| asset)
+            .collect();
+
+        let derived_prices = Self::derived_prices();
+        let order = Self::derived_price_order(
+            derived_prices
+                .iter()
+                .map(|(asset, derived_price)| (asset.clone(), derived_price.dependencies()))
+                .collect(),
+        );
+        let mut derived_prices: BTreeMap<Vec<u8>, (T::AssetId, DerivedPrice<_, _>)> =
+            derived_prices
+                .into_iter()
+                .map(|(asset, derived_price)| (asset.encode(), (asset, derived_price)))
+                .collect();
+
+        for asset in order {
+            let derived_price = match derived_prices.remove(&asset.encode()) {
+                Some((_, derived_price)) => derived_price,
+                None => continue,
+            };
+            let timestamp = Self::derived_timestamp(&derived_price.dependencies());
+
+            match derived_price {
+                DerivedPrice::Correlated(correlation) => {
+                    // rates may change every block
+                    let should_update = correlation.with_rate
+                        || correlation
+                            .dependencies()
+                            .any(|dependency| updated_assets.contains(dependency))
+                        || <PricePoints<T>>::get(&asset)
+                            .map_or(true, |pd| is_outdated(&pd, timestamp));
+                    if !should_update {
+                        continue;
+                    }
+
+                    let rate = if correlation.with_rate {
+                        Self::get_rate(&asset)
+                    } else {
+                        None
+                    };
+                    match correlation.evaluate(rate, |dependency| {
+                        <Self as PriceGetter>::get_price(dependency.clone())
+                    }) {
+                        Ok(price) => {
+                            Self::set_the_only_price(asset.clone(), price, timestamp);
+                            updated_assets.push(asset);
+                        }
+                        Err(err) => log::debug!(
+                            target: "eq_oracle",
+                            "Correlated price is not available. Asset: {:?}, error: {:?}",
+                            asset,
+                            err
+                        ),
+                    }
+                }
+                DerivedPrice::Lp(pool) => match Self::calc_lp_price(&pool) {
+                    Ok(price) => {
+                        let should_update = <PricePoints<T>>::get(&asset)
+                            .map_or(true, |pd| pd.price != price || is_outdated(&pd, timestamp));
+                        if should_update {
+                            Self::set_the_only_price(asset.clone(), price, timestamp);
+                            updated_assets.push(asset);
+                        }
+                    }
+                    Err(err) => log::debug!(
+                        target: "eq_oracle",
+                        "LP token price is not available. Asset: {:?}, error: {:?}",
+                        asset,
+                        err
+                    ),
+                },
+            }
+        }
+
+        // derived assets not ordered because of a cycle keep their prices
+        for (_, (asset, _)) in derived_prices {
+            log::error!(
+                target: "eq_oracle",
+                "Derived price is not updated, asset is out of the dependency order: {:?}",
+                asset
+            );
+        }
+    }
+
+    /// LP token price: virtual price (invariant / LP supply) multiplied by the minimal
+    /// price of pool assets. It doesn't depend on pool balances, so it can't be moved
+    /// by swaps within the pool
+    fn calc_lp_price(pool: &LpPoolState<T::AssetId, u128>) -> Result<T::Price, DispatchError> {
+        if pool.assets.len() != pool.reserves.len()
+            || pool.reserves.iter().any(|reserve| reserve.is_zero())
+        {
+            frame_support::fail!(Error::<T>::InvalidPoolState);
+        }
+
+        let virtual_price = T::Price::checked_from_rational(pool.invariant, pool.lp_supply)
+            .ok_or(Error::<T>::InvalidPoolState)?;
+        let min_price = pool
+            .assets
+            .iter()
+            .map(|asset| <Self as PriceGetter>::get_price(asset.clone()))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .min()
+            .ok_or(Error::<T>::InvalidPoolState)?;
+
+        virtual_price
+            .checked_mul(&min_price)
+            .ok_or_else(|| ArithmeticError::Overflow.into())
+    }
+
+    /// Returns current rate of a derivative asset: stored one or from `RateProvider`
+    pub fn get_rate(asset: &T::AssetId) -> Option<T::Price> {
+        <Rates<T>>::get(asset).or_else(|| T::RateProvider::get_rate(asset))
+    }
+
+    /// Returns a price point of `who` for the asset if it is stored
+    pub fn price_points_of(asset: &T::AssetId, who: &T::AccountId) -> Option<PricePointOf<T>> {
+        <PricePoints<T>>::get(asset)?
+            .price_points
+            .into_iter()
+            .find(|pp| &pp.account_id == who)
+    }
+
+    /// Returns aggregated price data of the asset with all its price points.
+    /// Reference implementation of `EqOracleApi::get_price_data`
+    pub fn price_data_view(
+        asset: &T::AssetId,
+    ) -> Option<PriceDataView<T::AccountId, T::BlockNumber, T::Price>> {
+        <PricePoints<T>>::get(asset).map(Into::into)
+    }
+
+    /// Returns the last submission of `who` for every asset.
+    /// Reference implementation of `EqOracleApi::feeder_status`
+    pub fn feeder_status(
+        who: &T::AccountId,
+    ) -> Vec<(T::AssetId, FeederSubmission<T::BlockNumber, T::Price>)> {
+        let current_time = T::UnixTime::now().as_secs();
+
+        T::AssetGetter::get_assets()
+            .into_iter()
+            .filter_map(|asset| {
+                let price_point = Self::price_points_of(&asset, who)?;
+
+                Some((
+                    asset,
+                    FeederSubmission {
+                        block_number: price_point.block_number,
+                        timestamp: price_point.timestamp,
+                        price: price_point.price,
+                        is_stale: price_point.timestamp + T::PriceTimeout::get() <= current_time,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Remove prices from `who` and recalc median price for each asset.
+    /// Returns the consumed weight to be charged by the caller
+    pub fn filter_prices_from(who: &T::AccountId) -> Weight {
+        let assets = T::AssetGetter::get_assets();
+        let weight = T::WeightInfo::filter_prices_from(assets.len() as u32);
+        for asset in assets {
+            <PricePoints<T>>::mutate_exists(&asset, |maybe_price_data| {
                 if let Some(PriceData {
                     price,
+                    timestamp,
                     price_points,
                     ..
                 }) = maybe_price_data.as_mut()
@@ -738,12 +1420,22 @@ impl<T: Config> Pallet<T> {
                     price_points.retain(|pp| &pp.account_id != who);
                     if price_points.len() == 0 {
                         *maybe_price_data = None;
+                        <UpdatedAssets<T>>::insert(&asset, ());
                     } else if price_points.len() != initial_len {
                         *price = Self::calc_median_price(price_points);
+                        // the median is as recent as the latest observation left
+                        *timestamp = price_points
+                            .iter()
+                            .map(|pp| pp.timestamp)
+                            .max()
+                            .unwrap_or(*timestamp);
+                        <UpdatedAssets<T>>::insert(&asset, ());
                     }
                 };
             });
         }
+
+        weight
     }
 }
 
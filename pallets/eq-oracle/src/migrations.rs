@@ -0,0 +1,167 @@
+//! Storage migrations of the oracle pallet
+
+use super::*;
+#[cfg(feature = "try-runtime")]
+use frame_support::traits::OnRuntimeUpgradeHelpersExt;
+use frame_support::{
+    traits::{GetStorageVersion, OnRuntimeUpgrade, PalletInfoAccess},
+    weights::Weight,
+};
+
+/// Bounds `PriceData.price_points` with `MaxFeeders`
+pub mod v1 {
+    use super::*;
+
+    /// `PriceData` layout before v1, price points were stored in an unbounded `Vec`
+    #[derive(Encode, Decode)]
+    struct OldPriceData<AccountId, BlockNumber, Price> {
+        block_number: BlockNumber,
+        timestamp: u64,
+        price: Price,
+        price_points: Vec<PricePoint<AccountId, BlockNumber, Price>>,
+    }
+
+    /// Moves `PricePoints` to bounded storage.
+    /// If an asset has more than `MaxFeeders` price points, the most recent ones are kept
+    /// and the median price and timestamp are recalculated over them.
+    /// Entries that can't be decoded are logged and left untouched.
+    /// Must be added to the runtime `OnRuntimeUpgrade` list.
+    pub struct MigrateToV1<T>(sp_std::marker::PhantomData<T>);
+
+    impl<T: Config> OnRuntimeUpgrade for MigrateToV1<T> {
+        fn on_runtime_upgrade() -> Weight {
+            let on_chain_version = Pallet::<T>::on_chain_storage_version();
+            if on_chain_version >= 1 {
+                log::info!(
+                    target: "eq_oracle",
+                    "{} storage is already at {:?}, migration skipped",
+                    <Pallet<T> as PalletInfoAccess>::name(),
+                    on_chain_version
+                );
+                return T::DbWeight::get().reads(1);
+            }
+
+            let max_feeders = T::MaxFeeders::get() as usize;
+            let assets: Vec<T::AssetId> = <PricePoints<T>>::iter_keys().collect();
+            let mut translated = 0u64;
+            let mut truncated = 0u64;
+            let mut failed = 0u64;
+
+            for asset in assets {
+                let key = <PricePoints<T>>::hashed_key_for(&asset);
+                let old = frame_support::storage::unhashed::get::<
+                    OldPriceData<T::AccountId, T::BlockNumber, T::Price>,
+                >(&key);
+                let OldPriceData {
+                    block_number,
+                    mut timestamp,
+                    mut price,
+                    mut price_points,
+                } = match old {
+                    Some(old) => old,
+                    None => {
+                        failed += 1;
+                        log::error!(
+                            target: "eq_oracle",
+                            "Price data of asset {:?} can't be decoded, it is left as is",
+                            asset
+                        );
+                        continue;
+                    }
+                };
+
+                if price_points.len() > max_feeders {
+                    truncated += 1;
+                    log::warn!(
+                        target: "eq_oracle",
+                        "Asset {:?} has {:?} price points, only {:?} most recent are kept",
+                        asset,
+                        price_points.len(),
+                        max_feeders
+                    );
+
+                    // price points are sorted by price, restore the order after truncation
+                    price_points.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+                    price_points.truncate(max_feeders);
+                    price_points.sort_by(|a, b| a.price.cmp(&b.price));
+
+                    if !price_points.is_empty() {
+                        price = Pallet::<T>::calc_median_price(&price_points);
+                    }
+                    if let Some(latest) = price_points.iter().map(|pp| pp.timestamp).max() {
+                        timestamp = latest;
+                    }
+                }
+
+                // fits after the truncation above
+                let price_points = match price_points.try_into() {
+                    Ok(price_points) => price_points,
+                    Err(_) => {
+                        failed += 1;
+                        log::error!(
+                            target: "eq_oracle",
+                            "Price points of asset {:?} exceed MaxFeeders, they are left as is",
+                            asset
+                        );
+                        continue;
+                    }
+                };
+                <PricePoints<T>>::insert(
+                    &asset,
+                    PriceData {
+                        block_number,
+                        timestamp,
+                        price,
+                        price_points,
+                    },
+                );
+                translated += 1;
+            }
+
+            STORAGE_VERSION.put::<Pallet<T>>();
+            log::info!(
+                target: "eq_oracle",
+                "{} migrated to {:?}: {:?} assets, {:?} truncated, {:?} failed",
+                <Pallet<T> as PalletInfoAccess>::name(),
+                STORAGE_VERSION,
+                translated,
+                truncated,
+                failed
+            );
+
+            T::DbWeight::get().reads_writes(translated + failed + 1, translated + 1)
+        }
+
+        #[cfg(feature = "try-runtime")]
+        fn pre_upgrade() -> Result<(), &'static str> {
+            let count = <PricePoints<T>>::iter_keys().count() as u32;
+            Self::set_temp_storage(count, "price_points_count");
+            Ok(())
+        }
+
+        #[cfg(feature = "try-runtime")]
+        fn post_upgrade() -> Result<(), &'static str> {
+            frame_support::ensure!(
+                Pallet::<T>::on_chain_storage_version() == STORAGE_VERSION,
+                "storage version is not updated"
+            );
+
+            let count_before: u32 = Self::get_temp_storage("price_points_count")
+                .ok_or("price points count is not found")?;
+            let mut count_after = 0u32;
+            for price_data in <PricePoints<T>>::iter_values() {
+                count_after += 1;
+                frame_support::ensure!(
+                    price_data.price_points.len() <= T::MaxFeeders::get() as usize,
+                    "price points exceed MaxFeeders"
+                );
+            }
+            frame_support::ensure!(
+                count_before == count_after,
+                "some price data was lost during migration"
+            );
+
+            Ok(())
+        }
+    }
+}
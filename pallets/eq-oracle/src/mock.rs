@@ -2,18 +2,39 @@
 
 use super::*;
 use crate as eq_oracle;
-pub use crate::price_source::{json::JsonPriceSource, PriceSourceStruct};
+pub use crate::price_source::{
+    http_client::{HttpMethod, HttpRequest, HttpTransport, RequestError},
+    json::JsonPriceSource,
+    ChainlinkPriceSource, EvmPriceSource, GraphqlPriceSource, PancakePriceSource,
+    PriceSourceStruct, SubstratePriceSource,
+};
 use core::cell::RefCell;
 use frame_support::parameter_types;
-use frame_support::traits::Everything;
+use frame_support::traits::{Everything, Hooks};
+use parking_lot::RwLock;
 use primitives::Asset;
-use sp_core::{sr25519::Signature, H256};
+use sp_core::{
+    offchain::{
+        testing::{
+            OffchainState, PendingRequest, PoolState, TestOffchainExt, TestTransactionPoolExt,
+        },
+        OffchainDbExt, OffchainWorkerExt, TransactionPoolExt,
+    },
+    sr25519::Signature,
+    H256,
+};
+use sp_keystore::{testing::KeyStore, KeystoreExt, SyncCryptoStore};
 use sp_runtime::traits::One;
 use sp_runtime::{
     testing::{Header, TestXt},
-    traits::{BlakeTwo256, Extrinsic as ExtrinsicT, IdentifyAccount, IdentityLookup, Verify},
+    traits::{
+        BlakeTwo256, Extrinsic as ExtrinsicT, IdentifyAccount, IdentityLookup, ValidateUnsigned,
+        Verify,
+    },
+    transaction_validity::TransactionSource,
 };
 use sp_runtime::{DispatchError, FixedI64};
+use std::sync::Arc;
 
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
 type Block = frame_system::mocking::MockBlock<Test>;
@@ -33,7 +54,14 @@ pub mod asset {
     pub const HDOT: Asset = Asset(0x68646f74);
 
     pub const LP_XDOT: Asset = Asset(0x786C707430);
+
     pub const LP_CURVE: Asset = Asset(0x6C707430);
+
+    pub const HXDOT: Asset = Asset(0x68786474);
+    pub const CMP: Asset = Asset(0x636d70);
+    pub const LDOT: Asset = Asset(0x6c646f74);
+    pub const LP_POOL: Asset = Asset(0x6C70706C);
+    pub const SLP_POOL: Asset = Asset(0x736C70);
 }
 
 frame_support::construct_runtime!(
@@ -215,8 +243,12 @@ where
 }
 
 parameter_types! {
-    pub const PriceTimeout: u64 = 1;
+    pub static PriceTimeout: u64 = 1;
     pub const MedianPriceTimeout: u64 = 60 * 60 * 2;
+    pub const MaxFeeders: u32 = 8;
+    pub const MaxDerivedPrices: u32 = 16;
+    pub const PriceRefreshInterval: u64 = 60 * 60;
+    pub const PriceTimestampTolerance: u64 = 60;
 }
 
 pub struct FinancialMock;
@@ -230,17 +262,104 @@ parameter_types! {
     pub const FinancialRecalcPeriodBlocks: u64  = (1000 * 60 * 60 * 4) as u64 / 6000;
 }
 
-pub struct DirectPriceCorrelation;
-impl<'a> Convert<(&'a Asset, &'a ()), Option<(Asset, FixedI64)>> for DirectPriceCorrelation {
-    fn convert((a, _): (&'a Asset, &'a ())) -> Option<(Asset, FixedI64)> {
+thread_local! {
+    pub static CORRELATION_CYCLE: RefCell<bool> = RefCell::new(false);
+}
+
+pub struct PriceCorrelation;
+
+impl PriceCorrelation {
+    /// Makes DOT correlated with HXDOT: DOT -> HXDOT -> XDOT -> DOT
+    pub fn set_cycle(value: bool) {
+        CORRELATION_CYCLE.with(|cycle| *cycle.borrow_mut() = value);
+    }
+}
+
+impl<'a> Convert<(&'a Asset, &'a ()), Option<Correlation<Asset, FixedI64>>> for PriceCorrelation {
+    fn convert((a, _): (&'a Asset, &'a ())) -> Option<Correlation<Asset, FixedI64>> {
         match *a {
-            // asset::EQ => Some((asset::DOT, FixedI64::saturating_from_rational(7, 10))),
-            asset::XDOT | asset::HDOT => Some((asset::DOT, FixedI64::one())),
+            // asset::EQ => Some(Correlation::direct(asset::DOT, FixedI64::saturating_from_rational(7, 10))),
+            asset::XDOT | asset::HDOT => Some(Correlation::direct(asset::DOT, FixedI64::one())),
+            asset::HXDOT => Some(Correlation::direct(
+                asset::XDOT,
+                FixedI64::saturating_from_rational(1, 2),
+            )),
+            // CMP = 2 * ETH * DOT / BTC
+            asset::CMP => Some(Correlation::new(
+                FixedI64::saturating_from_integer(2),
+                vec![asset::ETH, asset::DOT],
+                vec![asset::BTC],
+            )),
+            asset::LDOT => Some(Correlation::with_rate(asset::DOT)),
+            // depends on LP token price
+            asset::SLP_POOL => Some(Correlation::direct(
+                asset::LP_POOL,
+                FixedI64::saturating_from_integer(2),
+            )),
+            asset::DOT if CORRELATION_CYCLE.with(|cycle| *cycle.borrow()) => {
+                Some(Correlation::direct(asset::HXDOT, FixedI64::one()))
+            }
             _ => None,
         }
     }
 }
 
+thread_local! {
+    pub static RATES: RefCell<Vec<(Asset, FixedI64)>> = RefCell::new(vec![]);
+}
+
+pub struct RateProviderMock;
+
+impl RateProviderMock {
+    pub fn set_rate(asset: Asset, rate: FixedI64) {
+        RATES.with(|rates| {
+            let mut rates = rates.borrow_mut();
+            rates.retain(|(a, _)| *a != asset);
+            rates.push((asset, rate));
+        });
+    }
+}
+
+impl primitives::RateProvider<Asset, FixedI64> for RateProviderMock {
+    fn get_rate(asset: &Asset) -> Option<FixedI64> {
+        RATES.with(|rates| {
+            rates
+                .borrow()
+                .iter()
+                .find(|(a, _)| a == asset)
+                .map(|(_, rate)| *rate)
+        })
+    }
+}
+
+thread_local! {
+    pub static LP_POOLS: RefCell<Vec<(Asset, LpPoolState<Asset, u128>)>> = RefCell::new(vec![]);
+}
+
+pub struct LpPriceProviderMock;
+
+impl LpPriceProviderMock {
+    pub fn set_pool_state(lp_asset: Asset, pool: LpPoolState<Asset, u128>) {
+        LP_POOLS.with(|pools| {
+            let mut pools = pools.borrow_mut();
+            pools.retain(|(a, _)| *a != lp_asset);
+            pools.push((lp_asset, pool));
+        });
+    }
+}
+
+impl LpPriceProvider<Asset, u128> for LpPriceProviderMock {
+    fn get_pool_state(lp_asset: &Asset) -> Option<LpPoolState<Asset, u128>> {
+        LP_POOLS.with(|pools| {
+            pools
+                .borrow()
+                .iter()
+                .find(|(a, _)| a == lp_asset)
+                .map(|(_, pool)| pool.clone())
+        })
+    }
+}
+
 pub struct SpecialPrices;
 impl<'a> Convert<(&'a Asset, &'a ()), Option<FixedI64>> for SpecialPrices {
     fn convert((a, _): (&'a Asset, &'a ())) -> Option<FixedI64> {
@@ -260,15 +379,29 @@ impl eq_oracle::Config for Test {
     type Whitelist = Whitelist;
     type MedianPriceTimeout = MedianPriceTimeout;
     type PriceTimeout = PriceTimeout;
+    type MaxFeeders = MaxFeeders;
+    type MaxDerivedPrices = MaxDerivedPrices;
+    type PriceRefreshInterval = PriceRefreshInterval;
+    type PriceTimestampTolerance = PriceTimestampTolerance;
     type OnPriceSet = FinancialMock;
+    type RateProvider = RateProviderMock;
+    type RateOrigin = frame_system::EnsureRoot<AccountId>;
+    type LpPriceProvider = LpPriceProviderMock;
     type UnsignedPriority = UnsignedPriority;
     type AssetGetter = AssetGetterMock;
     type WeightInfo = ();
     type UnsignedLifetimeInBlocks = UnsignedLifetimeInBlocks;
     type AdditionalParamsValidator = ();
     type Price = FixedI64;
-    type PriceSource = (PriceSourceStruct<JsonPriceSource<Asset, ()>>,);
-    type DirectPriceCorrelation = DirectPriceCorrelation;
+    type PriceSource = (
+        PriceSourceStruct<JsonPriceSource<Asset, ()>>,
+        PriceSourceStruct<PancakePriceSource<Asset, (), Oracle>>,
+        PriceSourceStruct<EvmPriceSource<Asset, ()>>,
+        PriceSourceStruct<ChainlinkPriceSource<Asset, ()>>,
+        PriceSourceStruct<SubstratePriceSource<Asset, ()>>,
+        PriceSourceStruct<GraphqlPriceSource<Asset, ()>>,
+    );
+    type PriceCorrelation = PriceCorrelation;
     type SpecialPrices = SpecialPrices;
 }
 
@@ -292,3 +425,190 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
 
     t.into()
 }
+
+thread_local! {
+    pub static HTTP_RESPONSES: RefCell<Vec<(HttpMethod, String, Result<String, RequestError>)>> =
+        RefCell::new(vec![]);
+    pub static HTTP_REQUESTS: RefCell<Vec<HttpRequest>> = RefCell::new(vec![]);
+}
+
+/// Http transport replaying expected responses and recording sent requests
+#[derive(Clone, Debug)]
+pub struct HttpMock;
+
+impl HttpMock {
+    /// Adds a response to the next request with the `method` and the `url`
+    pub fn expect(method: HttpMethod, url: &str, response: Result<&str, RequestError>) {
+        HTTP_RESPONSES.with(|responses| {
+            responses
+                .borrow_mut()
+                .push((method, url.into(), response.map(Into::into)))
+        });
+    }
+
+    /// Returns all sent requests in their order
+    pub fn requests() -> Vec<HttpRequest> {
+        HTTP_REQUESTS.with(|requests| requests.borrow().clone())
+    }
+
+    /// Returns true if all expected responses are replayed
+    pub fn is_done() -> bool {
+        HTTP_RESPONSES.with(|responses| responses.borrow().is_empty())
+    }
+}
+
+impl HttpTransport for HttpMock {
+    fn send_all(
+        requests: &[HttpRequest],
+        _deadline: sp_runtime::offchain::Timestamp,
+    ) -> Vec<Result<String, RequestError>> {
+        requests
+            .iter()
+            .map(|request| {
+                HTTP_REQUESTS.with(|requests| requests.borrow_mut().push(request.clone()));
+                HTTP_RESPONSES.with(|responses| {
+                    let mut responses = responses.borrow_mut();
+                    let index = responses
+                        .iter()
+                        .position(|(method, url, _)| {
+                            *method == request.method && *url == request.url
+                        })
+                        .unwrap_or_else(|| {
+                            panic!("Unexpected request: {:?} {}", request.method, request.url)
+                        });
+                    responses.remove(index).2
+                })
+            })
+            .collect()
+    }
+}
+
+/// Test externalities with `PriceTimeout` longer than `PriceTimestampTolerance`,
+/// as `integrity_test` requires, for prices fed with their observation time
+pub fn new_observation_test_ext() -> sp_io::TestExternalities {
+    PriceTimeout::set(&120);
+    new_test_ext()
+}
+
+pub fn new_offchain_test_ext() -> sp_io::TestExternalities {
+    new_offchain_test_ext_with_http(Vec::new())
+}
+
+/// Offchain test externalities answering the `requests` in their order
+pub fn new_offchain_test_ext_with_http(requests: Vec<PendingRequest>) -> sp_io::TestExternalities {
+    let (offchain, state) = TestOffchainExt::new();
+    for request in requests {
+        state.write().expect_request(request);
+    }
+    let mut t = new_test_ext();
+    t.register_extension(OffchainDbExt::new(offchain.clone()));
+    t.register_extension(OffchainWorkerExt::new(offchain));
+
+    t
+}
+
+pub type OraclePricePayload = PricePayload<sp_core::sr25519::Public, u64, Asset, FixedI64>;
+
+/// Offchain worker test environment: an oracle key in the keystore,
+/// expected http requests and a transaction pool collecting submitted prices
+pub struct OffchainWorkerTest {
+    pub ext: sp_io::TestExternalities,
+    pub offchain: Arc<RwLock<OffchainState>>,
+    pub pool: Arc<RwLock<PoolState>>,
+    pub public: sp_core::sr25519::Public,
+}
+
+impl OffchainWorkerTest {
+    /// Creates an environment with a new oracle key, `whitelisted` adds its account to the whitelist
+    pub fn new(whitelisted: bool) -> Self {
+        let (offchain, offchain_state) = TestOffchainExt::new();
+        let (pool, pool_state) = TestTransactionPoolExt::new();
+        let keystore = KeyStore::new();
+        let public = SyncCryptoStore::sr25519_generate_new(&keystore, crate::KEY_TYPE, None)
+            .expect("Key is generated");
+        if whitelisted {
+            Whitelist::add_to_whitelist(&public.into_account());
+        }
+
+        let mut ext = new_test_ext();
+        ext.register_extension(OffchainDbExt::new(offchain.clone()));
+        ext.register_extension(OffchainWorkerExt::new(offchain));
+        ext.register_extension(TransactionPoolExt::new(pool));
+        ext.register_extension(KeystoreExt(Arc::new(keystore)));
+
+        Self {
+            ext,
+            offchain: offchain_state,
+            pool: pool_state,
+            public,
+        }
+    }
+
+    /// Expects an http request, requests are answered in their order
+    pub fn expect_request(&self, request: PendingRequest) {
+        self.offchain.write().expect_request(request);
+    }
+
+    /// Stores a raw value in the persistent offchain storage
+    pub fn set_storage(&mut self, key: &[u8], value: &[u8]) {
+        self.ext.execute_with(|| {
+            sp_io::offchain::local_storage_set(
+                sp_runtime::offchain::StorageKind::PERSISTENT,
+                key,
+                value,
+            )
+        });
+    }
+
+    /// Runs the offchain worker at `block_number` and returns submitted price payloads
+    pub fn run_offchain_worker(
+        &mut self,
+        block_number: u64,
+    ) -> Vec<(OraclePricePayload, Signature)> {
+        self.ext.execute_with(|| {
+            System::set_block_number(block_number);
+            Oracle::offchain_worker(block_number);
+        });
+        self.take_transactions()
+    }
+
+    /// Validates and dispatches price transactions like a block author does
+    pub fn apply(&mut self, transactions: Vec<(OraclePricePayload, Signature)>) {
+        self.ext.execute_with(|| {
+            for (payload, signature) in transactions {
+                let call = crate::Call::set_price_unsigned_v2 {
+                    payload: payload.clone(),
+                    signature: signature.clone(),
+                };
+                assert!(
+                    Oracle::validate_unsigned(TransactionSource::External, &call).is_ok(),
+                    "Invalid transaction: {:?}",
+                    call
+                );
+                assert!(Oracle::set_price_unsigned_v2(Origin::none(), payload, signature).is_ok());
+            }
+        });
+    }
+
+    pub fn execute_with<R>(&mut self, execute: impl FnOnce() -> R) -> R {
+        self.ext.execute_with(execute)
+    }
+
+    fn take_transactions(&self) -> Vec<(OraclePricePayload, Signature)> {
+        self.pool
+            .write()
+            .transactions
+            .drain(..)
+            .map(|raw| {
+                let tx = Extrinsic::decode(&mut &raw[..]).expect("Transaction is decoded");
+                assert_eq!(tx.signature, None);
+                match tx.call {
+                    Call::Oracle(crate::Call::set_price_unsigned_v2 { payload, signature }) => {
+                        (payload, signature)
+                    }
+                    call => panic!("Unexpected call: {:?}", call),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,722 @@
+//! Typed offchain configuration document
+
+use crate::price_source::{
+    evm::{from_hex, AbiType},
+    http_client::percent_encode,
+};
+use alloc::string::String;
+use core::convert::TryFrom;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use sp_runtime::{FixedPointNumber, FixedU128};
+use sp_std::{collections::btree_map::BTreeMap, vec::Vec};
+use utils::fixed::parse_decimal;
+
+/// Latest supported version of `OracleOffchainConfig`, it's increased with every layout change.
+/// Documents of older versions are migrated on reading, see `VersionProbe::upgrade_to`:
+/// - 1: `source_type`, `price_periodicity`, `custom_query`, `source_assets`
+/// - 2: `pancake`
+/// - 3: `evm`
+/// - 4: `chainlink`
+/// - 5: `substrate`
+/// - 6: `graphql`
+/// - 7: `http` with headers and query parameters, `graphql.headers` moved to `http.graphql.headers`
+/// - 8: `timeout_ms`, `retries` and `backoff_ms` of `http`
+/// - 9: `price_bounds`
+/// - 10: `max_price_age`
+pub const CONFIG_VERSION: u32 = 10;
+
+/// Defines how to interpret a value received from a price source
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceStrategy {
+    /// Value from the source is a price
+    Price,
+    /// Value from the source is a reciprocal of a price: price = 1 / value
+    Reverse,
+}
+
+impl Default for PriceStrategy {
+    fn default() -> Self {
+        PriceStrategy::Price
+    }
+}
+
+/// Plausible prices of an asset as decimal strings, e.g. `{"min":"0.9","max":"1.1"}`.
+/// Prices outside of the bounds are not fed
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PriceBounds {
+    /// Minimum price, not limited if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<String>,
+    /// Maximum price, not limited if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<String>,
+}
+
+/// Settings of the `pancake` price source
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PancakeSettings {
+    /// Url of an EVM node JSON-RPC api, e.g. `https://bsc-dataseed.binance.org/`
+    pub node_url: String,
+    /// Pools by LP asset symbol
+    pub pools: BTreeMap<String, PancakePool>,
+}
+
+/// PancakeSwap (Uniswap V2) pair contract of an LP asset
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PancakePool {
+    /// Pair contract address, e.g. `0x58f876857a02d6762e0101bb5c46a8c1ed44dc16`
+    pub contract: String,
+    /// Asset symbol of the pair `token0()`
+    pub token_0: String,
+    /// Asset symbol of the pair `token1()`
+    pub token_1: String,
+}
+
+/// Settings of the `evm` price source
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EvmSettings {
+    /// Url of an EVM node JSON-RPC api, e.g. `https://bsc-dataseed.binance.org/`
+    pub node_url: String,
+    /// Price feeds by asset symbol
+    pub feeds: BTreeMap<String, EvmFeed>,
+}
+
+/// View function returning a price of an asset
+///
+/// Example of a Chainlink aggregator:
+/// `{"contract":"0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419","function":"latestRoundData()",
+/// "outputs":["uint80","int256","uint256","uint256","uint80"],"output_index":1,"decimals":8}`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EvmFeed {
+    /// Contract address
+    pub contract: String,
+    /// Signature of a function without arguments, e.g. `latestAnswer()`
+    pub function: String,
+    /// Static types of returned values, e.g. `["int256"]`
+    pub outputs: Vec<String>,
+    /// Index of the price in returned values
+    pub output_index: u32,
+    /// Decimals of the returned price
+    pub decimals: u32,
+}
+
+/// Settings of the `chainlink` price source
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChainlinkSettings {
+    /// Url of an EVM node JSON-RPC api, e.g. `https://bsc-dataseed.binance.org/`
+    pub node_url: String,
+    /// Maximum age of a round in seconds, older rounds are rejected
+    pub max_round_age: u64,
+    /// Aggregator contract addresses by asset symbol
+    pub aggregators: BTreeMap<String, String>,
+}
+
+/// Settings of the `substrate` price source
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubstrateSettings {
+    /// Url of a remote Substrate node JSON-RPC api, e.g. `https://rpc.polkadot.io`
+    pub node_url: String,
+    /// Price feeds by asset symbol
+    pub feeds: BTreeMap<String, SubstrateFeed>,
+}
+
+/// RPC method of a remote Substrate node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubstrateMethod {
+    /// `state_getStorage` of a storage key
+    Storage,
+    /// `state_call` of a runtime api function
+    Call,
+}
+
+impl Default for SubstrateMethod {
+    fn default() -> Self {
+        SubstrateMethod::Storage
+    }
+}
+
+/// Layout of a SCALE encoded value
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubstrateDecoder {
+    /// `u128` price with `decimals`
+    U128,
+    /// `u64` price with `decimals`
+    U64,
+    /// `(u128, u128)` reserves of base and quote assets of a pool, price is `quote / base`
+    Reserves,
+}
+
+impl Default for SubstrateDecoder {
+    fn default() -> Self {
+        SubstrateDecoder::U128
+    }
+}
+
+/// Remote value with a price of an asset
+///
+/// Example of a pool of a DEX pallet:
+/// `{"method":"storage","key":"0x...{$}","decoder":"reserves","offset":0,"decimals":10,"quote_decimals":12}`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubstrateFeed {
+    pub method: SubstrateMethod,
+    /// Hex storage key for `storage` or hex call data for `call`,
+    /// `{$}` is replaced with the hex encoded asset symbol
+    pub key: String,
+    /// Runtime api function for `call`, e.g. `DexApi_get_reserves`
+    pub function: String,
+    pub decoder: SubstrateDecoder,
+    /// Amount of bytes skipped before the value, e.g. `1` for an `Option` returned by `state_call`
+    pub offset: u32,
+    /// Decimals of the value or of the base reserve
+    pub decimals: u32,
+    /// Decimals of the quote reserve
+    pub quote_decimals: u32,
+}
+
+/// Settings of the `graphql` price source
+///
+/// Example of a subgraph:
+/// `{"url":"https://api.thegraph.com/subgraphs/name/uniswap/uniswap-v3",
+/// "query":"query($symbol: String!) { tokens(where: {symbol: $symbol}) { derivedUSD } }",
+/// "variables":{"symbol":"{$}"},"price_path":"data.tokens[0].derivedUSD","uppercase":true}`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphqlSettings {
+    /// Url of a GraphQL endpoint
+    pub url: String,
+    /// Query template, `{$}` is replaced with an asset symbol
+    pub query: String,
+    /// Query variables, `{$}` in values is replaced with an asset symbol
+    pub variables: BTreeMap<String, String>,
+    /// Path to a price in a response, e.g. `data.pair.token0Price`
+    pub price_path: String,
+    /// Substitute upper case asset symbols, lower case by default
+    pub uppercase: bool,
+}
+
+/// Request timeout if it's not set in `HttpOptions`
+pub const DEFAULT_HTTP_TIMEOUT_MS: u64 = 5_000;
+/// Delay before the first retry if it's not set in `HttpOptions`, doubled for every next retry
+pub const DEFAULT_HTTP_BACKOFF_MS: u64 = 500;
+/// Maximum amount of retries of a request
+pub const MAX_HTTP_RETRIES: u32 = 3;
+/// Maximum time of all requests of a price source run with their retries,
+/// so a feed round fits an offchain worker run
+pub const MAX_HTTP_REQUEST_TIME_MS: u64 = 20_000;
+
+/// Http options of a price source, e.g. api keys of pro tiers of public apis, timeouts and retries.
+/// Header and parameter values are secrets, they are never written to logs.
+///
+/// Example of CoinGecko Pro:
+/// `{"headers":{"x-cg-pro-api-key":"..."},"timeout_ms":3000,"retries":2}`
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpOptions {
+    /// Additional http headers of every request
+    pub headers: BTreeMap<String, String>,
+    /// Additional query parameters of every request, e.g. `{"api_key":"..."}`
+    pub query: BTreeMap<String, String>,
+    /// Timeout of a single attempt, `DEFAULT_HTTP_TIMEOUT_MS` if 0
+    pub timeout_ms: u64,
+    /// Amount of retries after timeouts, io errors, 5xx and 429 responses, at most `MAX_HTTP_RETRIES`
+    pub retries: u32,
+    /// Delay before the first retry, `DEFAULT_HTTP_BACKOFF_MS` if 0
+    pub backoff_ms: u64,
+}
+
+impl core::fmt::Debug for HttpOptions {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("HttpOptions")
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .field("query", &self.query.keys().collect::<Vec<_>>())
+            .field("timeout_ms", &self.timeout_ms)
+            .field("retries", &self.retries)
+            .field("backoff_ms", &self.backoff_ms)
+            .finish()
+    }
+}
+
+impl HttpOptions {
+    /// Checks that header and parameter names are not empty and can be sent
+    /// and that retries fit `MAX_HTTP_REQUEST_TIME_MS`
+    pub fn is_valid(&self) -> bool {
+        let is_token = |name: &String| {
+            !name.is_empty()
+                && name
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+        };
+        self.headers.keys().all(is_token)
+            && self
+                .headers
+                .values()
+                .all(|value| !value.bytes().any(|b| b == b'\r' || b == b'\n'))
+            && self.query.keys().all(is_token)
+            && self.retries <= MAX_HTTP_RETRIES
+            && self.timeout() <= MAX_HTTP_REQUEST_TIME_MS
+            && self.backoff() <= MAX_HTTP_REQUEST_TIME_MS
+    }
+
+    /// Timeout of a single attempt in milliseconds
+    pub fn timeout(&self) -> u64 {
+        if self.timeout_ms == 0 {
+            DEFAULT_HTTP_TIMEOUT_MS
+        } else {
+            self.timeout_ms
+        }
+    }
+
+    /// Delay before the first retry in milliseconds
+    pub fn backoff(&self) -> u64 {
+        if self.backoff_ms == 0 {
+            DEFAULT_HTTP_BACKOFF_MS
+        } else {
+            self.backoff_ms
+        }
+    }
+
+    /// Replaces secrets in a text, e.g. a url, before it's logged.
+    /// Query parameters are replaced as they are and percent encoded, as they're sent in urls
+    pub fn redact(&self, text: &str) -> String {
+        let encoded: Vec<String> = self
+            .query
+            .values()
+            .map(|value| percent_encode(value))
+            .collect();
+        self.headers
+            .values()
+            .chain(self.query.values())
+            .chain(encoded.iter())
+            .filter(|secret| !secret.is_empty())
+            .fold(String::from(text), |text, secret| {
+                text.replace(secret.as_str(), "***")
+            })
+    }
+}
+
+/// Maximum decimals of a feed price, so `10^decimals` fits `u128`
+pub const MAX_FEED_DECIMALS: u32 = 38;
+
+/// Offchain settings of an oracle feeder, stored as a JSON document under `oracle::config`.
+///
+/// Example:
+/// `{"version":10,"source_type":"custom","price_periodicity":5,
+/// "custom_query":"json(https://api.binance.com/api/v3/ticker/price?symbol={$}USDT).price",
+/// "source_assets":{"eth":"price","btc":"price","dai":"reverse"}}`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OracleOffchainConfig {
+    /// Version of the document layout
+    pub version: u32,
+    /// One of the price sources available on chain, e.g. `custom`
+    pub source_type: String,
+    /// Amount of blocks between price feeds
+    pub price_periodicity: u32,
+    /// Maximum age in seconds of a price observed by a source, older prices are not fed.
+    /// Only prices with a source timestamp are checked, not checked if 0
+    pub max_price_age: u64,
+    /// Query for the `custom` price source, e.g. `json(https://ftx.com/api/markets/{$}/USD).result.price`
+    pub custom_query: Option<String>,
+    /// Assets to feed prices for with their price strategy. All assets are fed if empty
+    pub source_assets: BTreeMap<String, PriceStrategy>,
+    /// Plausible prices by asset symbol, checked by the `custom` and `graphql` price sources
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub price_bounds: BTreeMap<String, PriceBounds>,
+    /// Settings of the `pancake` price source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pancake: Option<PancakeSettings>,
+    /// Settings of the `evm` price source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evm: Option<EvmSettings>,
+    /// Settings of the `chainlink` price source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chainlink: Option<ChainlinkSettings>,
+    /// Settings of the `substrate` price source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub substrate: Option<SubstrateSettings>,
+    /// Settings of the `graphql` price source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graphql: Option<GraphqlSettings>,
+    /// Http options by price source type, e.g. `{"custom":{"headers":{"x-cg-pro-api-key":"..."}}}`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub http: BTreeMap<String, HttpOptions>,
+}
+
+/// Reason why an offchain configuration can't be used
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Neither the config document nor legacy settings are set
+    NotFound,
+    /// Stored document is not a valid UTF-8 string
+    NotUtf8,
+    /// Stored document is not a valid JSON config, contains a parser message
+    Malformed(String),
+    /// Document version is not supported by this runtime
+    UnsupportedVersion(u32),
+    /// `source_type` is empty
+    MissingSourceType,
+    /// `price_periodicity` should be more or equal 1
+    ZeroPricePeriodicity,
+    /// `custom` price source is chosen, but `custom_query` is empty
+    MissingCustomQuery,
+    /// `source_assets` contains an empty asset symbol
+    EmptyAssetSymbol,
+    /// `pancake` price source is chosen, but `pancake` settings are empty or incomplete
+    MissingPancakeSettings,
+    /// `evm` price source is chosen, but `evm` settings are empty
+    MissingEvmSettings,
+    /// Feed of the `evm` price source with the given asset symbol is invalid
+    InvalidEvmFeed(String),
+    /// `chainlink` price source is chosen, but `chainlink` settings are empty or incomplete
+    MissingChainlinkSettings,
+    /// `substrate` price source is chosen, but `substrate` settings are empty
+    MissingSubstrateSettings,
+    /// Feed of the `substrate` price source with the given asset symbol is invalid
+    InvalidSubstrateFeed(String),
+    /// `graphql` price source is chosen, but `url`, `query` or `price_path` is empty
+    MissingGraphqlSettings,
+    /// Http options of the given price source have an invalid header or parameter
+    InvalidHttpOptions(String),
+    /// Price bounds of the given asset symbol are not non negative decimals or `min > max`
+    InvalidPriceBounds(String),
+    /// Legacy setting with the given key can't be parsed
+    InvalidLegacyValue(&'static str),
+}
+
+/// Helper for reading a version before parsing the whole document
+/// and migrating the document of an older version to `CONFIG_VERSION`
+struct VersionProbe {
+    version: u32,
+    document: json::Map<String, json::Value>,
+}
+
+impl VersionProbe {
+    fn parse(raw: &str) -> Result<Self, ConfigError> {
+        let document = match json::from_str::<json::Value>(raw).map_err(malformed)? {
+            json::Value::Object(document) => document,
+            _ => return Err(ConfigError::Malformed("document is not an object".into())),
+        };
+        let version = match document.get("version") {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| ConfigError::Malformed("version is not a u32".into()))?,
+        };
+
+        Ok(VersionProbe { version, document })
+    }
+
+    /// Returns the document migrated to `CONFIG_VERSION`
+    fn migrate(mut self) -> Result<json::Value, ConfigError> {
+        if self.version == 0 || self.version > CONFIG_VERSION {
+            return Err(ConfigError::UnsupportedVersion(self.version));
+        }
+        for version in self.version + 1..=CONFIG_VERSION {
+            self.upgrade_to(version)?;
+        }
+        self.document
+            .insert("version".into(), json::Value::from(CONFIG_VERSION));
+
+        Ok(json::Value::Object(self.document))
+    }
+
+    /// Migrates the document from `version - 1` to `version`
+    fn upgrade_to(&mut self, version: u32) -> Result<(), ConfigError> {
+        let added: &[&str] = match version {
+            2 => &["pancake"],
+            3 => &["evm"],
+            4 => &["chainlink"],
+            5 => &["substrate"],
+            6 => &["graphql"],
+            7 => &["http"],
+            9 => &["price_bounds"],
+            10 => &["max_price_age"],
+            _ => &[],
+        };
+        // the document of an older version can't have fields added later
+        if let Some(field) = added
+            .iter()
+            .find(|field| self.document.contains_key(**field))
+        {
+            return Err(self.unknown_field(field));
+        }
+
+        match version {
+            7 => {
+                let headers = self
+                    .document
+                    .get_mut("graphql")
+                    .and_then(json::Value::as_object_mut)
+                    .and_then(|graphql| graphql.remove("headers"));
+                if let Some(headers) = headers {
+                    let mut graphql = json::Map::new();
+                    graphql.insert("headers".into(), headers);
+                    let mut http = json::Map::new();
+                    http.insert("graphql".into(), json::Value::Object(graphql));
+                    self.document
+                        .insert("http".into(), json::Value::Object(http));
+                }
+            }
+            8 => {
+                let options = self
+                    .document
+                    .get("http")
+                    .and_then(json::Value::as_object)
+                    .into_iter()
+                    .flat_map(|http| http.values())
+                    .filter_map(json::Value::as_object);
+                for options in options {
+                    if let Some(field) = ["timeout_ms", "retries", "backoff_ms"]
+                        .iter()
+                        .find(|field| options.contains_key(**field))
+                    {
+                        return Err(self.unknown_field(field));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn unknown_field(&self, field: &str) -> ConfigError {
+        ConfigError::Malformed(alloc::format!(
+            "unknown field `{}` in version {}",
+            field,
+            self.version
+        ))
+    }
+}
+
+fn malformed(error: json::Error) -> ConfigError {
+    ConfigError::Malformed(alloc::format!("{}", error))
+}
+
+impl OracleOffchainConfig {
+    /// Parses a JSON document, migrates it to `CONFIG_VERSION` and validates
+    pub fn from_json(raw: &[u8]) -> Result<Self, ConfigError> {
+        let raw = core::str::from_utf8(raw).map_err(|_| ConfigError::NotUtf8)?;
+
+        let document = VersionProbe::parse(raw)?.migrate()?;
+        let config: Self = json::from_value(document).map_err(malformed)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Validates and serializes config to a JSON document
+    pub fn to_json(&self) -> Result<Vec<u8>, ConfigError> {
+        self.validate()?;
+        json::to_vec(self).map_err(malformed)
+    }
+
+    /// Checks that config is consistent. Typed config has the layout of `CONFIG_VERSION`
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.version != CONFIG_VERSION {
+            return Err(ConfigError::UnsupportedVersion(self.version));
+        }
+        if self.source_type.trim().is_empty() {
+            return Err(ConfigError::MissingSourceType);
+        }
+        if self.price_periodicity < 1 {
+            return Err(ConfigError::ZeroPricePeriodicity);
+        }
+        if self.source_type == "custom"
+            && self
+                .custom_query
+                .as_ref()
+                .map_or(true, |query| query.trim().is_empty())
+        {
+            return Err(ConfigError::MissingCustomQuery);
+        }
+        if self.source_type == "pancake"
+            && self.pancake.as_ref().map_or(true, |pancake| {
+                pancake.node_url.trim().is_empty()
+                    || pancake.pools.values().any(|pool| {
+                        pool.contract.trim().is_empty()
+                            || pool.token_0.trim().is_empty()
+                            || pool.token_1.trim().is_empty()
+                    })
+            })
+        {
+            return Err(ConfigError::MissingPancakeSettings);
+        }
+        if self.source_type == "evm" {
+            let evm = self
+                .evm
+                .as_ref()
+                .filter(|evm| !evm.node_url.trim().is_empty())
+                .ok_or(ConfigError::MissingEvmSettings)?;
+            for (symbol, feed) in &evm.feeds {
+                if !feed.is_valid() {
+                    return Err(ConfigError::InvalidEvmFeed(symbol.clone()));
+                }
+            }
+        }
+        if self.source_type == "chainlink"
+            && self.chainlink.as_ref().map_or(true, |chainlink| {
+                chainlink.node_url.trim().is_empty()
+                    || chainlink.max_round_age == 0
+                    || chainlink
+                        .aggregators
+                        .values()
+                        .any(|contract| contract.trim().is_empty())
+            })
+        {
+            return Err(ConfigError::MissingChainlinkSettings);
+        }
+        if self.source_type == "substrate" {
+            let substrate = self
+                .substrate
+                .as_ref()
+                .filter(|substrate| !substrate.node_url.trim().is_empty())
+                .ok_or(ConfigError::MissingSubstrateSettings)?;
+            for (symbol, feed) in &substrate.feeds {
+                if !feed.is_valid() {
+                    return Err(ConfigError::InvalidSubstrateFeed(symbol.clone()));
+                }
+            }
+        }
+        if self.source_type == "graphql"
+            && self.graphql.as_ref().map_or(true, |graphql| {
+                graphql.url.trim().is_empty()
+                    || graphql.query.trim().is_empty()
+                    || graphql.price_path.trim().is_empty()
+            })
+        {
+            return Err(ConfigError::MissingGraphqlSettings);
+        }
+        for (source_type, options) in &self.http {
+            if !options.is_valid() {
+                return Err(ConfigError::InvalidHttpOptions(source_type.clone()));
+            }
+        }
+        if self
+            .source_assets
+            .keys()
+            .any(|symbol| symbol.trim().is_empty())
+        {
+            return Err(ConfigError::EmptyAssetSymbol);
+        }
+        for (symbol, bounds) in &self.price_bounds {
+            if !bounds.is_valid() {
+                return Err(ConfigError::InvalidPriceBounds(symbol.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns price strategy for an asset symbol or `None` if the asset shouldn't be fed.
+    /// All assets are fed with `PriceStrategy::Price` when `source_assets` is empty.
+    pub fn price_strategy(&self, symbol: &str) -> Option<PriceStrategy> {
+        if self.source_assets.is_empty() {
+            Some(PriceStrategy::Price)
+        } else {
+            self.source_assets
+                .iter()
+                .find(|(asset, _)| asset.trim().eq_ignore_ascii_case(symbol))
+                .map(|(_, strategy)| *strategy)
+        }
+    }
+
+    /// Checks that a price of an asset symbol is within its `price_bounds`
+    pub fn is_price_in_bounds<F: FixedPointNumber>(&self, symbol: &str, price: F) -> bool {
+        let bounds = match self
+            .price_bounds
+            .iter()
+            .find(|(asset, _)| asset.trim().eq_ignore_ascii_case(symbol))
+        {
+            Some((_, bounds)) => bounds,
+            None => return true,
+        };
+
+        // bounds beyond the range of `F` are not reachable
+        let above_min = bounds.min.as_ref().map_or(true, |min| {
+            parse_decimal::<F>(min.trim()).map_or(false, |min| price >= min)
+        });
+        let below_max = bounds.max.as_ref().map_or(true, |max| {
+            parse_decimal::<F>(max.trim()).map_or(true, |max| price <= max)
+        });
+        above_min && below_max
+    }
+
+    /// Checks that a price observed at `timestamp` is not older than `max_price_age` at `now`,
+    /// both are unix times in seconds
+    pub fn is_price_fresh(&self, timestamp: Option<u64>, now: u64) -> bool {
+        match timestamp {
+            Some(timestamp) if self.max_price_age != 0 => {
+                now.saturating_sub(timestamp) <= self.max_price_age
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns http options of a price source, empty if not set
+    pub fn http_options(&self, source_type: &str) -> HttpOptions {
+        self.http.get(source_type).cloned().unwrap_or_default()
+    }
+}
+
+impl PriceBounds {
+    /// Checks that bounds are non negative decimals and `min <= max`
+    pub fn is_valid(&self) -> bool {
+        let parse = |bound: &Option<String>| match bound {
+            Some(bound) => parse_decimal::<FixedU128>(bound.trim()).ok().map(Some),
+            None => Some(None),
+        };
+        match (parse(&self.min), parse(&self.max)) {
+            (Some(Some(min)), Some(Some(max))) => min <= max,
+            (Some(_), Some(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl EvmFeed {
+    /// Checks that the feed can be called and its price can be decoded
+    pub fn is_valid(&self) -> bool {
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|name| AbiType::parse(name))
+            .collect::<Option<Vec<_>>>();
+
+        !self.contract.trim().is_empty()
+            && self.function.trim().ends_with("()")
+            && self.decimals <= MAX_FEED_DECIMALS
+            && outputs.map_or(false, |outputs| {
+                matches!(
+                    outputs.get(self.output_index as usize),
+                    Some(AbiType::Uint(_)) | Some(AbiType::Int(_))
+                )
+            })
+    }
+}
+
+impl SubstrateFeed {
+    /// Checks that the feed can be requested and its price can be decoded
+    pub fn is_valid(&self) -> bool {
+        let key = self.key.replace("{$}", "");
+        from_hex(&key).is_ok()
+            && (self.method == SubstrateMethod::Call
+                || !key.trim().trim_start_matches("0x").is_empty())
+            && (self.method == SubstrateMethod::Storage || !self.function.trim().is_empty())
+            && self.decimals <= MAX_FEED_DECIMALS
+            && self.quote_decimals <= MAX_FEED_DECIMALS
+    }
+}
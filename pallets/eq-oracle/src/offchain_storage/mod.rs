@@ -4,13 +4,33 @@ use alloc::string::{String, ToString};
 use sp_io::offchain;
 use sp_runtime::offchain::StorageKind;
 use sp_std::collections::btree_map::BTreeMap;
-use utils::offchain::get_local_storage_val;
+use utils::{log, offchain::get_local_storage_val};
 
-mod storage_keys;
+pub mod config;
+pub mod storage_keys;
 
-/// Gets query for price requests
-pub fn get_query() -> Option<String> {
-    get_local_storage_val(storage_keys::CUSTOM_QUERY)
+pub use config::{
+    ChainlinkSettings, ConfigError, EvmFeed, EvmSettings, GraphqlSettings, HttpOptions,
+    OracleOffchainConfig, PancakePool, PancakeSettings, PriceBounds, PriceStrategy,
+    SubstrateDecoder, SubstrateFeed, SubstrateMethod, SubstrateSettings, CONFIG_VERSION,
+    DEFAULT_HTTP_BACKOFF_MS, DEFAULT_HTTP_TIMEOUT_MS, MAX_FEED_DECIMALS, MAX_HTTP_REQUEST_TIME_MS,
+    MAX_HTTP_RETRIES,
+};
+
+/// Gets typed offchain config.
+/// Config is migrated from legacy settings if the document is not set yet.
+pub fn get_config() -> Result<OracleOffchainConfig, ConfigError> {
+    match offchain::local_storage_get(StorageKind::PERSISTENT, storage_keys::CONFIG) {
+        Some(raw) => OracleOffchainConfig::from_json(&raw),
+        None => migrate_legacy_config(),
+    }
+}
+
+/// Validates and stores offchain config
+pub fn set_config(config: &OracleOffchainConfig) -> Result<(), ConfigError> {
+    let raw = config.to_json()?;
+    offchain::local_storage_set(StorageKind::PERSISTENT, storage_keys::CONFIG, &raw);
+    Ok(())
 }
 
 /// Get counter
@@ -18,11 +38,6 @@ pub fn get_counter() -> Option<u32> {
     get_local_storage_val(storage_keys::COUNTER)
 }
 
-/// Get periodicity of price update
-pub fn get_price_periodicity() -> Option<u32> {
-    get_local_storage_val(storage_keys::PRICE_PERIODICITY)
-}
-
 /// Update counter value
 pub fn set_counter(value: u32) {
     offchain::local_storage_set(
@@ -32,39 +47,102 @@ pub fn set_counter(value: u32) {
     );
 }
 
-/// Get source type value
-pub fn get_source_type() -> Option<String> {
-    get_local_storage_val(storage_keys::RESOURCE_TYPE)
+/// Builds config from legacy settings `oracle::resource_type`, `oracle::price_periodicity`,
+/// `oracle::custom_query` and `oracle::source_assets`, stores it and clears legacy settings
+fn migrate_legacy_config() -> Result<OracleOffchainConfig, ConfigError> {
+    let get_legacy = |key: &'static [u8]| -> Result<Option<String>, ConfigError> {
+        offchain::local_storage_get(StorageKind::PERSISTENT, key)
+            .map(|raw| {
+                String::from_utf8(raw).map_err(|_| ConfigError::InvalidLegacyValue(key_name(key)))
+            })
+            .transpose()
+    };
+
+    let source_type = get_legacy(storage_keys::RESOURCE_TYPE)?;
+    let price_periodicity = get_legacy(storage_keys::PRICE_PERIODICITY)?;
+    let custom_query = get_legacy(storage_keys::CUSTOM_QUERY)?;
+    let source_assets = get_legacy(storage_keys::SOURCE_ASSETS)?;
+
+    if source_type.is_none()
+        && price_periodicity.is_none()
+        && custom_query.is_none()
+        && source_assets.is_none()
+    {
+        return Err(ConfigError::NotFound);
+    }
+
+    let price_periodicity = match price_periodicity {
+        Some(value) => value.trim().parse::<u32>().map_err(|_| {
+            ConfigError::InvalidLegacyValue(key_name(storage_keys::PRICE_PERIODICITY))
+        })?,
+        None => 0,
+    };
+
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: source_type.unwrap_or_default().trim().into(),
+        price_periodicity,
+        max_price_age: 0,
+        custom_query,
+        source_assets: parse_legacy_asset_settings(&source_assets.unwrap_or_default())?,
+        price_bounds: BTreeMap::new(),
+        pancake: None,
+        evm: None,
+        chainlink: None,
+        substrate: None,
+        graphql: None,
+        http: BTreeMap::new(),
+    };
+
+    set_config(&config)?;
+    for key in [
+        storage_keys::RESOURCE_TYPE,
+        storage_keys::PRICE_PERIODICITY,
+        storage_keys::CUSTOM_QUERY,
+        storage_keys::SOURCE_ASSETS,
+    ] {
+        offchain::local_storage_clear(StorageKind::PERSISTENT, key);
+    }
+    log::info!(
+        target: "eq_oracle",
+        "Legacy offchain settings migrated to {:?}",
+        config
+    );
+
+    Ok(config)
 }
 
-/// Returns collection of pairs (asset, price_strategy) available values for price_strategy is: "price", "reverse".
-/// Price_strategy defines how to serve value from price source for particular asset.
-/// If price_strategy == "price" then value recieved from price source is price.
-/// if price_strategy == "reverse" then price = 1 / value
-pub fn get_asset_settings() -> BTreeMap<String, String> {
-    // List of assets that require price setting
-    // example USDC:price, USDT:price, BTC:price, DAI:reverse
-    // example USDC, USDT, DAI:reverse, BTC
-    get_local_storage_val::<String>(storage_keys::SOURCE_ASSETS)
-        .map(|assets_str| {
-            assets_str
-                .split(',')
-                .map(|pair_str| {
-                    let mut split_pair = pair_str.split(':');
-
-                    (
-                        split_pair.next().unwrap().trim().to_lowercase(),
-                        split_pair
-                            .next()
-                            .map(|v| v.trim().to_lowercase())
-                            .unwrap_or(String::from("price")),
-                    )
-                })
-                .collect()
+/// Parses legacy list of assets with optional price strategies.
+/// Example: `USDC:price, USDT:price, BTC, DAI:reverse`
+fn parse_legacy_asset_settings(
+    assets_str: &str,
+) -> Result<BTreeMap<String, PriceStrategy>, ConfigError> {
+    assets_str
+        .split(',')
+        .map(|pair_str| pair_str.trim())
+        .filter(|pair_str| !pair_str.is_empty())
+        .map(|pair_str| {
+            let mut split_pair = pair_str.split(':');
+            let symbol = split_pair.next().unwrap_or_default().trim().to_lowercase();
+            let strategy = match split_pair
+                .next()
+                .map(|v| v.trim().to_lowercase())
+                .as_deref()
+            {
+                None | Some("price") => PriceStrategy::Price,
+                Some("reverse") => PriceStrategy::Reverse,
+                Some(_) => {
+                    return Err(ConfigError::InvalidLegacyValue(key_name(
+                        storage_keys::SOURCE_ASSETS,
+                    )))
+                }
+            };
+
+            Ok((symbol, strategy))
         })
-        .unwrap_or_default()
+        .collect()
 }
 
-pub fn clear_asset_settings() {
-    offchain::local_storage_clear(StorageKind::PERSISTENT, storage_keys::SOURCE_ASSETS);
+fn key_name(key: &'static [u8]) -> &'static str {
+    core::str::from_utf8(key).unwrap_or("")
 }
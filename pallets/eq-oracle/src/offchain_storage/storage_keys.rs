@@ -1,5 +1,8 @@
-pub const CUSTOM_QUERY: &[u8] = b"oracle::custom_query";
+pub const CONFIG: &[u8] = b"oracle::config";
 pub const COUNTER: &[u8] = b"oracle::counter";
+
+// Legacy settings, migrated to `CONFIG` on first read
+pub const CUSTOM_QUERY: &[u8] = b"oracle::custom_query";
 pub const PRICE_PERIODICITY: &[u8] = b"oracle::price_periodicity";
 pub const RESOURCE_TYPE: &[u8] = b"oracle::resource_type";
 pub const SOURCE_ASSETS: &[u8] = b"oracle::source_assets";
@@ -0,0 +1,215 @@
+use super::{
+    apply_price_strategy,
+    evm::{AbiType, AbiValue, EvmClient},
+    http_client::{request_deadline, HttpTransport, OffchainHttp},
+    json::PriceSourceError,
+    log_price_result, PriceSource,
+};
+use crate::offchain_storage::{ChainlinkSettings, OracleOffchainConfig, MAX_FEED_DECIMALS};
+use primitives::AsSymbol;
+use sp_arithmetic::FixedPointNumber;
+use sp_core::U256;
+use sp_std::{convert::TryFrom, marker::PhantomData, vec::Vec};
+use utils::log;
+
+/// Chainlink price source.
+/// Reads `latestRoundData()` and `decimals()` of aggregator contracts from the `chainlink`
+/// section of the offchain config. Stale rounds, i.e. updated more than `max_round_age` seconds ago,
+/// and rounds with `answeredInRound < roundId` are rejected. Prices are observed at `updatedAt`
+/// of their rounds. Requests are sent with `T`.
+#[derive(Debug)]
+pub struct ChainlinkPriceSource<AssetId, AssetData, T = OffchainHttp> {
+    settings: ChainlinkSettings,
+    config: OracleOffchainConfig,
+    assets_data: Vec<(AssetId, AssetData)>,
+    _transport: PhantomData<T>,
+}
+
+/// Result of aggregator `latestRoundData()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoundData {
+    pub round_id: U256,
+    pub answer: AbiValue,
+    pub started_at: U256,
+    pub updated_at: U256,
+    pub answered_in_round: U256,
+}
+
+impl RoundData {
+    /// Returns the answer of a complete and fresh round with a positive price
+    pub fn checked_answer(&self, now: u64, max_round_age: u64) -> Result<U256, PriceSourceError> {
+        if self.updated_at.is_zero() || self.answered_in_round < self.round_id {
+            log::error!("Chainlink round is not complete. Round: {:?}", self);
+            return Err(PriceSourceError::IncompleteRound);
+        }
+        if self.updated_at.saturating_add(max_round_age.into()) < now.into() {
+            log::error!(
+                "Chainlink round is stale. Round: {:?}, now: {:?}",
+                self,
+                now
+            );
+            return Err(PriceSourceError::StaleRound);
+        }
+
+        self.answer
+            .as_uint()
+            .filter(|answer| !answer.is_zero())
+            .ok_or(PriceSourceError::InvalidContractValue)
+    }
+}
+
+/// Provides read methods of aggregator contract
+mod aggregator {
+    use super::*;
+
+    /// Returns decimals of an answer
+    pub fn decimals<T: HttpTransport>(
+        client: &EvmClient<T>,
+        contract: &str,
+    ) -> Result<u32, PriceSourceError> {
+        let values = client.call_function(contract, "decimals()", &[], &[AbiType::Uint(8)])?;
+        values[0]
+            .as_uint()
+            .map(|decimals| decimals.low_u32())
+            .filter(|decimals| *decimals <= MAX_FEED_DECIMALS)
+            .ok_or(PriceSourceError::InvalidContractValue)
+    }
+
+    /// Returns the latest round
+    pub fn latest_round_data<T: HttpTransport>(
+        client: &EvmClient<T>,
+        contract: &str,
+    ) -> Result<RoundData, PriceSourceError> {
+        // (uint80 roundId, int256 answer, uint256 startedAt, uint256 updatedAt, uint80 answeredInRound)
+        let values = client.call_function(
+            contract,
+            "latestRoundData()",
+            &[],
+            &[
+                AbiType::Uint(80),
+                AbiType::Int(256),
+                AbiType::Uint(256),
+                AbiType::Uint(256),
+                AbiType::Uint(80),
+            ],
+        )?;
+        let uint = |value: &AbiValue| {
+            value
+                .as_uint()
+                .ok_or(PriceSourceError::InvalidContractValue)
+        };
+
+        Ok(RoundData {
+            round_id: uint(&values[0])?,
+            answer: values[1].clone(),
+            started_at: uint(&values[2])?,
+            updated_at: uint(&values[3])?,
+            answered_in_round: uint(&values[4])?,
+        })
+    }
+}
+
+impl<AssetId, AssetData, T: HttpTransport> ChainlinkPriceSource<AssetId, AssetData, T> {
+    /// Returns the price of the latest round and its `updatedAt`
+    fn fetch_price<F: FixedPointNumber>(
+        &self,
+        client: &EvmClient<T>,
+        contract: &str,
+        now: u64,
+    ) -> Result<(F, u64), PriceSourceError> {
+        let decimals = aggregator::decimals(client, contract)?;
+        let round = aggregator::latest_round_data(client, contract)?;
+        let answer = round.checked_answer(now, self.settings.max_round_age)?;
+        let updated_at =
+            u64::try_from(round.updated_at).map_err(|_| PriceSourceError::InvalidTimestamp)?;
+
+        let answer = u128::try_from(answer).map_err(|_| PriceSourceError::OverflowError)?;
+        let price = F::checked_from_rational(answer, 10u128.pow(decimals))
+            .ok_or(PriceSourceError::OverflowError)?;
+        Ok((price, updated_at))
+    }
+}
+
+impl<AssetId: AsSymbol + Clone, AssetData, T: HttpTransport> PriceSource<AssetId, AssetData>
+    for ChainlinkPriceSource<AssetId, AssetData, T>
+{
+    const PRICE_SOURCE_TYPE: &'static str = "chainlink";
+
+    fn new(
+        config: &OracleOffchainConfig,
+        assets_data: Vec<(AssetId, AssetData)>,
+    ) -> Result<Self, &'static str> {
+        Ok(ChainlinkPriceSource {
+            settings: config.chainlink.clone().ok_or("No chainlink settings")?,
+            config: config.clone(),
+            assets_data,
+            _transport: PhantomData,
+        })
+    }
+
+    fn get_prices<F>(&self) -> Vec<(AssetId, Result<F, &'static str>)>
+    where
+        F: FixedPointNumber,
+    {
+        self.get_observed_prices::<F>()
+            .into_iter()
+            .map(|(asset, price)| (asset, price.map(|(price, _)| price)))
+            .collect()
+    }
+
+    fn get_observed_prices<F>(&self) -> Vec<(AssetId, Result<(F, Option<u64>), &'static str>)>
+    where
+        F: FixedPointNumber,
+    {
+        let client = match EvmClient::<T>::pinned(
+            &self.settings.node_url,
+            self.config.http_options(Self::PRICE_SOURCE_TYPE),
+            request_deadline(),
+        ) {
+            Ok(client) => client,
+            Err(err) => {
+                log::error!("Chainlink node is not available. Error: {:?}", err);
+                return Vec::new();
+            }
+        };
+        let now = sp_io::offchain::timestamp().unix_millis() / 1000;
+
+        let mut asset_prices: Vec<(AssetId, Result<(F, Option<u64>), &'static str>)> =
+            Vec::with_capacity(self.settings.aggregators.len());
+
+        for (asset, _) in &self.assets_data {
+            // only assets with aggregators in settings are fed
+            let symbol = match asset.get_symbol() {
+                Some(symbol) => symbol,
+                None => continue,
+            };
+            let contract = match self
+                .settings
+                .aggregators
+                .iter()
+                .find(|(aggregator_symbol, _)| {
+                    aggregator_symbol.trim().eq_ignore_ascii_case(&symbol)
+                }) {
+                Some((_, contract)) => contract,
+                None => continue,
+            };
+            let price_strategy = match self.config.price_strategy(&symbol) {
+                Some(price_strategy) => price_strategy,
+                None => continue,
+            };
+
+            let price =
+                self.fetch_price::<F>(&client, contract, now)
+                    .and_then(|(price, updated_at)| {
+                        let price = apply_price_strategy(price, price_strategy)?;
+                        Ok((price, Some(updated_at)))
+                    });
+            asset_prices.push((
+                asset.clone(),
+                log_price_result(Self::PRICE_SOURCE_TYPE, symbol, price),
+            ));
+        }
+
+        asset_prices
+    }
+}
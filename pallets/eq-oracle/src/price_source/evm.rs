@@ -0,0 +1,415 @@
+//! Minimal EVM JSON-RPC client for offchain workers: `eth_call` of view functions
+//! with ABI encoding of arguments and decoding of static return values.
+
+use super::{
+    apply_price_strategy,
+    http_client::{request_deadline, HttpClient, HttpTransport, OffchainHttp},
+    json::PriceSourceError,
+    log_price_result, PriceSource,
+};
+use crate::offchain_storage::{EvmFeed, EvmSettings, HttpOptions, OracleOffchainConfig};
+use alloc::string::String;
+use primitives::AsSymbol;
+use serde_json as json;
+use sp_arithmetic::FixedPointNumber;
+use sp_core::U256;
+use sp_runtime::offchain::Timestamp;
+use sp_std::{convert::TryFrom, marker::PhantomData, vec::Vec};
+use utils::log;
+
+/// Size of an ABI word in bytes
+const WORD_SIZE: usize = 32;
+/// Size of an address in bytes
+const ADDRESS_SIZE: usize = 20;
+
+/// Static ABI type of a function argument or a return value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AbiType {
+    Address,
+    Bool,
+    /// `uint<bits>`
+    Uint(usize),
+    /// `int<bits>`
+    Int(usize),
+}
+
+impl AbiType {
+    /// Parses a solidity type name, e.g. `uint80`, `int256`, `address`
+    pub fn parse(name: &str) -> Option<Self> {
+        let bits = |bits: &str| -> Option<usize> {
+            let bits = if bits.is_empty() {
+                256
+            } else {
+                bits.parse::<usize>().ok()?
+            };
+            (bits > 0 && bits <= 256 && bits % 8 == 0).then(|| bits)
+        };
+
+        match name.trim() {
+            "address" => Some(AbiType::Address),
+            "bool" => Some(AbiType::Bool),
+            name if name.starts_with("uint") => bits(&name[4..]).map(AbiType::Uint),
+            name if name.starts_with("int") => bits(&name[3..]).map(AbiType::Int),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded ABI value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AbiValue {
+    Address([u8; ADDRESS_SIZE]),
+    Bool(bool),
+    Uint(U256),
+    /// Signed integer as a sign and an absolute value
+    Int {
+        negative: bool,
+        abs: U256,
+    },
+}
+
+impl AbiValue {
+    /// Parses an address in `0x...` format
+    pub fn address(address: &str) -> Result<Self, PriceSourceError> {
+        let bytes = from_hex(address)?;
+        let address =
+            <[u8; ADDRESS_SIZE]>::try_from(&bytes[..]).map_err(|_| PriceSourceError::AbiError)?;
+        Ok(AbiValue::Address(address))
+    }
+
+    /// Returns a non negative integer value
+    pub fn as_uint(&self) -> Option<U256> {
+        match self {
+            AbiValue::Uint(value) => Some(*value),
+            AbiValue::Int {
+                negative: false,
+                abs,
+            } => Some(*abs),
+            _ => None,
+        }
+    }
+
+    /// Returns an address value in `0x...` format
+    pub fn as_address(&self) -> Option<String> {
+        match self {
+            AbiValue::Address(address) => Some(to_hex(address)),
+            _ => None,
+        }
+    }
+
+    fn encode(&self) -> [u8; WORD_SIZE] {
+        let mut word = [0u8; WORD_SIZE];
+        match self {
+            AbiValue::Address(address) => word[WORD_SIZE - ADDRESS_SIZE..].copy_from_slice(address),
+            AbiValue::Bool(value) => word[WORD_SIZE - 1] = *value as u8,
+            AbiValue::Uint(value) => value.to_big_endian(&mut word),
+            AbiValue::Int { negative, abs } => {
+                let value = if *negative {
+                    // two's complement
+                    (!*abs).overflowing_add(U256::one()).0
+                } else {
+                    *abs
+                };
+                value.to_big_endian(&mut word);
+            }
+        }
+        word
+    }
+}
+
+/// Returns first 4 bytes of keccak256 of a function signature, e.g. `decimals()`
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = sp_io::hashing::keccak_256(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Encodes a call of a function with static arguments
+pub fn encode_call(signature: &str, args: &[AbiValue]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(4 + WORD_SIZE * args.len());
+    data.extend_from_slice(&selector(signature));
+    for arg in args {
+        data.extend_from_slice(&arg.encode());
+    }
+    data
+}
+
+/// Decodes static return values, a returned tuple is decoded as a list of its fields
+pub fn decode(types: &[AbiType], data: &[u8]) -> Result<Vec<AbiValue>, PriceSourceError> {
+    if data.len() < types.len() * WORD_SIZE {
+        log::error!(
+            "ABI data is too short. Expected {:?} words, data: {:?}",
+            types.len(),
+            to_hex(data)
+        );
+        return Err(PriceSourceError::AbiError);
+    }
+
+    types
+        .iter()
+        .zip(data.chunks(WORD_SIZE))
+        .map(|(abi_type, word)| decode_word(*abi_type, word))
+        .collect()
+}
+
+fn decode_word(abi_type: AbiType, word: &[u8]) -> Result<AbiValue, PriceSourceError> {
+    let value = U256::from_big_endian(word);
+    match abi_type {
+        AbiType::Address => {
+            if value.bits() > ADDRESS_SIZE * 8 {
+                return Err(PriceSourceError::AbiError);
+            }
+            let mut address = [0u8; ADDRESS_SIZE];
+            address.copy_from_slice(&word[WORD_SIZE - ADDRESS_SIZE..]);
+            Ok(AbiValue::Address(address))
+        }
+        AbiType::Bool if value <= U256::one() => Ok(AbiValue::Bool(!value.is_zero())),
+        AbiType::Bool => Err(PriceSourceError::AbiError),
+        AbiType::Uint(bits) => {
+            if value.bits() > bits {
+                return Err(PriceSourceError::AbiError);
+            }
+            Ok(AbiValue::Uint(value))
+        }
+        AbiType::Int(bits) => {
+            let negative = word[0] & 0x80 != 0;
+            let abs = if negative {
+                (!value).overflowing_add(U256::one()).0
+            } else {
+                value
+            };
+            // -2^(bits-1) <= value < 2^(bits-1)
+            let limit = U256::one() << (bits - 1);
+            if (negative && abs > limit) || (!negative && abs >= limit) {
+                return Err(PriceSourceError::AbiError);
+            }
+            Ok(AbiValue::Int { negative, abs })
+        }
+    }
+}
+
+/// Encodes bytes to `0x...` hex string
+pub fn to_hex(bytes: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push(HEX[(byte >> 4) as usize] as char);
+        hex.push(HEX[(byte & 0xf) as usize] as char);
+    }
+    hex
+}
+
+/// Decodes `0x...` hex string
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, PriceSourceError> {
+    let hex = hex.trim().trim_start_matches("0x").as_bytes();
+    if hex.len() % 2 != 0 {
+        return Err(PriceSourceError::AbiError);
+    }
+
+    let digit = |c: u8| -> Result<u8, PriceSourceError> {
+        (c as char)
+            .to_digit(16)
+            .map(|d| d as u8)
+            .ok_or(PriceSourceError::AbiError)
+    };
+    hex.chunks(2)
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+/// JSON-RPC client of an EVM node, requests are sent with `T`
+#[derive(Clone, Debug)]
+pub struct EvmClient<T = OffchainHttp> {
+    url: String,
+    /// Headers and query parameters of requests to the node, e.g. an api key
+    http: HttpOptions,
+    /// Block for `eth_call`, `latest` if not set
+    block: Option<U256>,
+    /// Deadline of all requests of the client
+    deadline: Timestamp,
+    _transport: PhantomData<T>,
+}
+
+impl<T: HttpTransport> EvmClient<T> {
+    /// Client calling contracts at the latest block, all requests fit `deadline`
+    pub fn new(url: &str, http: HttpOptions, deadline: Timestamp) -> Self {
+        EvmClient {
+            url: url.into(),
+            http,
+            block: None,
+            deadline,
+            _transport: PhantomData,
+        }
+    }
+
+    /// Client calling contracts at the current block of the node,
+    /// so all calls of the client see the same state
+    pub fn pinned(
+        url: &str,
+        http: HttpOptions,
+        deadline: Timestamp,
+    ) -> Result<Self, PriceSourceError> {
+        let mut client = Self::new(url, http, deadline);
+        client.block = Some(client.block_number()?);
+        Ok(client)
+    }
+
+    /// Block the client is pinned to
+    pub fn block(&self) -> Option<U256> {
+        self.block
+    }
+
+    /// Returns current block number of the node, `eth_blockNumber`
+    pub fn block_number(&self) -> Result<U256, PriceSourceError> {
+        let result = self.request("eth_blockNumber", "[]")?;
+        U256::from_str_radix(result.trim_start_matches("0x"), 16)
+            .map_err(|_| PriceSourceError::JsonParseError)
+    }
+
+    /// Executes `eth_call` with raw call data and returns raw output
+    pub fn call(&self, contract: &str, data: &[u8]) -> Result<Vec<u8>, PriceSourceError> {
+        let block = match self.block {
+            Some(block) => alloc::format!("0x{:x}", block),
+            None => "latest".into(),
+        };
+        let params = alloc::format!(
+            "[{{\"to\":\"{}\",\"data\":\"{}\"}},\"{}\"]",
+            contract,
+            to_hex(data),
+            block
+        );
+
+        from_hex(&self.request("eth_call", &params)?)
+    }
+
+    /// Calls a view function, e.g. `balanceOf(address)`, and decodes its static return values
+    pub fn call_function(
+        &self,
+        contract: &str,
+        signature: &str,
+        args: &[AbiValue],
+        outputs: &[AbiType],
+    ) -> Result<Vec<AbiValue>, PriceSourceError> {
+        let output = self.call(contract, &encode_call(signature, args))?;
+        decode(outputs, &output).map_err(|err| {
+            log::error!(
+                "Unexpected output of {:?} of {:?}: {:?}",
+                signature,
+                contract,
+                to_hex(&output)
+            );
+            err
+        })
+    }
+
+    /// Sends a JSON-RPC request and returns a string result
+    fn request(&self, method: &str, params: &str) -> Result<String, PriceSourceError> {
+        match HttpClient::<T>::json_rpc(&self.url, method, params, &self.http, self.deadline)? {
+            json::Value::String(result) => Ok(result),
+            _ => Err(PriceSourceError::JsonParseError),
+        }
+    }
+}
+
+/// EVM price source.
+/// Reads prices with view functions of contracts from the `evm` section of the offchain config,
+/// e.g. `latestRoundData()` of a Chainlink aggregator. All calls of a feed round are made
+/// at the same block. Requests are sent with `T`.
+#[derive(Debug)]
+pub struct EvmPriceSource<AssetId, AssetData, T = OffchainHttp> {
+    settings: EvmSettings,
+    config: OracleOffchainConfig,
+    assets_data: Vec<(AssetId, AssetData)>,
+    _transport: PhantomData<T>,
+}
+
+impl<AssetId, AssetData, T: HttpTransport> EvmPriceSource<AssetId, AssetData, T> {
+    fn fetch_price<F: FixedPointNumber>(
+        client: &EvmClient<T>,
+        feed: &EvmFeed,
+    ) -> Result<F, PriceSourceError> {
+        let outputs = feed
+            .outputs
+            .iter()
+            .map(|name| AbiType::parse(name).ok_or(PriceSourceError::AbiError))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let values = client.call_function(&feed.contract, &feed.function, &[], &outputs)?;
+        let value = values
+            .get(feed.output_index as usize)
+            .and_then(AbiValue::as_uint)
+            .ok_or(PriceSourceError::InvalidContractValue)?;
+
+        let value = u128::try_from(value).map_err(|_| PriceSourceError::OverflowError)?;
+        F::checked_from_rational(value, 10u128.pow(feed.decimals))
+            .ok_or(PriceSourceError::OverflowError)
+    }
+}
+
+impl<AssetId: AsSymbol + Clone, AssetData, T: HttpTransport> PriceSource<AssetId, AssetData>
+    for EvmPriceSource<AssetId, AssetData, T>
+{
+    const PRICE_SOURCE_TYPE: &'static str = "evm";
+
+    fn new(
+        config: &OracleOffchainConfig,
+        assets_data: Vec<(AssetId, AssetData)>,
+    ) -> Result<Self, &'static str> {
+        Ok(EvmPriceSource {
+            settings: config.evm.clone().ok_or("No evm settings")?,
+            config: config.clone(),
+            assets_data,
+            _transport: PhantomData,
+        })
+    }
+
+    fn get_prices<F>(&self) -> Vec<(AssetId, Result<F, &'static str>)>
+    where
+        F: FixedPointNumber,
+    {
+        let client = match EvmClient::<T>::pinned(
+            &self.settings.node_url,
+            self.config.http_options(Self::PRICE_SOURCE_TYPE),
+            request_deadline(),
+        ) {
+            Ok(client) => client,
+            Err(err) => {
+                log::error!("Evm node is not available. Error: {:?}", err);
+                return Vec::new();
+            }
+        };
+
+        let mut asset_prices: Vec<(AssetId, Result<F, &'static str>)> =
+            Vec::with_capacity(self.settings.feeds.len());
+
+        for (asset, _) in &self.assets_data {
+            // only assets with feeds in settings are fed
+            let symbol = match asset.get_symbol() {
+                Some(symbol) => symbol,
+                None => continue,
+            };
+            let feed = match self
+                .settings
+                .feeds
+                .iter()
+                .find(|(feed_symbol, _)| feed_symbol.trim().eq_ignore_ascii_case(&symbol))
+            {
+                Some((_, feed)) => feed,
+                None => continue,
+            };
+            let price_strategy = match self.config.price_strategy(&symbol) {
+                Some(price_strategy) => price_strategy,
+                None => continue,
+            };
+
+            let price = Self::fetch_price::<F>(&client, feed)
+                .and_then(|price| apply_price_strategy(price, price_strategy));
+            asset_prices.push((
+                asset.clone(),
+                log_price_result(Self::PRICE_SOURCE_TYPE, symbol, price),
+            ));
+        }
+
+        asset_prices
+    }
+}
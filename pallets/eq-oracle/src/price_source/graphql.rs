@@ -0,0 +1,151 @@
+use super::{
+    apply_price_strategy,
+    http_client::{request_deadline, HttpClient, HttpTransport, OffchainHttp},
+    json::{check_price_bounds, PriceSourceError},
+    log_price_result, JsonPriceSource, PriceSource,
+};
+use crate::offchain_storage::{GraphqlSettings, HttpOptions, OracleOffchainConfig};
+use alloc::string::String;
+use primitives::AsSymbol;
+use serde::Serialize;
+use serde_json as json;
+use sp_arithmetic::FixedPointNumber;
+use sp_runtime::offchain::Timestamp;
+use sp_std::{collections::btree_map::BTreeMap, marker::PhantomData, vec::Vec};
+use utils::log;
+
+/// GraphQL price source, e.g. for DEX subgraphs.
+/// Posts the query from the `graphql` section of the offchain config for every asset
+/// and extracts the price from a response with a JSON path, as the `custom` source does,
+/// with an optional `@<timestamp path>` to the time the price was observed at.
+/// `{$}` in the query, variables and the path is replaced with the asset symbol.
+/// Requests are sent with `T`.
+#[derive(Debug)]
+pub struct GraphqlPriceSource<AssetId, AssetData, T = OffchainHttp> {
+    settings: GraphqlSettings,
+    http: HttpOptions,
+    config: OracleOffchainConfig,
+    assets_data: Vec<(AssetId, AssetData)>,
+    _transport: PhantomData<T>,
+}
+
+/// Body of a GraphQL request
+#[derive(Serialize)]
+struct GraphqlRequest<'a> {
+    query: String,
+    variables: BTreeMap<&'a str, String>,
+}
+
+impl<AssetId: AsSymbol, AssetData, T: HttpTransport> GraphqlPriceSource<AssetId, AssetData, T> {
+    /// Builds a request body for the asset symbol
+    pub(crate) fn request_body(
+        settings: &GraphqlSettings,
+        symbol: &str,
+    ) -> Result<String, PriceSourceError> {
+        let request = GraphqlRequest {
+            query: settings.query.replace("{$}", symbol),
+            variables: settings
+                .variables
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.replace("{$}", symbol)))
+                .collect(),
+        };
+
+        json::to_string(&request).map_err(|_| PriceSourceError::DeserializationError)
+    }
+
+    /// Fetches a price and, if the path has `@<timestamp path>`, its source timestamp
+    fn fetch_price<F: FixedPointNumber>(
+        &self,
+        symbol: &str,
+        deadline: Timestamp,
+    ) -> Result<(F, Option<u64>), PriceSourceError> {
+        let body = Self::request_body(&self.settings, symbol)?;
+        let response =
+            HttpClient::<T>::post(&self.settings.url, body.as_bytes(), &self.http, deadline)
+                .map_err(|e| {
+                    log::error!(
+                        "Http POST {:?} error: {:?}",
+                        self.http.redact(&self.settings.url),
+                        e
+                    );
+                    PriceSourceError::from(e)
+                })?;
+
+        JsonPriceSource::<AssetId, AssetData>::fetch_observation_from_json::<F>(
+            &response,
+            &self.settings.price_path.replace("{$}", symbol),
+        )
+    }
+}
+
+impl<AssetId: AsSymbol + Clone, AssetData, T: HttpTransport> PriceSource<AssetId, AssetData>
+    for GraphqlPriceSource<AssetId, AssetData, T>
+{
+    const PRICE_SOURCE_TYPE: &'static str = "graphql";
+
+    fn new(
+        config: &OracleOffchainConfig,
+        assets_data: Vec<(AssetId, AssetData)>,
+    ) -> Result<Self, &'static str> {
+        Ok(GraphqlPriceSource {
+            settings: config.graphql.clone().ok_or("No graphql settings")?,
+            http: config.http_options(Self::PRICE_SOURCE_TYPE),
+            config: config.clone(),
+            assets_data,
+            _transport: PhantomData,
+        })
+    }
+
+    fn get_prices<F>(&self) -> Vec<(AssetId, Result<F, &'static str>)>
+    where
+        F: FixedPointNumber,
+    {
+        self.get_observed_prices::<F>()
+            .into_iter()
+            .map(|(asset, price)| (asset, price.map(|(price, _)| price)))
+            .collect()
+    }
+
+    fn get_observed_prices<F>(&self) -> Vec<(AssetId, Result<(F, Option<u64>), &'static str>)>
+    where
+        F: FixedPointNumber,
+    {
+        let deadline = request_deadline();
+        let mut asset_prices: Vec<(AssetId, Result<(F, Option<u64>), &'static str>)> =
+            Vec::with_capacity(self.assets_data.len());
+
+        for (asset, _) in &self.assets_data {
+            // If specified, do not fetch non available currencies
+            let price_strategy = match asset
+                .get_symbol()
+                .and_then(|symbol| self.config.price_strategy(&symbol))
+            {
+                Some(price_strategy) => price_strategy,
+                None => continue,
+            };
+
+            let price = asset
+                .get_query_symbol(false)
+                .map(|symbol| {
+                    if self.settings.uppercase {
+                        symbol.to_uppercase()
+                    } else {
+                        symbol.to_lowercase()
+                    }
+                })
+                .ok_or(PriceSourceError::Symbol)
+                .and_then(|symbol| self.fetch_price::<F>(&symbol, deadline))
+                .and_then(|(price, timestamp)| {
+                    let price = apply_price_strategy(price, price_strategy)?;
+                    Ok((check_price_bounds(&self.config, asset, price)?, timestamp))
+                });
+            asset_prices.push((
+                asset.clone(),
+                log_price_result(Self::PRICE_SOURCE_TYPE, asset.get_symbol(), price),
+            ));
+        }
+
+        asset_prices
+    }
+}
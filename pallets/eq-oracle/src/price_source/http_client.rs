@@ -1,55 +1,433 @@
-use super::*;
-use sp_runtime::offchain::{http, Duration};
+use super::{json::PriceSourceError, *};
+use crate::offchain_storage::{HttpOptions, MAX_HTTP_REQUEST_TIME_MS, MAX_HTTP_RETRIES};
+use serde_json as json;
+use sp_runtime::offchain::{http, Duration, HttpError, Timestamp};
+use sp_std::marker::PhantomData;
 use utils::log;
 
-/// Send get request
-pub fn get(url: &str) -> Result<String, http::Error> {
-    let request = http::Request::get(url);
-    execute_request(request)
+/// Maximum length of a response body kept in `RequestError::Status`
+const BODY_SNIPPET_LEN: usize = 256;
+
+/// Reason why an http request failed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RequestError {
+    /// Request can't be sent, e.g. the url is invalid
+    Invalid,
+    /// No response before the deadline
+    DeadlineReached,
+    /// Connection or transfer error
+    IoError,
+    /// Response status is not 200, contains a beginning of the response body
+    Status { code: u16, body: String },
+    /// 429 response, contains a delay in seconds from the `Retry-After` header
+    RateLimited { retry_after: Option<u64> },
+    /// Response body is not a valid UTF-8 string
+    NotUtf8,
 }
 
-///Send post request with `body` and header Content-Type: application/json
-pub fn post(url: &str, body: Vec<&[u8]>) -> Result<String, http::Error> {
-    let mut request = http::Request::post(url, body);
-    request = request.add_header("Content-type", "application/json");
+impl From<HttpError> for RequestError {
+    fn from(error: HttpError) -> Self {
+        match error {
+            HttpError::DeadlineReached => RequestError::DeadlineReached,
+            HttpError::IoError => RequestError::IoError,
+            HttpError::Invalid => RequestError::Invalid,
+        }
+    }
+}
 
-    execute_request(request)
+impl From<http::Error> for RequestError {
+    fn from(error: http::Error) -> Self {
+        match error {
+            http::Error::DeadlineReached => RequestError::DeadlineReached,
+            http::Error::IoError => RequestError::IoError,
+            http::Error::Unknown => RequestError::Invalid,
+        }
+    }
 }
 
-fn execute_request<T: Default + IntoIterator<Item = I>, I: AsRef<[u8]>>(
-    request: http::Request<T>,
-) -> Result<String, http::Error> {
-    let deadline = sp_io::offchain::timestamp().add(Duration::from_millis(5_000));
+impl From<RequestError> for PriceSourceError {
+    fn from(error: RequestError) -> Self {
+        match error {
+            RequestError::Invalid | RequestError::IoError => PriceSourceError::HttpError,
+            RequestError::DeadlineReached => PriceSourceError::HttpDeadlineReached,
+            RequestError::Status { code, body } => PriceSourceError::HttpStatus { code, body },
+            RequestError::RateLimited { .. } => PriceSourceError::RateLimited,
+            RequestError::NotUtf8 => PriceSourceError::HttpNotUtf8,
+        }
+    }
+}
+
+/// Returns a deadline of all requests of a price source run.
+/// Sources get it once per run and pass it to every request, so their sequential calls
+/// with retries fit `MAX_HTTP_REQUEST_TIME_MS` together
+pub fn request_deadline() -> Timestamp {
+    sp_io::offchain::timestamp().add(Duration::from_millis(MAX_HTTP_REQUEST_TIME_MS))
+}
+
+/// Http method of a price source request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpMethod {
+    Get,
+    Post,
+}
+
+/// Http request of a price source with headers and query parameters from `HttpOptions`.
+/// Contains secrets, only `HttpOptions::redact`ed urls are logged
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn new(method: HttpMethod, url: &str, body: &[u8], options: &HttpOptions) -> Self {
+        let mut headers = Vec::with_capacity(options.headers.len() + 1);
+        if method == HttpMethod::Post {
+            headers.push(("Content-type".into(), "application/json".into()));
+        }
+        headers.extend(
+            options
+                .headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+
+        HttpRequest {
+            method,
+            url: with_query(url, options),
+            headers,
+            body: body.into(),
+        }
+    }
+}
+
+/// Transport of price source requests.
+/// Price sources are generic over it, so their requests can be recorded and replayed in tests
+pub trait HttpTransport {
+    /// Sends all requests at once and waits for their responses until `deadline`.
+    /// Returns responses in the order of requests
+    fn send_all(requests: &[HttpRequest], deadline: Timestamp)
+        -> Vec<Result<String, RequestError>>;
+}
+
+/// Transport of the offchain worker http api
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OffchainHttp;
+
+impl HttpTransport for OffchainHttp {
+    fn send_all(
+        requests: &[HttpRequest],
+        deadline: Timestamp,
+    ) -> Vec<Result<String, RequestError>> {
+        let mut results: Vec<Result<String, RequestError>> = requests
+            .iter()
+            .map(|_| Err(RequestError::DeadlineReached))
+            .collect();
+
+        let mut pending = Vec::with_capacity(requests.len());
+        let mut sent = Vec::with_capacity(requests.len());
+        for (index, request) in requests.iter().enumerate() {
+            let method = match request.method {
+                HttpMethod::Get => http::Method::Get,
+                HttpMethod::Post => http::Method::Post,
+            };
+            let body = if request.body.is_empty() {
+                Vec::new()
+            } else {
+                sp_std::vec![request.body.as_slice()]
+            };
+            let mut http_request = http::Request::new(&request.url)
+                .method(method)
+                .body(body)
+                .deadline(deadline);
+            for (name, value) in &request.headers {
+                http_request = http_request.add_header(name, value);
+            }
+
+            match http_request.send() {
+                Ok(request) => {
+                    pending.push(request);
+                    sent.push(index);
+                }
+                Err(error) => results[index] = Err(error.into()),
+            }
+        }
+
+        // no response or a timeout
+        let responses = http::PendingRequest::try_wait_all(pending, deadline);
+        for (index, response) in sent.into_iter().zip(responses) {
+            results[index] = match response {
+                Ok(Ok(response)) => read_response(response),
+                Ok(Err(error)) => Err(error.into()),
+                Err(_) => Err(RequestError::DeadlineReached),
+            };
+        }
+
+        results
+    }
+}
+
+/// Http client of price sources sending requests with `T`
+pub struct HttpClient<T = OffchainHttp>(PhantomData<T>);
+
+impl<T: HttpTransport> HttpClient<T> {
+    /// Send get request with headers and query parameters from `options`, retries fit `deadline`
+    pub fn get(
+        url: &str,
+        options: &HttpOptions,
+        deadline: Timestamp,
+    ) -> Result<String, RequestError> {
+        Self::execute_request(
+            HttpRequest::new(HttpMethod::Get, url, &[], options),
+            options,
+            deadline,
+        )
+    }
+
+    /// Sends get requests to all `urls` in parallel with headers and query parameters from `options`.
+    /// Retries fit `deadline`. Returns responses in the order of `urls`
+    pub fn get_all(
+        urls: &[String],
+        options: &HttpOptions,
+        deadline: Timestamp,
+    ) -> Vec<Result<String, RequestError>> {
+        let requests = urls
+            .iter()
+            .map(|url| HttpRequest::new(HttpMethod::Get, url, &[], options))
+            .collect();
+        Self::execute_requests(requests, options, deadline)
+    }
+
+    ///Send post request with `body`, header Content-Type: application/json,
+    /// headers and query parameters from `options`, retries fit `deadline`
+    pub fn post(
+        url: &str,
+        body: &[u8],
+        options: &HttpOptions,
+        deadline: Timestamp,
+    ) -> Result<String, RequestError> {
+        Self::execute_request(
+            HttpRequest::new(HttpMethod::Post, url, body, options),
+            options,
+            deadline,
+        )
+    }
 
-    let url = request.url.clone();
-    let pending = request.deadline(deadline).send().map_err(|e| {
-        log::error!(
-            "Error sending request. Request: {:?}, deadline: {:?}.",
-            url,
-            deadline
+    /// Sends a JSON-RPC 2.0 request with `params` array or object and returns its `result`.
+    /// Returns `JsonRpcError` if the node responds with an error
+    pub fn json_rpc(
+        url: &str,
+        method: &str,
+        params: &str,
+        options: &HttpOptions,
+        deadline: Timestamp,
+    ) -> Result<json::Value, PriceSourceError> {
+        let body = alloc::format!(
+            "{{\"jsonrpc\":\"2.0\",\"method\":\"{}\",\"params\":{},\"id\":1}}",
+            method,
+            params
         );
-        match e {
-            sp_runtime::offchain::HttpError::DeadlineReached => http::Error::DeadlineReached,
-            sp_runtime::offchain::HttpError::IoError => http::Error::IoError,
-            sp_runtime::offchain::HttpError::Invalid => http::Error::Unknown,
-        }
-    })?;
-    // no response or a timeout
-    let response = pending
-        .try_wait(deadline)
-        .map_err(|_| {
-            log::error!("Didn't receive response. Deadline: {:?}.", deadline);
-            http::Error::DeadlineReached
-        })?
-        .map_err(|e| {
-            log::error!("RESPONSE {:?}", e);
-            e
-        })?;
-    if response.code != 200 {
-        log::error!("Unexpected status code: {}", response.code);
-        return Err(http::Error::Unknown);
-    }
-    let body = response.body();
-    let str = String::from_utf8(body.collect()).unwrap_or(String::new());
-    Ok(str)
+
+        let response = Self::post(url, body.as_bytes(), options, deadline)?;
+
+        let mut json_value = json::from_str::<json::Value>(&response)
+            .map_err(|_| PriceSourceError::JsonParseError)?;
+
+        match json_value.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => {
+                log::error!(
+                    "{}:{}. Error response from {}. Error: {:?}, params: {:?}.",
+                    file!(),
+                    line!(),
+                    method,
+                    json_value.get("error"),
+                    params
+                );
+                Err(PriceSourceError::JsonRpcError)
+            }
+        }
+    }
+
+    /// Executes a request with retries, all attempts and delays fit `deadline`
+    fn execute_request(
+        request: HttpRequest,
+        options: &HttpOptions,
+        deadline: Timestamp,
+    ) -> Result<String, RequestError> {
+        Self::execute_requests(sp_std::vec![request], options, deadline)
+            .pop()
+            .unwrap_or(Err(RequestError::Invalid))
+    }
+
+    /// Executes requests in parallel: all requests are sent first and then awaited with one deadline.
+    /// Failed requests are retried together, all attempts and delays fit `request_deadline`.
+    /// Returns results in the order of requests
+    fn execute_requests(
+        requests: Vec<HttpRequest>,
+        options: &HttpOptions,
+        request_deadline: Timestamp,
+    ) -> Vec<Result<String, RequestError>> {
+        // secrets from options are never logged
+        let urls: Vec<String> = requests
+            .iter()
+            .map(|request| options.redact(&request.url))
+            .collect();
+
+        let mut results: Vec<Result<String, RequestError>> = requests
+            .iter()
+            .map(|_| Err(RequestError::Invalid))
+            .collect();
+        // indices of requests of the current attempt
+        let mut attempted: Vec<usize> = (0..requests.len()).collect();
+        let mut attempt = 0;
+        while !attempted.is_empty() {
+            let now = sp_io::offchain::timestamp();
+            let deadline = earliest(
+                now.add(Duration::from_millis(options.timeout())),
+                request_deadline,
+            );
+
+            let batch: Vec<HttpRequest> = attempted
+                .iter()
+                .map(|&index| requests[index].clone())
+                .collect();
+            for (&index, result) in attempted.iter().zip(T::send_all(&batch, deadline)) {
+                results[index] = result;
+            }
+
+            // requests which can't be retried before `request_deadline` are not retried,
+            // so a long `Retry-After` doesn't hold back other requests
+            let now = sp_io::offchain::timestamp();
+            let fits_deadline = |delay: &u64| {
+                now.add(Duration::from_millis(*delay)).unix_millis()
+                    < request_deadline.unix_millis()
+            };
+            let mut delay = 0;
+            let mut retried = Vec::new();
+            for index in attempted {
+                if let Err(error) = &results[index] {
+                    log::error!(
+                        "Http request failed. Request: {:?}, attempt: {}, error: {:?}.",
+                        urls[index],
+                        attempt,
+                        error
+                    );
+                    if let Some(retry_delay) =
+                        retry_delay(options, attempt, error).filter(fits_deadline)
+                    {
+                        delay = delay.max(retry_delay);
+                        retried.push(index);
+                    }
+                }
+            }
+            if retried.is_empty() {
+                break;
+            }
+
+            sp_io::offchain::sleep_until(now.add(Duration::from_millis(delay)));
+            attempted = retried;
+            attempt += 1;
+        }
+
+        results
+    }
+}
+
+/// Appends query parameters from `options` to `url`
+pub fn with_query(url: &str, options: &HttpOptions) -> String {
+    let mut url = String::from(url);
+    for (name, value) in &options.query {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        url.push_str(&percent_encode(name));
+        url.push('=');
+        url.push_str(&percent_encode(value));
+    }
+    url
+}
+
+/// Percent encodes all bytes except unreserved characters of RFC 3986
+pub(crate) fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&alloc::format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// Returns a delay in milliseconds before a retry of the failed `attempt`, starting from 0,
+/// or `None` if the request shouldn't be retried.
+/// Timeouts, io errors and 5xx responses are retried with exponential backoff,
+/// 429 responses are retried after `Retry-After` if it's set
+pub(crate) fn retry_delay(
+    options: &HttpOptions,
+    attempt: u32,
+    error: &RequestError,
+) -> Option<u64> {
+    if attempt >= options.retries.min(MAX_HTTP_RETRIES) {
+        return None;
+    }
+    let backoff = options.backoff().saturating_mul(1 << attempt);
+
+    match error {
+        RequestError::DeadlineReached | RequestError::IoError => Some(backoff),
+        RequestError::Status { code, .. } if *code >= 500 => Some(backoff),
+        RequestError::RateLimited { retry_after } => Some(
+            retry_after
+                .map(|seconds| seconds.saturating_mul(1_000))
+                .unwrap_or(backoff),
+        ),
+        _ => None,
+    }
+}
+
+/// Parses a `Retry-After` header in seconds, http dates are not supported
+pub(crate) fn parse_retry_after(value: &str) -> Option<u64> {
+    value.trim().parse::<u64>().ok()
+}
+
+/// Returns a beginning of a response body for logs and errors
+pub(crate) fn body_snippet(body: &[u8]) -> String {
+    let snippet = &body[..body.len().min(BODY_SNIPPET_LEN)];
+    String::from_utf8_lossy(snippet).into_owned()
+}
+
+fn earliest(a: Timestamp, b: Timestamp) -> Timestamp {
+    if a.unix_millis() <= b.unix_millis() {
+        a
+    } else {
+        b
+    }
+}
+
+/// Reads a body of a successful response
+fn read_response(response: http::Response) -> Result<String, RequestError> {
+    let code = response.code;
+    if code == 429 {
+        let mut headers = response.headers().into_iter();
+        let mut retry_after = None;
+        while headers.next() {
+            if let Some((name, value)) = headers.current() {
+                if name.eq_ignore_ascii_case("retry-after") {
+                    retry_after = parse_retry_after(value);
+                }
+            }
+        }
+        return Err(RequestError::RateLimited { retry_after });
+    }
+
+    let body: Vec<u8> = response.body().collect();
+    if code != 200 {
+        return Err(RequestError::Status {
+            code,
+            body: body_snippet(&body),
+        });
+    }
+    String::from_utf8(body).map_err(|_| RequestError::NotUtf8)
 }
@@ -1,74 +1,244 @@
-use super::{http_client, PriceSource};
-use crate::offchain_storage;
+use super::{
+    apply_price_strategy,
+    http_client::{request_deadline, HttpClient, HttpTransport, OffchainHttp, RequestError},
+    log_price_result, PriceSource,
+};
+use crate::offchain_storage::{HttpOptions, OracleOffchainConfig, PriceStrategy};
 use crate::regex_offsets::{get_index_offsets, get_url_offset};
 use alloc::string::String;
+use core::convert::TryFrom;
 use serde_json as json;
 use sp_arithmetic::FixedPointNumber;
-use sp_std::vec::Vec;
+use sp_runtime::offchain::Timestamp;
+use sp_std::{marker::PhantomData, vec::Vec};
 
 use primitives::AsSymbol;
-use utils::log;
+use utils::{
+    fixed::{parse_decimal, DecimalError},
+    log,
+};
 
-/// Json price source. Gets prices for assets from setting "oracle::source_assets"
-/// or for all assets if no settings specified. Also uses price_strategy from "oracle::source_assets"
-/// if specifies. Price strategy define how to interpret value from source (price, reverse)
+/// Json price source. Gets prices for assets from config `source_assets`
+/// or for all assets if no settings specified. Also uses price strategy from `source_assets`
+/// if specifies. Price strategy define how to interpret value from source (price, reverse).
+/// Requests are sent with `T`
 #[derive(Debug)]
-pub struct JsonPriceSource<AssetId, AssetData> {
-    /// Full query, containing url template and path to price in json
+pub struct JsonPriceSource<AssetId, AssetData, T = OffchainHttp> {
+    /// Full query, containing url template, path to price in json
+    /// and an optional path to a timestamp after `@`
     /// example: json(https://ftx.com/api/markets/{$}/USD).result.price
     query: String,
+    http: HttpOptions,
+    config: OracleOffchainConfig,
     assets_data: Vec<(AssetId, AssetData)>,
+    _transport: PhantomData<T>,
 }
 
-impl<AssetId: AsSymbol, AssetData> JsonPriceSource<AssetId, AssetData> {
-    /// Fetches a price for an asset from a URL source with the query
-    fn fetch_price<F: FixedPointNumber>(
-        asset: &AssetId,
-        query: &str,
-    ) -> Result<F, PriceSourceError> {
+impl<AssetId: AsSymbol, AssetData, T: HttpTransport> JsonPriceSource<AssetId, AssetData, T> {
+    /// Splits the query into a url template and a path template
+    pub(crate) fn parse_query(query: &str) -> Result<(&str, &str), PriceSourceError> {
         let (start, end) = get_url_offset(query.as_bytes()).ok_or_else(|| {
             log::error!("Incorrect query format, can't parse. Query: {}", query);
             PriceSourceError::IncorrectQueryFormat
         })?;
 
         // regex is \(.+\)\.
-        let url_template = &query[start + 1..end - 2];
-        if !url_template.contains("{$}") {
+        Ok((&query[start + 1..end - 2], &query[end..]))
+    }
+
+    /// Fetches prices of every asset with its own request, all requests are sent at once
+    fn fetch_each<F: FixedPointNumber>(
+        &self,
+        assets: &[(&AssetId, Result<PriceStrategy, PriceSourceError>)],
+        url_template: &str,
+        path_template: &str,
+        deadline: Timestamp,
+    ) -> Vec<Result<(F, Option<u64>), PriceSourceError>> {
+        let requests: Vec<_> = assets
+            .iter()
+            .map(|(asset, price_strategy)| {
+                price_strategy
+                    .clone()
+                    .and_then(|_| asset.get_url(url_template, path_template))
+            })
+            .collect();
+
+        let urls: Vec<String> = requests
+            .iter()
+            .filter_map(|request| request.as_ref().ok())
+            .map(|(url, _)| url.clone())
+            .collect();
+        let mut responses = HttpClient::<T>::get_all(&urls, &self.http, deadline).into_iter();
+
+        requests
+            .into_iter()
+            .map(|request| {
+                let (url, path) = request?;
+                let response = responses
+                    .next()
+                    .unwrap_or(Err(RequestError::Invalid))
+                    .map_err(|e| {
+                        log::error!("Http GET {:?} error: {:?}", self.http.redact(&url), e);
+                        PriceSourceError::from(e)
+                    })?;
+                Self::fetch_observation_from_json::<F>(&response, &path)
+            })
+            .collect()
+    }
+
+    /// Fetches prices of all assets with one request, `{*}` in the url template is replaced
+    /// with comma separated asset symbols. Every price is extracted with the path template
+    fn fetch_bulk<F: FixedPointNumber>(
+        &self,
+        assets: &[(&AssetId, Result<PriceStrategy, PriceSourceError>)],
+        url_template: &str,
+        path_template: &str,
+        deadline: Timestamp,
+    ) -> Vec<Result<(F, Option<u64>), PriceSourceError>> {
+        if !split_path(path_template).0.contains("{$}") {
             log::error!(
-                "Incorrect query format, doesn't have {{$}}. Query: {}, url template: {:?}.",
-                query,
-                url_template
+                "Incorrect query format, path of a bulk query doesn't have {{$}}. Path template: {:?}.",
+                path_template
             );
-            frame_support::fail!(PriceSourceError::WrongUrlPattern)
+            return assets
+                .iter()
+                .map(|_| Err(PriceSourceError::WrongUrlPattern))
+                .collect();
         }
 
-        let path_template = &query[end..];
-        let (url, path) = asset.get_url(url_template, path_template)?;
-        let s = http_client::get(url.as_str()).map_err(|e| {
-            let e = match e {
-                sp_runtime::offchain::http::Error::DeadlineReached => "DEADLINE",
-                sp_runtime::offchain::http::Error::IoError => "IO_ERROR",
-                sp_runtime::offchain::http::Error::Unknown => "UNKNOWN",
-            };
-            log::error!("Http GET {:?} error: {:?}", url, e);
-            PriceSourceError::HttpError
-        })?;
+        let symbols: Vec<_> = assets
+            .iter()
+            .map(|(asset, price_strategy)| {
+                price_strategy
+                    .clone()
+                    .and_then(|_| asset.get_url_symbol(url_template))
+            })
+            .collect();
+        let joined = symbols
+            .iter()
+            .filter_map(|symbol| symbol.as_ref().ok().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(",");
+        if joined.is_empty() {
+            return symbols
+                .into_iter()
+                .map(|symbol| Err(symbol.err().unwrap_or(PriceSourceError::Symbol)))
+                .collect();
+        }
+
+        let url = url_template.replace("{*}", &joined);
+        let response = HttpClient::<T>::get(&url, &self.http, deadline)
+            .map_err(|e| {
+                log::error!("Http GET {:?} error: {:?}", self.http.redact(&url), e);
+                PriceSourceError::from(e)
+            })
+            .and_then(|body| Self::parse_json(&body));
 
-        Self::fetch_price_from_json::<F>(s, path.as_str())
+        symbols
+            .into_iter()
+            .map(|symbol| {
+                let symbol = symbol?;
+                let response = response.as_ref().map_err(Clone::clone)?;
+                Self::observation_from_json::<F>(response, &path_template.replace("{$}", &symbol))
+            })
+            .collect()
     }
 
-    /// Fetches a price from a collected JSON
-    pub(crate) fn fetch_price_from_json<F: FixedPointNumber>(
-        body: String,
-        path: &str,
-    ) -> Result<F, PriceSourceError> {
-        let mut val: &json::Value = &json::from_str(&body).map_err(|_| {
+    fn parse_json(body: &str) -> Result<json::Value, PriceSourceError> {
+        json::from_str(body).map_err(|_| {
             log::error!(
                 "Cannot deserialize an instance from a string to JSON. String: {:?}.",
                 body
             );
             PriceSourceError::DeserializationError
+        })
+    }
+
+    /// Fetches a price and its optional source timestamp from a collected JSON
+    pub(crate) fn fetch_observation_from_json<F: FixedPointNumber>(
+        body: &str,
+        path: &str,
+    ) -> Result<(F, Option<u64>), PriceSourceError> {
+        Self::observation_from_json(&Self::parse_json(body)?, path)
+    }
+
+    /// Extracts a price and, if the path has `@<timestamp path>`, its source timestamp
+    pub(crate) fn observation_from_json<F: FixedPointNumber>(
+        json: &json::Value,
+        path: &str,
+    ) -> Result<(F, Option<u64>), PriceSourceError> {
+        let (price_path, timestamp_path) = split_path(path);
+        let price = Self::price_from_json::<F>(json, price_path)?;
+        let timestamp = timestamp_path
+            .map(|timestamp_path| Self::timestamp_from_json(json, timestamp_path))
+            .transpose()?;
+
+        Ok((price, timestamp))
+    }
+
+    /// Extracts a unix time in seconds from a parsed JSON
+    pub(crate) fn timestamp_from_json(
+        json: &json::Value,
+        path: &str,
+    ) -> Result<u64, PriceSourceError> {
+        let val = Self::value_from_json(json, path)?;
+        let timestamp = match val {
+            json::Value::Number(v) => parse_timestamp(&v.to_string()),
+            json::Value::String(v) => parse_timestamp(v),
+            _ => None,
+        };
+
+        timestamp.ok_or_else(|| {
+            log::error!("Couldn't get value as a timestamp. Value: {:?}.", val);
+            PriceSourceError::InvalidTimestamp
+        })
+    }
+
+    /// Extracts a price from a parsed JSON
+    pub(crate) fn price_from_json<F: FixedPointNumber>(
+        json: &json::Value,
+        path: &str,
+    ) -> Result<F, PriceSourceError> {
+        let val = Self::value_from_json(json, path)?;
+
+        let maybe_price = match val {
+            // `arbitrary_precision` keeps the source text of the number
+            json::Value::Number(v) => parse_decimal::<F>(&v.to_string()),
+            json::Value::String(v) => parse_decimal::<F>(v.trim()),
+            _ => {
+                log::error!(
+                    "Value received from json not number or string. Value: {:?}.",
+                    val
+                );
+                frame_support::fail!(PriceSourceError::JsonValueNotANumber)
+            }
+        };
+
+        let price = maybe_price.map_err(|err| {
+            log::error!(
+                "Couldn't get value as a decimal. Value: {:?}, error: {:?}.",
+                val,
+                err
+            );
+            match err {
+                DecimalError::Invalid => PriceSourceError::JsonPriceConversionError,
+                DecimalError::Overflow => PriceSourceError::OverflowError,
+            }
         })?;
+        if price.is_negative() {
+            log::error!("Negative price received from json. Value: {:?}.", val);
+            frame_support::fail!(PriceSourceError::NegativePrice)
+        }
+
+        Ok(price)
+    }
+
+    /// Gets a value of a parsed JSON by the path
+    fn value_from_json<'a>(
+        json: &'a json::Value,
+        path: &str,
+    ) -> Result<&'a json::Value, PriceSourceError> {
+        let mut val = json;
 
         let indices = path.split(".");
         for index in indices {
@@ -96,9 +266,13 @@ impl<AssetId: AsSymbol, AssetData> JsonPriceSource<AssetId, AssetData> {
                         })?;
                     }
 
-                    let i = &index[start + 1..end - 1]
-                        .parse::<usize>()
-                        .expect("Expect a number as array index");
+                    let i = index[start + 1..end - 1].parse::<usize>().map_err(|_| {
+                        log::error!(
+                            "Array index doesn't fit usize. Index: {:?}.",
+                            &index[start..end]
+                        );
+                        PriceSourceError::JsonParseError
+                    })?;
 
                     val = val.get(i).ok_or_else(|| {
                         log::error!(
@@ -112,36 +286,138 @@ impl<AssetId: AsSymbol, AssetData> JsonPriceSource<AssetId, AssetData> {
             }
         }
 
-        let maybe_price = match val {
-            json::Value::Number(v) => v.as_f64(),
-            json::Value::String(v) => v.parse::<f64>().ok(),
-            _ => {
-                log::error!(
-                    "Value received from json not number or string. Value: {:?}.",
-                    val
-                );
-                frame_support::fail!(PriceSourceError::JsonValueNotANumber)
+        Ok(val)
+    }
+}
+
+/// Splits a path template into a price path and an optional timestamp path after `@`
+fn split_path(path_template: &str) -> (&str, Option<&str>) {
+    match path_template.split_once('@') {
+        Some((price_path, timestamp_path)) => (price_path, Some(timestamp_path)),
+        None => (path_template, None),
+    }
+}
+
+/// Parses a unix time in seconds, milliseconds, microseconds or nanoseconds,
+/// told apart by magnitude, or an RFC 3339 date time, e.g. `2022-05-01T12:00:00.123Z`,
+/// into unix seconds
+pub(crate) fn parse_timestamp(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if !value.bytes().all(|c| c.is_ascii_digit() || c == b'.') {
+        return parse_rfc3339(value);
+    }
+
+    let integral = value.split('.').next()?;
+    if integral.is_empty() || value.matches('.').count() > 1 {
+        return None;
+    }
+    let mut timestamp = integral.parse::<u64>().ok()?;
+    // 10^11 seconds is the year 5138
+    while timestamp >= 100_000_000_000 {
+        timestamp /= 1000;
+    }
+    Some(timestamp)
+}
+
+/// Parses an RFC 3339 date time `YYYY-MM-DDTHH:MM:SS[.fraction](Z|+HH:MM|-HH:MM)`
+/// into unix seconds
+fn parse_rfc3339(value: &str) -> Option<u64> {
+    let bytes = value.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let number = |digits: Option<&str>| -> Option<i64> {
+        let digits = digits?;
+        if digits.bytes().all(|c| c.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    };
+    let (year, month, day) = (
+        number(value.get(0..4))?,
+        number(value.get(5..7))?,
+        number(value.get(8..10))?,
+    );
+    let (hour, minute, second) = (
+        number(value.get(11..13))?,
+        number(value.get(14..16))?,
+        number(value.get(17..19))?,
+    );
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let mut rest = value.get(19..)?;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        rest = &fraction[digits..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let offset = number(rest.get(1..3))? * 3_600 + number(rest.get(4..6))? * 60;
+            match rest.as_bytes()[0] {
+                b'+' => offset,
+                b'-' => -offset,
+                _ => return None,
             }
-        };
+        }
+        _ => return None,
+    };
 
-        let price = maybe_price.ok_or_else(|| {
-            log::error!("Couldn't get value as f64. Value: {:?}.", val);
-            PriceSourceError::JsonPriceConversionError
-        })?;
+    // days since the unix epoch of a proleptic Gregorian date
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
 
-        const MAX_ACCURACY: u128 = 1_000_000_000_000;
-        Ok(F::saturating_from_rational(
-            (price * MAX_ACCURACY as f64) as u128,
-            MAX_ACCURACY,
-        ))
+    let timestamp = days * 86_400 + hour * 3_600 + minute * 60 + second - offset;
+    u64::try_from(timestamp).ok()
+}
+
+/// Rejects a price out of `price_bounds` of the asset
+pub(crate) fn check_price_bounds<AssetId: AsSymbol, F: FixedPointNumber>(
+    config: &OracleOffchainConfig,
+    asset: &AssetId,
+    price: F,
+) -> Result<F, PriceSourceError> {
+    let symbol = asset.get_symbol().ok_or(PriceSourceError::Symbol)?;
+    if config.is_price_in_bounds(&symbol, price) {
+        Ok(price)
+    } else {
+        Err(PriceSourceError::PriceOutOfBounds)
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum PriceSourceError {
     HttpError,
+    HttpStatus { code: u16, body: String },
+    HttpNotUtf8,
+    HttpDeadlineReached,
     WrongUrlPattern,
-    NoQueryStringInStorage,
+    NoCustomQuery,
     IncorrectQueryFormat,
     DeserializationError,
     JsonParseError,
@@ -149,14 +425,30 @@ pub enum PriceSourceError {
     JsonPriceConversionError,
     UnknownPriceStrategy,
     Symbol,
+    OverflowError,
+    NoPoolTokenPrice,
+    AbiError,
+    InvalidContractValue,
+    StaleRound,
+    IncompleteRound,
+    JsonRpcError,
+    NoRemoteValue,
+    ScaleDecodeError,
+    RateLimited,
+    NegativePrice,
+    PriceOutOfBounds,
+    InvalidTimestamp,
 }
 
 impl From<PriceSourceError> for &'static str {
     fn from(error: PriceSourceError) -> Self {
         match error {
             PriceSourceError::HttpError => "Http error",
+            PriceSourceError::HttpStatus { .. } => "Http status error",
+            PriceSourceError::HttpNotUtf8 => "Http response is not utf-8",
+            PriceSourceError::HttpDeadlineReached => "Http deadline reached",
             PriceSourceError::WrongUrlPattern => "Wrong url pattern",
-            PriceSourceError::NoQueryStringInStorage => "No query string in storage",
+            PriceSourceError::NoCustomQuery => "No custom_query in config",
             PriceSourceError::IncorrectQueryFormat => "Incorrect query format",
             PriceSourceError::DeserializationError => "Deserialization error",
             PriceSourceError::JsonParseError => "Json parse error",
@@ -164,19 +456,41 @@ impl From<PriceSourceError> for &'static str {
             PriceSourceError::JsonPriceConversionError => "Json price conversion error",
             PriceSourceError::UnknownPriceStrategy => "Unknown price strategy",
             PriceSourceError::Symbol => "Symbol",
+            PriceSourceError::OverflowError => "Overflow error",
+            PriceSourceError::NoPoolTokenPrice => "No pool token price",
+            PriceSourceError::AbiError => "Abi error",
+            PriceSourceError::InvalidContractValue => "Invalid contract value",
+            PriceSourceError::StaleRound => "Stale round",
+            PriceSourceError::IncompleteRound => "Incomplete round",
+            PriceSourceError::JsonRpcError => "Json rpc error",
+            PriceSourceError::NoRemoteValue => "No remote value",
+            PriceSourceError::ScaleDecodeError => "Scale decode error",
+            PriceSourceError::RateLimited => "Rate limited",
+            PriceSourceError::NegativePrice => "Negative price",
+            PriceSourceError::PriceOutOfBounds => "Price out of bounds",
+            PriceSourceError::InvalidTimestamp => "Invalid timestamp",
         }
     }
 }
 
-impl<AssetId: AsSymbol + Clone, AssetData> PriceSource<AssetId, AssetData>
-    for JsonPriceSource<AssetId, AssetData>
+impl<AssetId: AsSymbol + Clone, AssetData, T: HttpTransport> PriceSource<AssetId, AssetData>
+    for JsonPriceSource<AssetId, AssetData, T>
 {
     const PRICE_SOURCE_TYPE: &'static str = "custom";
 
-    fn new(assets_data: Vec<(AssetId, AssetData)>) -> Result<Self, &'static str> {
+    fn new(
+        config: &OracleOffchainConfig,
+        assets_data: Vec<(AssetId, AssetData)>,
+    ) -> Result<Self, &'static str> {
         Ok(JsonPriceSource {
-            query: offchain_storage::get_query().ok_or("No query string in storage")?,
+            query: config
+                .custom_query
+                .clone()
+                .ok_or(PriceSourceError::NoCustomQuery)?,
+            http: config.http_options(Self::PRICE_SOURCE_TYPE),
+            config: config.clone(),
             assets_data,
+            _transport: PhantomData,
         })
     }
 
@@ -184,46 +498,53 @@ impl<AssetId: AsSymbol + Clone, AssetData> PriceSource<AssetId, AssetData>
     where
         F: FixedPointNumber,
     {
-        let asset_settings = offchain_storage::get_asset_settings();
-        let empty_settings = asset_settings.is_empty();
-        let mut asset_prices: Vec<(AssetId, Result<F, &'static str>)> =
-            Vec::with_capacity(self.assets_data.len());
-
-        for asset in &self.assets_data {
-            let (asset, _) = asset;
+        self.get_observed_prices::<F>()
+            .into_iter()
+            .map(|(asset, price)| (asset, price.map(|(price, _)| price)))
+            .collect()
+    }
 
+    fn get_observed_prices<F>(&self) -> Vec<(AssetId, Result<(F, Option<u64>), &'static str>)>
+    where
+        F: FixedPointNumber,
+    {
+        // assets to fetch with their price strategies
+        let mut assets = Vec::with_capacity(self.assets_data.len());
+        for (asset, _) in &self.assets_data {
             // If specified, do not fetch non available currencies
-            let price = if empty_settings {
-                offchain_storage::clear_asset_settings();
-                Self::fetch_price(asset, &self.query)
-            } else {
-                if let Some(symbol) = asset.get_symbol() {
-                    match asset_settings.get(&symbol) {
-                        Some(price_strategy) => Self::fetch_price::<F>(&asset, &self.query)
-                            .and_then(|price| match price_strategy.as_str() {
-                                "price" => Ok(price),
-                                "reverse" => {
-                                    Ok(price.reciprocal().expect("Price should be more than 0"))
-                                }
-                                _ => Err(PriceSourceError::UnknownPriceStrategy),
-                            }),
-                        _ => continue, // skip asset
-                    }
-                } else {
-                    Err(PriceSourceError::Symbol)
-                }
-            };
+            match asset.get_symbol() {
+                Some(symbol) => match self.config.price_strategy(&symbol) {
+                    Some(price_strategy) => assets.push((asset, Ok(price_strategy))),
+                    None => continue, // skip asset
+                },
+                None => assets.push((asset, Err(PriceSourceError::Symbol))),
+            }
+        }
 
-            if let Err(err) = &price {
-                log::error!(
-                    "{}:{} Custom price source return error. Asset: {:?}, error: {:?}",
-                    file!(),
-                    line!(),
-                    asset.get_symbol(),
-                    err,
-                );
-            };
-            asset_prices.push((asset.clone(), price.map_err(From::from)));
+        let deadline = request_deadline();
+        let prices = match Self::parse_query(&self.query) {
+            // one request per asset
+            Ok((url_template, path_template)) if url_template.contains("{$}") => {
+                self.fetch_each::<F>(&assets, url_template, path_template, deadline)
+            }
+            // one request for all assets
+            Ok((url_template, path_template)) => {
+                self.fetch_bulk::<F>(&assets, url_template, path_template, deadline)
+            }
+            Err(err) => assets.iter().map(|_| Err(err.clone())).collect(),
+        };
+
+        let mut asset_prices: Vec<(AssetId, Result<(F, Option<u64>), &'static str>)> =
+            Vec::with_capacity(assets.len());
+        for ((asset, price_strategy), price) in assets.into_iter().zip(prices) {
+            let price = price.and_then(|(price, timestamp)| {
+                let price = apply_price_strategy(price, price_strategy?)?;
+                Ok((check_price_bounds(&self.config, asset, price)?, timestamp))
+            });
+            asset_prices.push((
+                asset.clone(),
+                log_price_result(Self::PRICE_SOURCE_TYPE, asset.get_symbol(), price),
+            ));
         }
 
         asset_prices
@@ -238,6 +559,9 @@ pub(crate) trait WithUrl {
         url_template: &str,
         path_template: &str,
     ) -> Result<(String, String), PriceSourceError>;
+
+    /// Gets a symbol put in `url_template`
+    fn get_url_symbol(&self, url_template: &str) -> Result<String, PriceSourceError>;
 }
 
 impl<AssetId: AsSymbol> WithUrl for AssetId {
@@ -249,25 +573,30 @@ impl<AssetId: AsSymbol> WithUrl for AssetId {
         url_template: &str,
         path_template: &str,
     ) -> Result<(String, String), PriceSourceError> {
-        let is_upper_case = url_template.find("USD").is_some();
-        let symbol = {
-            let is_kraken = url_template.contains("api.kraken.com");
-            let symbol = self
-                .get_query_symbol(is_kraken)
-                .ok_or(PriceSourceError::Symbol)?;
-
-            if is_upper_case {
-                symbol.to_uppercase()
-            } else {
-                symbol.to_lowercase()
-            }
-        };
+        let symbol = self.get_url_symbol(url_template)?;
 
         Ok((
             url_template.replace("{$}", &symbol),
             path_template.replace("{$}", &symbol),
         ))
     }
+
+    /// Gets a symbol
+    ///
+    /// Symbol is upper case if `url_template` has `USD` and lower case otherwise
+    fn get_url_symbol(&self, url_template: &str) -> Result<String, PriceSourceError> {
+        let is_upper_case = url_template.find("USD").is_some();
+        let is_kraken = url_template.contains("api.kraken.com");
+        let symbol = self
+            .get_query_symbol(is_kraken)
+            .ok_or(PriceSourceError::Symbol)?;
+
+        if is_upper_case {
+            Ok(symbol.to_uppercase())
+        } else {
+            Ok(symbol.to_lowercase())
+        }
+    }
 }
 
 // /// Returns a symbolic ticker
@@ -1,28 +1,88 @@
+pub mod chainlink;
+pub mod evm;
+pub mod graphql;
 pub mod http_client;
 pub mod json;
+pub mod pancake;
+pub mod substrate;
+pub use chainlink::ChainlinkPriceSource;
+pub use evm::EvmPriceSource;
+pub use graphql::GraphqlPriceSource;
 pub use json::JsonPriceSource;
+pub use pancake::PancakePriceSource;
+pub use substrate::SubstratePriceSource;
 
+use crate::offchain_storage::{OracleOffchainConfig, PriceStrategy};
 use alloc::string::String;
+use core::fmt::Debug;
+use json::PriceSourceError;
 use sp_runtime::FixedPointNumber;
 use sp_std::vec::Vec;
+use utils::log;
 
 /// Price source abstraction. Settings of price source stored in offchain local storage.
 pub trait PriceSource<AssetId, AssetData>: Sized {
     const PRICE_SOURCE_TYPE: &'static str;
 
-    fn new(assets_data: Vec<(AssetId, AssetData)>) -> Result<Self, &'static str>;
+    fn new(
+        config: &OracleOffchainConfig,
+        assets_data: Vec<(AssetId, AssetData)>,
+    ) -> Result<Self, &'static str>;
 
     /// Returns collection of (asset, price result)
     fn get_prices<F>(&self) -> Vec<(AssetId, Result<F, &'static str>)>
     where
         F: FixedPointNumber;
+
+    /// Returns collection of (asset, price result with a unix time in seconds when the price
+    /// was observed by the source). Sources without timestamps return `None`
+    fn get_observed_prices<F>(&self) -> Vec<(AssetId, Result<(F, Option<u64>), &'static str>)>
+    where
+        F: FixedPointNumber,
+    {
+        self.get_prices::<F>()
+            .into_iter()
+            .map(|(asset, price)| (asset, price.map(|price| (price, None))))
+            .collect()
+    }
+}
+
+/// Converts a value received from a price source to a price with a price strategy of the asset
+pub(crate) fn apply_price_strategy<F: FixedPointNumber>(
+    value: F,
+    price_strategy: PriceStrategy,
+) -> Result<F, PriceSourceError> {
+    match price_strategy {
+        PriceStrategy::Price => Ok(value),
+        PriceStrategy::Reverse => value
+            .reciprocal()
+            .ok_or(PriceSourceError::JsonPriceConversionError),
+    }
+}
+
+/// Logs an error of a price source and converts a price result of an asset to be returned
+pub(crate) fn log_price_result<T>(
+    source_type: &str,
+    asset: impl Debug,
+    price: Result<T, PriceSourceError>,
+) -> Result<T, &'static str> {
+    if let Err(err) = &price {
+        log::error!(
+            target: "eq_oracle",
+            "Price source {} returned an error. Asset: {:?}, error: {:?}",
+            source_type,
+            asset,
+            err,
+        );
+    }
+    price.map_err(From::from)
 }
 
 pub trait PriceSourcePeeker<AssetId, AssetData> {
     fn get_prices<F>(
-        price_source_type: impl AsRef<str>,
+        config: &OracleOffchainConfig,
         assets_data: &Vec<(AssetId, AssetData)>,
-    ) -> Result<Vec<(AssetId, Result<F, &'static str>)>, Option<&'static str>>
+    ) -> Result<Vec<(AssetId, Result<(F, Option<u64>), &'static str>)>, Option<&'static str>>
     where
         F: FixedPointNumber;
 }
@@ -35,32 +95,32 @@ where
     P: PriceSource<AssetId, AssetData>,
 {
     fn get_prices<F>(
-        price_source_type: impl AsRef<str>,
+        config: &OracleOffchainConfig,
         assets_data: &Vec<(AssetId, AssetData)>,
-    ) -> Result<Vec<(AssetId, Result<F, &'static str>)>, Option<&'static str>>
+    ) -> Result<Vec<(AssetId, Result<(F, Option<u64>), &'static str>)>, Option<&'static str>>
     where
         F: FixedPointNumber,
     {
-        if price_source_type.as_ref() == P::PRICE_SOURCE_TYPE {
-            let price_source = P::new(assets_data.clone()).map_err(Some)?;
-            Ok(price_source.get_prices::<F>())
+        if config.source_type == P::PRICE_SOURCE_TYPE {
+            let price_source = P::new(config, assets_data.clone()).map_err(Some)?;
+            Ok(price_source.get_observed_prices::<F>())
         } else {
             Err(None)
         }
     }
 }
 
-#[impl_trait_for_tuples::impl_for_tuples(5)]
+#[impl_trait_for_tuples::impl_for_tuples(10)]
 impl<AssetId: Clone, AssetData: Clone> PriceSourcePeeker<AssetId, AssetData> for Tuple {
     fn get_prices<F>(
-        price_source_type: impl AsRef<str>,
+        config: &OracleOffchainConfig,
         assets_data: &Vec<(AssetId, AssetData)>,
-    ) -> Result<Vec<(AssetId, Result<F, &'static str>)>, Option<&'static str>>
+    ) -> Result<Vec<(AssetId, Result<(F, Option<u64>), &'static str>)>, Option<&'static str>>
     where
         F: FixedPointNumber,
     {
         for_tuples!( #(
-            match Tuple::get_prices::<F>(price_source_type.as_ref(), assets_data) {
+            match Tuple::get_prices::<F>(config, assets_data) {
                 Err(None) => {},
                 res => return res,
             }
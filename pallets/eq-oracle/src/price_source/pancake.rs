@@ -1,191 +1,263 @@
-use crate::price_source::{PriceSource};
-use crate::offchain_storage;
+use super::{
+    evm::{AbiType, AbiValue, EvmClient},
+    http_client::{request_deadline, HttpTransport, OffchainHttp},
+    json::PriceSourceError,
+    log_price_result, PriceSource,
+};
+use crate::offchain_storage::{HttpOptions, OracleOffchainConfig, PancakePool, PancakeSettings};
 use alloc::string::String;
-// use eq_primitives::asset::Asset;
-// use eq_utils::{fixedi64_from_fixedu128, fixedu128_from_fixedi64};
-use sp_arithmetic::FixedI64;
-use sp_std::{vec, vec::Vec};
+use primitives::{AsSymbol, PriceGetter};
+use sp_arithmetic::FixedPointNumber;
+use sp_core::U512;
+use sp_runtime::offchain::Timestamp;
+use sp_std::{convert::TryFrom, marker::PhantomData, vec::Vec};
+use utils::log;
+
+/// Decimals of LP token prices and of pool amounts in calculations
+const PRICE_DECIMALS: u32 = 18;
+const PRICE_ACCURACY: u128 = 1_000_000_000_000_000_000_u128;
+/// Tokens with more decimals are rejected, so normalized amounts can't overflow
+const MAX_TOKEN_DECIMALS: u32 = 36;
 
 /// Pancake price source.
-/// Gets data from pancake smart contract and calculate contract lp token price.
-/// Price source requires all pool in "oracle::pool_assets" setting.
-#[derive(Debug)]
-pub struct PancakePriceSource<AssetId> {
-    /// Contract address of PancakeSwap.
-    /// Example: 0x58f876857a02d6762e0101bb5c46a8c1ed44dc16  contract address in BSC on pair WBNB/BUSD
-    /// https://bscscan.com/address/0x58f876857a02d6762e0101bb5c46a8c1ed44dc16
-    contract: String,
-    /// token_0 contract address
-    token_0: String,
-    /// token_1 contract token contract address
-    token_1: String,
-    /// Url of BSC node api.
-    /// Example: https://scn1.equilab.io/bsc/mainnet/rpc/
-    node_url: String,
-    /// token_0 price from our oracle
-    token_0_price: FixedI64,
-    /// token_1 price from our oracle
-    token_1_price: FixedI64,
-    /// Target lp token
-    asset: AssetId,
+/// Gets data from PancakeSwap (Uniswap V2) pair contracts and calculates LP token prices.
+/// Price source requires pools in the `pancake` section of the offchain config
+/// and prices of pool tokens stored on chain, they are read with `G`.
+///
+/// LP token is priced by fair reserves: `2 * sqrt(r0 * r1 * p0 * p1) / total_supply`.
+/// Unlike `(r0 * p0 + r1 * p1) / total_supply` it doesn't depend on the ratio of reserves,
+/// so it can't be manipulated by skewing reserves with a flash loan.
+/// Requests are sent with `T`.
+pub struct PancakePriceSource<AssetId, AssetData, G, T = OffchainHttp> {
+    settings: PancakeSettings,
+    http: HttpOptions,
+    assets_data: Vec<(AssetId, AssetData)>,
+    _price_getter: PhantomData<G>,
+    _transport: PhantomData<T>,
 }
 
-impl<AssetId> PancakePriceSource<AssetId> {
-    pub fn new(
-        token_0_price: FixedI64,
-        token_1_price: FixedI64,
-        lp_token: AssetId,
-    ) -> Result<Self, &'static str> {
-        let contract = offchain_storage::get_contract_address()
-            .ok_or("Storage value does not exists")?;
-        let node_url =
-            offchain_storage::get_node_url().ok_or("Storage value does not exists")?;
-        let token_0_address = pancake_contract::token_0(&node_url, &contract)?;
-        let token_1_address = pancake_contract::token_1(&node_url, &contract)?;
+impl<AssetId, AssetData, G, T> PancakePriceSource<AssetId, AssetData, G, T>
+where
+    AssetId: AsSymbol + Clone,
+    G: PriceGetter<AssetId = AssetId>,
+    T: HttpTransport,
+{
+    fn pool(&self, symbol: &str) -> Option<&PancakePool> {
+        self.settings
+            .pools
+            .iter()
+            .find(|(lp_symbol, _)| lp_symbol.trim().eq_ignore_ascii_case(symbol))
+            .map(|(_, pool)| pool)
+    }
 
-        Ok(PancakePriceSource {
-            contract,
-            token_0: token_0_address,
-            token_1: token_1_address,
-            node_url,
-            token_0_price,
-            token_1_price,
-            asset: lp_token,
-        })
+    /// Returns on chain price of a pool token with 18 decimals
+    fn token_price(&self, symbol: &str) -> Result<U512, PriceSourceError> {
+        let asset = self
+            .assets_data
+            .iter()
+            .map(|(asset, _)| asset)
+            .find(|asset| {
+                asset
+                    .get_symbol()
+                    .map_or(false, |s| s.eq_ignore_ascii_case(symbol.trim()))
+            })
+            .ok_or(PriceSourceError::Symbol)?;
+        let price = G::get_price(asset.clone()).map_err(|_| {
+            log::error!("Pool token price is not available. Token: {:?}", symbol);
+            PriceSourceError::NoPoolTokenPrice
+        })?;
+
+        Ok(U512::from(price.saturating_mul_int(PRICE_ACCURACY)))
+    }
+
+    /// Fetches pool state from the pair contract and calculates LP token price.
+    /// All calls are made at the same block, so reserves and supply are consistent
+    fn fetch_price<F: FixedPointNumber>(
+        &self,
+        pool: &PancakePool,
+        deadline: Timestamp,
+    ) -> Result<F, PriceSourceError> {
+        let client = &EvmClient::<T>::pinned(&self.settings.node_url, self.http.clone(), deadline)?;
+        let contract = pool.contract.as_str();
+
+        let (reserve_0, reserve_1) = pancake_contract::get_reserves(client, contract)?;
+        let total_supply = pancake_contract::total_supply(client, contract)?;
+        let lp_decimals = pancake_contract::decimals(client, contract)?;
+        let decimals_0 =
+            pancake_contract::decimals(client, &pancake_contract::token_0(client, contract)?)?;
+        let decimals_1 =
+            pancake_contract::decimals(client, &pancake_contract::token_1(client, contract)?)?;
+
+        let price = fair_lp_price(
+            normalize(reserve_0, decimals_0),
+            normalize(reserve_1, decimals_1),
+            self.token_price(&pool.token_0)?,
+            self.token_price(&pool.token_1)?,
+            normalize(total_supply, lp_decimals),
+        )
+        .ok_or(PriceSourceError::OverflowError)?;
+
+        F::checked_from_rational(price, PRICE_ACCURACY).ok_or(PriceSourceError::OverflowError)
     }
 }
 
-impl<AssetId, AssetData> PriceSource<AssetId, AssetData> for PancakePriceSource<AssetId> {
+impl<AssetId, AssetData, G, T> PriceSource<AssetId, AssetData>
+    for PancakePriceSource<AssetId, AssetData, G, T>
+where
+    AssetId: AsSymbol + Clone,
+    G: PriceGetter<AssetId = AssetId>,
+    T: HttpTransport,
+{
     const PRICE_SOURCE_TYPE: &'static str = "pancake";
 
-    fn new(assets_data: Vec<(AssetId, AssetData)>) -> Result<Self, &'static str> {
-        todo!()
+    fn new(
+        config: &OracleOffchainConfig,
+        assets_data: Vec<(AssetId, AssetData)>,
+    ) -> Result<Self, &'static str> {
+        Ok(PancakePriceSource {
+            settings: config.pancake.clone().ok_or("No pancake settings")?,
+            http: config.http_options(Self::PRICE_SOURCE_TYPE),
+            assets_data,
+            _price_getter: PhantomData,
+            _transport: PhantomData,
+        })
     }
 
-    fn get_prices(&self) -> Vec<(AssetId, Result<FixedI64, PriceSourceError>)> {
-        let get_price = || -> Result<FixedI64, PriceSourceError> {
-            let total_supply = pancake_contract::total_supply(&self.node_url, &self.contract)?;
+    fn get_prices<F>(&self) -> Vec<(AssetId, Result<F, &'static str>)>
+    where
+        F: FixedPointNumber,
+    {
+        let deadline = request_deadline();
+        let mut asset_prices: Vec<(AssetId, Result<F, &'static str>)> =
+            Vec::with_capacity(self.settings.pools.len());
+
+        for (asset, _) in &self.assets_data {
+            // only LP tokens with pools in settings are fed
+            let pool = match asset.get_symbol().and_then(|symbol| self.pool(&symbol)) {
+                Some(pool) => pool,
+                None => continue,
+            };
 
-            let gens_balance =
-                pancake_contract::balance_of(&self.node_url, &self.contract, &self.token_0)?;
-            let busd_balance =
-                pancake_contract::balance_of(&self.node_url, &self.contract, &self.token_1)?;
+            let price = self.fetch_price::<F>(pool, deadline);
+            asset_prices.push((
+                asset.clone(),
+                log_price_result(Self::PRICE_SOURCE_TYPE, asset.get_symbol(), price),
+            ));
+        }
 
-            let price_u128 = (gens_balance * fixedu128_from_fixedi64(self.token_0_price).unwrap()
-                + busd_balance * fixedu128_from_fixedi64(self.token_1_price).unwrap())
-                / total_supply;
+        asset_prices
+    }
+}
 
-            fixedi64_from_fixedu128(price_u128).ok_or(PriceSourceError::OverflowError)
-        };
+/// Converts an amount with `decimals` to an amount with 18 decimals
+fn normalize(amount: U512, decimals: u32) -> U512 {
+    if decimals <= PRICE_DECIMALS {
+        amount * U512::exp10((PRICE_DECIMALS - decimals) as usize)
+    } else {
+        amount / U512::exp10((decimals - PRICE_DECIMALS) as usize)
+    }
+}
 
-        vec![(self.asset, get_price())]
+/// Fair price of an LP token: `2 * sqrt(reserve_0 * reserve_1) * sqrt(price_0 * price_1) / total_supply`.
+/// All arguments and the result have 18 decimals.
+/// Returns `None` if there are no LP tokens or the price doesn't fit `u128`
+pub(crate) fn fair_lp_price(
+    reserve_0: U512,
+    reserve_1: U512,
+    price_0: U512,
+    price_1: U512,
+    total_supply: U512,
+) -> Option<u128> {
+    if total_supply.is_zero() {
+        return None;
     }
+
+    let sqrt_k = reserve_0.checked_mul(reserve_1)?.integer_sqrt();
+    let sqrt_p = price_0.checked_mul(price_1)?.integer_sqrt();
+    let price = sqrt_k
+        .checked_mul(sqrt_p)?
+        .checked_mul(U512::from(2u8))?
+        .checked_div(total_supply)?;
+
+    u128::try_from(price).ok()
 }
 
 /// Provides read methods of pancake swap smart-contract
 mod pancake_contract {
     use super::*;
-    use crate::alloc::string::ToString;
-    use crate::price_source::*;
-    use sp_arithmetic::{FixedPointNumber, FixedU128};
-
-    const ETH_ACCURACY: u128 = 1_000_000_000_000_000_000_u128;
-
-    /// Execute `eth_call` method
-    fn call_contract(url: &str, contract: &str, data: &str) -> Result<String, PriceSourceError> {
-        let body_str = format!("{{\"jsonrpc\":\"2.0\", \"method\":\"eth_call\", \"params\":[{{\"to\": \"{}\",\"data\": \"{}\"}},\"latest\"],\"id\":1}}",
-                               contract,
-                               data);
-
-        let response = http_client::post(url, vec![body_str.as_bytes()])
-            .map_err(|_| PriceSourceError::HttpError)?;
-
-        let json_value = json::from_str::<json::Value>(&response)
-            .map_err(|_| PriceSourceError::JsonParseError)?;
-
-        match json_value
-            .get("result")
-            .ok_or_else(||{
-                log::error!(
-                    "{}:{}. Error response from call_contract. Error:{:?} url: {:?}, contract: {:?}, data {:?}.",
-                    file!(),
-                    line!(),
-                    response,
-                    url,
-                    contract,
-                    data
-                );
-
-                PriceSourceError::CallContractError
-            })?
-        {
-            json::Value::String(result) => Ok(result.to_string()),
-            _ => Err(PriceSourceError::JsonParseError)
-        }
-    }
 
-    /// Remove leading zeros from response
-    pub fn convert_to_address(response: &str) -> String {
-        const ADDRESS_LENGTH: usize = 40;
-        format!(
-            "0x{}",
-            response[response.len() - ADDRESS_LENGTH..].to_string()
-        )
+    fn uint(value: &AbiValue) -> Result<U512, PriceSourceError> {
+        value
+            .as_uint()
+            .map(U512::from)
+            .ok_or(PriceSourceError::InvalidContractValue)
     }
 
-    /// Returns total supply of LP token
-    pub fn total_supply(url: &str, contract: &str) -> Result<FixedU128, PriceSourceError> {
-        // keccak256('totalSupply()') = "0x18160ddd7f15c72528c2f94fd8dfe3c8d5aa26e2c50c7d81f4bc7bee8d4b7932"
-        // get first 4 bytes of keccak hash 18160ddd and make data parameter
-        const TOTAL_SUPPLY_DATA: &'static str =
-            "0x18160ddd0000000000000000000000000000000000000000000000000000000000000000";
-
-        let result = call_contract(url, contract, TOTAL_SUPPLY_DATA)?;
+    fn address(value: &AbiValue) -> Result<String, PriceSourceError> {
+        value
+            .as_address()
+            .ok_or(PriceSourceError::InvalidContractValue)
+    }
 
-        //received balance in Wei, convert it to units
-        u128::from_str_radix(&result.trim_start_matches("0x"), 16)
-            .map(|v| FixedU128::saturating_from_rational(v, ETH_ACCURACY))
-            .map_err(|_| PriceSourceError::JsonParseError)
+    /// Returns total supply of LP token in minimal units
+    pub fn total_supply<T: HttpTransport>(
+        client: &EvmClient<T>,
+        contract: &str,
+    ) -> Result<U512, PriceSourceError> {
+        let values = client.call_function(contract, "totalSupply()", &[], &[AbiType::Uint(256)])?;
+        uint(&values[0])
     }
 
-    /// Returns balance of `token_contract` on `contract`
-    pub fn balance_of(
-        url: &str,
+    /// Returns (reserve0, reserve1) of the pair in minimal units
+    pub fn get_reserves<T: HttpTransport>(
+        client: &EvmClient<T>,
         contract: &str,
+    ) -> Result<(U512, U512), PriceSourceError> {
+        // (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast)
+        let values = client.call_function(
+            contract,
+            "getReserves()",
+            &[],
+            &[AbiType::Uint(112), AbiType::Uint(112), AbiType::Uint(32)],
+        )?;
+        Ok((uint(&values[0])?, uint(&values[1])?))
+    }
+
+    /// Returns decimals of an ERC-20 token
+    pub fn decimals<T: HttpTransport>(
+        client: &EvmClient<T>,
         token_contract: &str,
-    ) -> Result<FixedU128, PriceSourceError> {
-        // keccak256('balance_of(address)') = "0x70a08231b98ef4ca268c9cc3f6b4590e4bfec28280db06bb5d45e689f2a360be"
-        // get first 4 bytes of keccak hash `70a08231` and make data parameter, last bytes of data should be `contract`
-        let mut balance_of_data = String::from(
-            "0x70a082310000000000000000000000000000000000000000000000000000000000000000",
-        );
-        //contract should be in format "0x......."
-        //replace last chars of `balance_of_data` by contract number without leading "0x"
-        balance_of_data.replace_range(balance_of_data.len() - contract.len() + 2.., &contract[2..]);
-
-        let result_str = call_contract(url, token_contract, balance_of_data.as_str())?;
-
-        u128::from_str_radix(&result_str.trim_start_matches("0x"), 16)
-            .map(|v| FixedU128::saturating_from_rational(v, ETH_ACCURACY))
-            .map_err(|_| PriceSourceError::JsonParseError)
+    ) -> Result<u32, PriceSourceError> {
+        let values =
+            client.call_function(token_contract, "decimals()", &[], &[AbiType::Uint(8)])?;
+        let decimals = uint(&values[0])?.low_u32();
+        if decimals > MAX_TOKEN_DECIMALS {
+            log::error!(
+                "Unsupported token decimals. Token: {:?}, decimals: {:?}",
+                token_contract,
+                decimals
+            );
+            return Err(PriceSourceError::InvalidContractValue);
+        }
+
+        Ok(decimals)
     }
 
     /// Returns token0 address
-    pub fn token_0(url: &str, contract: &str) -> Result<String, PriceSourceError> {
-        //keccak256('token0()') = "0x0dfe16819b2523f68151ea44c4f107305bfeb85c4b18e7e63ac6f999d8cf9a0e"
-        //get first 4 bytes of keccak hash 0dfe1681
-        const TOKEN_0_DATA: &'static str =
-            "0x0dfe16810000000000000000000000000000000000000000000000000000000000000000";
-
-        call_contract(url, contract, TOKEN_0_DATA).map(|s| convert_to_address(s.as_str()))
+    pub fn token_0<T: HttpTransport>(
+        client: &EvmClient<T>,
+        contract: &str,
+    ) -> Result<String, PriceSourceError> {
+        let values = client.call_function(contract, "token0()", &[], &[AbiType::Address])?;
+        address(&values[0])
     }
 
     /// Returns token1 address
-    pub fn token_1(url: &str, contract: &str) -> Result<String, PriceSourceError> {
-        //keccak256('token1()') = "0xd21220a7b5fcd6706feb17ecf897df81a823584a161e849e09b1ff66574ed2cb"
-        //get first 4 bytes of keccak hash d21220
-        const TOKEN_1_DATA: &'static str =
-            "0xd21220a70000000000000000000000000000000000000000000000000000000000000000";
-        call_contract(url, contract, TOKEN_1_DATA).map(|s| convert_to_address(s.as_str()))
+    pub fn token_1<T: HttpTransport>(
+        client: &EvmClient<T>,
+        contract: &str,
+    ) -> Result<String, PriceSourceError> {
+        let values = client.call_function(contract, "token1()", &[], &[AbiType::Address])?;
+        address(&values[0])
     }
 }
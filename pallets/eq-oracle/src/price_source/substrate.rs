@@ -0,0 +1,186 @@
+use super::{
+    apply_price_strategy,
+    evm::{from_hex, to_hex},
+    http_client::{request_deadline, HttpClient, HttpTransport, OffchainHttp},
+    json::PriceSourceError,
+    log_price_result, PriceSource,
+};
+use crate::offchain_storage::{
+    HttpOptions, OracleOffchainConfig, SubstrateDecoder, SubstrateFeed, SubstrateMethod,
+    SubstrateSettings,
+};
+use alloc::string::String;
+use codec::Decode;
+use primitives::AsSymbol;
+use serde_json as json;
+use sp_arithmetic::FixedPointNumber;
+use sp_core::U512;
+use sp_runtime::offchain::Timestamp;
+use sp_std::{convert::TryFrom, marker::PhantomData, vec::Vec};
+use utils::log;
+
+const PRICE_ACCURACY: u128 = 1_000_000_000_000_000_000_u128;
+
+/// Substrate price source.
+/// Mirrors prices from other Substrate chains: reads SCALE encoded values with `state_getStorage`
+/// or `state_call` of a remote node from the `substrate` section of the offchain config
+/// and decodes them with a feed decoder, e.g. DEX pool reserves or oracle prices.
+/// Requests are sent with `T`.
+#[derive(Debug)]
+pub struct SubstratePriceSource<AssetId, AssetData, T = OffchainHttp> {
+    settings: SubstrateSettings,
+    http: HttpOptions,
+    config: OracleOffchainConfig,
+    assets_data: Vec<(AssetId, AssetData)>,
+    _transport: PhantomData<T>,
+}
+
+impl<AssetId, AssetData, T: HttpTransport> SubstratePriceSource<AssetId, AssetData, T> {
+    /// Requests a SCALE encoded value from the remote node
+    fn fetch_value(
+        &self,
+        feed: &SubstrateFeed,
+        symbol: &str,
+        deadline: Timestamp,
+    ) -> Result<Vec<u8>, PriceSourceError> {
+        let key = feed_key(&feed.key, symbol);
+        let result = match feed.method {
+            SubstrateMethod::Storage => HttpClient::<T>::json_rpc(
+                &self.settings.node_url,
+                "state_getStorage",
+                &alloc::format!("[\"{}\"]", key),
+                &self.http,
+                deadline,
+            )?,
+            SubstrateMethod::Call => HttpClient::<T>::json_rpc(
+                &self.settings.node_url,
+                "state_call",
+                &alloc::format!("[\"{}\",\"{}\"]", feed.function, key),
+                &self.http,
+                deadline,
+            )?,
+        };
+
+        match result {
+            json::Value::String(value) => from_hex(&value),
+            json::Value::Null => {
+                log::error!("Remote node has no value. Key: {:?}", key);
+                Err(PriceSourceError::NoRemoteValue)
+            }
+            _ => Err(PriceSourceError::JsonParseError),
+        }
+    }
+}
+
+/// Replaces `{$}` in a hex key template with a hex encoded asset symbol
+pub(crate) fn feed_key(template: &str, symbol: &str) -> String {
+    let symbol = to_hex(symbol.as_bytes());
+    let key = template
+        .trim()
+        .replace("{$}", symbol.trim_start_matches("0x"));
+    if key.starts_with("0x") {
+        key
+    } else {
+        alloc::format!("0x{}", key)
+    }
+}
+
+/// Decodes a price from a SCALE encoded value
+pub(crate) fn decode_price<F: FixedPointNumber>(
+    feed: &SubstrateFeed,
+    value: &[u8],
+) -> Result<F, PriceSourceError> {
+    let mut input = value
+        .get(feed.offset as usize..)
+        .ok_or(PriceSourceError::ScaleDecodeError)?;
+    let decode_error = |_| {
+        log::error!("Can't decode {:?} value: {:?}", feed.decoder, to_hex(value));
+        PriceSourceError::ScaleDecodeError
+    };
+    let exp10 = |decimals: u32| 10u128.pow(decimals);
+
+    let price = match feed.decoder {
+        SubstrateDecoder::U128 => {
+            let amount = u128::decode(&mut input).map_err(decode_error)?;
+            F::checked_from_rational(amount, exp10(feed.decimals))
+        }
+        SubstrateDecoder::U64 => {
+            let amount = u64::decode(&mut input).map_err(decode_error)?;
+            F::checked_from_rational(amount, exp10(feed.decimals))
+        }
+        SubstrateDecoder::Reserves => {
+            let (base, quote) = <(u128, u128)>::decode(&mut input).map_err(decode_error)?;
+            if base == 0 || quote == 0 {
+                return Err(PriceSourceError::InvalidContractValue);
+            }
+            // (quote / 10^quote_decimals) / (base / 10^decimals)
+            let price =
+                U512::from(quote) * U512::from(exp10(feed.decimals)) * U512::from(PRICE_ACCURACY)
+                    / (U512::from(base) * U512::from(exp10(feed.quote_decimals)));
+            let price = u128::try_from(price).map_err(|_| PriceSourceError::OverflowError)?;
+            F::checked_from_rational(price, PRICE_ACCURACY)
+        }
+    };
+
+    price.ok_or(PriceSourceError::OverflowError)
+}
+
+impl<AssetId: AsSymbol + Clone, AssetData, T: HttpTransport> PriceSource<AssetId, AssetData>
+    for SubstratePriceSource<AssetId, AssetData, T>
+{
+    const PRICE_SOURCE_TYPE: &'static str = "substrate";
+
+    fn new(
+        config: &OracleOffchainConfig,
+        assets_data: Vec<(AssetId, AssetData)>,
+    ) -> Result<Self, &'static str> {
+        Ok(SubstratePriceSource {
+            settings: config.substrate.clone().ok_or("No substrate settings")?,
+            http: config.http_options(Self::PRICE_SOURCE_TYPE),
+            config: config.clone(),
+            assets_data,
+            _transport: PhantomData,
+        })
+    }
+
+    fn get_prices<F>(&self) -> Vec<(AssetId, Result<F, &'static str>)>
+    where
+        F: FixedPointNumber,
+    {
+        let deadline = request_deadline();
+        let mut asset_prices: Vec<(AssetId, Result<F, &'static str>)> =
+            Vec::with_capacity(self.settings.feeds.len());
+
+        for (asset, _) in &self.assets_data {
+            // only assets with feeds in settings are fed
+            let symbol = match asset.get_symbol() {
+                Some(symbol) => symbol,
+                None => continue,
+            };
+            let feed = match self
+                .settings
+                .feeds
+                .iter()
+                .find(|(feed_symbol, _)| feed_symbol.trim().eq_ignore_ascii_case(&symbol))
+            {
+                Some((_, feed)) => feed,
+                None => continue,
+            };
+            let price_strategy = match self.config.price_strategy(&symbol) {
+                Some(price_strategy) => price_strategy,
+                None => continue,
+            };
+
+            let price = self
+                .fetch_value(feed, &symbol, deadline)
+                .and_then(|value| decode_price::<F>(feed, &value))
+                .and_then(|price| apply_price_strategy(price, price_strategy));
+            asset_prices.push((
+                asset.clone(),
+                log_price_result(Self::PRICE_SOURCE_TYPE, symbol, price),
+            ));
+        }
+
+        asset_prices
+    }
+}
@@ -0,0 +1,49 @@
+//! Runtime API for inspecting oracle state.
+//!
+//! Runtime implements it with the reference functions of the pallet:
+//! ```ignore
+//! impl eq_oracle::runtime_api::EqOracleApi<Block, AccountId, BlockNumber, Asset, FixedI64>
+//!     for Runtime
+//! {
+//!     fn get_price(asset: Asset) -> Result<FixedI64, DispatchError> {
+//!         <Oracle as PriceGetter>::get_price(asset)
+//!     }
+//!     fn get_price_data(asset: Asset) -> Option<PriceDataView<AccountId, BlockNumber, FixedI64>> {
+//!         Oracle::price_data_view(&asset)
+//!     }
+//!     fn feeder_status(who: AccountId) -> Vec<(Asset, FeederSubmission<BlockNumber, FixedI64>)> {
+//!         Oracle::feeder_status(&who)
+//!     }
+//!     fn list_assets() -> Vec<Asset> {
+//!         <EqAssets as AssetGetter>::get_assets()
+//!     }
+//! }
+//! ```
+
+use crate::{FeederSubmission, PriceDataView};
+use codec::Codec;
+use sp_runtime::DispatchError;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+    /// Access to oracle prices without relying on storage layout
+    pub trait EqOracleApi<AccountId, BlockNumber, AssetId, Price>
+    where
+        AccountId: Codec,
+        BlockNumber: Codec,
+        AssetId: Codec,
+        Price: Codec,
+    {
+        /// Returns current reference (median) price or a reason why it can't be used
+        fn get_price(asset: AssetId) -> Result<Price, DispatchError>;
+
+        /// Returns aggregated price data of an asset with all its price points
+        fn get_price_data(asset: AssetId) -> Option<PriceDataView<AccountId, BlockNumber, Price>>;
+
+        /// Returns the last submission of a feeder for every asset
+        fn feeder_status(who: AccountId) -> Vec<(AssetId, FeederSubmission<BlockNumber, Price>)>;
+
+        /// Returns all assets known to the oracle
+        fn list_assets() -> Vec<AssetId>;
+    }
+}
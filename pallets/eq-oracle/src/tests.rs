@@ -5,9 +5,33 @@ use sp_arithmetic::FixedI64;
 
 use crate::{
     mock::*,
-    price_source::json::{PriceSourceError, WithUrl},
+    offchain_storage::{
+        storage_keys, ChainlinkSettings, ConfigError, EvmFeed, EvmSettings, GraphqlSettings,
+        HttpOptions, OracleOffchainConfig, PancakePool, PancakeSettings, PriceBounds,
+        PriceStrategy, SubstrateDecoder, SubstrateFeed, SubstrateMethod, SubstrateSettings,
+        CONFIG_VERSION, DEFAULT_HTTP_BACKOFF_MS, DEFAULT_HTTP_TIMEOUT_MS, MAX_HTTP_REQUEST_TIME_MS,
+        MAX_HTTP_RETRIES,
+    },
+    price_source::{
+        chainlink::RoundData,
+        evm::{self, AbiType, AbiValue},
+        graphql::GraphqlPriceSource,
+        http_client,
+        json::{parse_timestamp, PriceSourceError, WithUrl},
+        pancake::fair_lp_price,
+        substrate::{decode_price, feed_key},
+        PriceSource,
+    },
 };
+use frame_support::unsigned::ValidateUnsigned;
 use primitives::{Asset, PriceGetter};
+use sp_core::offchain::{testing::PendingRequest, Timestamp as OffchainTimestamp};
+use sp_runtime::{
+    offchain::StorageKind,
+    transaction_validity::{InvalidTransaction, TransactionSource},
+    FixedU128,
+};
+use utils::fixed::{parse_decimal, DecimalError};
 
 use super::*;
 
@@ -20,7 +44,7 @@ fn set_price(
     block_number: u64,
 ) -> DispatchResultWithPostInfo {
     let dummy_signature = sp_core::sr25519::Signature([0u8; 64]);
-    let payload = PricePayload {
+    let payload = LegacyPricePayload {
         public: account,
         asset,
         price: FixedI64::from_inner((price * (FixedI64::accuracy() as f64)) as i64),
@@ -33,6 +57,28 @@ fn set_price(
     )
 }
 
+fn set_observed_price(
+    account: Sign,
+    asset: Asset,
+    price: f64,
+    block_number: u64,
+    timestamp: u64,
+) -> DispatchResultWithPostInfo {
+    let dummy_signature = sp_core::sr25519::Signature([0u8; 64]);
+    let payload = PricePayload {
+        public: account,
+        asset,
+        price: FixedI64::from_inner((price * (FixedI64::accuracy() as f64)) as i64),
+        block_number,
+        timestamp,
+    };
+    Oracle::set_price_unsigned_v2(
+        frame_system::RawOrigin::None.into(),
+        payload,
+        dummy_signature,
+    )
+}
+
 fn set_price_ok(account: Sign, asset: Asset, price: f64, block_number: u64) {
     assert_ok!(set_price(account, asset, price, block_number));
 }
@@ -265,26 +311,26 @@ fn not_set_price_twice() {
 fn check_json_reader() {
     new_test_ext().execute_with(|| {
         assert_err!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>("".to_string(), "USD"),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>("", "USD"),
             PriceSourceError::DeserializationError
         );
         assert_err!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "rtdfgfdgfdgf".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "rtdfgfdgfdgf",
                 "USD"
             ),
             PriceSourceError::DeserializationError
         );
         assert_err!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{USD:2.98}".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{USD:2.98}",
                 "USD"
             ),
             PriceSourceError::DeserializationError
         );
         assert_err!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{\"USD\":'2.98'}".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"USD\":'2.98'}",
                 "USD"
             ),
             PriceSourceError::DeserializationError
@@ -292,74 +338,81 @@ fn check_json_reader() {
 
         let val = FixedI64::from_inner((2.98 * (FixedI64::accuracy() as f64)) as i64);
         assert_eq!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{\"USD\":2.98}".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"USD\":2.98}",
                 "USD"
             ),
-            Ok(val)
+            Ok((val, None))
         );
         assert_eq!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{\"USD\":\"2.98\"}".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"USD\":\"2.98\"}",
                 "USD"
             ),
-            Ok(val)
+            Ok((val, None))
         );
 
         assert_err!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{\"price\":\"2.98\"}".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"price\":\"2.98\"}",
                 "USD"
             ),
             PriceSourceError::JsonParseError
         );
 
         assert_err!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{\"price\":\"2.98\"}".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"price\":\"2.98\"}",
                 "USD"
             ),
             PriceSourceError::JsonParseError
         );
 
         assert_eq!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{\"price\": {\"last\": \"2.98\"}}".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"price\": {\"last\": \"2.98\"}}",
                 "price.last"
             ),
-            Ok(val)
+            Ok((val, None))
         );
 
         assert_eq!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{\"price\": [\"3.46\", \"2.98\"]}".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"price\": [\"3.46\", \"2.98\"]}",
                 "price[1]"
             ),
-            Ok(val)
+            Ok((val, None))
+        );
+        assert_err!(
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"price\": [\"3.46\", \"2.98\"]}",
+                "price[99999999999999999999999]"
+            ),
+            PriceSourceError::JsonParseError
         );
 
         assert_eq!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{\"price\": {\"last\": [\"2.98\"]}}".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"price\": {\"last\": [\"2.98\"]}}",
                 "price.last[0]"
             ),
-            Ok(val)
+            Ok((val, None))
         );
 
         assert_eq!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "[\"2.98\"]".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "[\"2.98\"]",
                 "[0]"
             ),
-            Ok(val)
+            Ok((val, None))
         );
 
         assert_eq!(
-            JsonPriceSource::<Asset, ()>::fetch_price_from_json::<FixedI64>(
-                "{\"data\": [ {\"data\": [ { \"price\": \"2.98\" } ] } ] }".to_string(),
+            JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+                "{\"data\": [ {\"data\": [ { \"price\": \"2.98\" } ] } ] }",
                 "data[0].data[0].price"
             ),
-            Ok(val)
+            Ok((val, None))
         );
     });
 }
@@ -431,13 +484,7 @@ fn should_build_on_genesis_price_points() {
             block_number: frame_system::Pallet::<Test>::block_number(),
             timestamp: 0,
             price: FixedI64::saturating_from_integer(0i32),
-            price_points: Vec::<
-                PricePoint<
-                    <mock::Test as frame_system::Config>::AccountId,
-                    <mock::Test as frame_system::Config>::BlockNumber,
-                    <mock::Test as crate::Config>::Price,
-                >,
-            >::new(),
+            price_points: BoundedVec::default(),
         };
 
         assert_eq!(<PricePoints<Test>>::contains_key(asset::EQ), true);
@@ -523,6 +570,40 @@ fn filter_prices_from_test() {
     });
 }
 
+#[test]
+fn filter_prices_from_keeps_latest_remaining_observation() {
+    new_observation_test_ext().execute_with(|| {
+        let account_id_1 = Sign { 0: [1; 32] };
+        let account_id_2 = Sign { 0: [2; 32] };
+        let now = 10_000;
+        Timestamp::set_timestamp(now * 1000);
+        System::set_block_number(1);
+        Whitelist::add_to_whitelist(&account_id_1);
+        Whitelist::add_to_whitelist(&account_id_2);
+
+        assert_ok!(set_observed_price(
+            account_id_1,
+            asset::EQ,
+            100.,
+            1,
+            now - 30
+        ));
+        assert_ok!(set_observed_price(
+            account_id_2,
+            asset::EQ,
+            110.,
+            1,
+            now - 10
+        ));
+        assert_eq!(Oracle::price_points(asset::EQ).unwrap().timestamp, now - 10);
+
+        Oracle::filter_prices_from(&account_id_2);
+        let price_data = Oracle::price_points(asset::EQ).unwrap();
+        assert_eq!(price_data.price, FixedI64::saturating_from_integer(100));
+        assert_eq!(price_data.timestamp, now - 30);
+    });
+}
+
 #[test]
 fn url_symbol_case() {
     let huobi_url_template = "https://api.huobi.pro/market/history/trade?symbol={$}usdt&size=1";
@@ -545,3 +626,3102 @@ fn url_symbol_case() {
         "https://api.kraken.com/0/public/Ticker?pair=XXBTZUSD"
     );
 }
+
+fn set_offchain_value(key: &[u8], value: &str) {
+    sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, key, value.as_bytes());
+}
+
+#[test]
+fn offchain_config_migrated_from_legacy_settings() {
+    new_offchain_test_ext().execute_with(|| {
+        let query = "json(https://api.binance.com/api/v3/ticker/price?symbol={$}USDT).price";
+        set_offchain_value(storage_keys::RESOURCE_TYPE, "custom");
+        set_offchain_value(storage_keys::PRICE_PERIODICITY, "3");
+        set_offchain_value(storage_keys::CUSTOM_QUERY, query);
+        set_offchain_value(storage_keys::SOURCE_ASSETS, "BTC, eth:price, DAI:reverse");
+
+        let expected = OracleOffchainConfig {
+            version: CONFIG_VERSION,
+            source_type: "custom".into(),
+            price_periodicity: 3,
+            max_price_age: 0,
+            custom_query: Some(query.into()),
+            source_assets: vec![
+                ("btc".into(), PriceStrategy::Price),
+                ("eth".into(), PriceStrategy::Price),
+                ("dai".into(), PriceStrategy::Reverse),
+            ]
+            .into_iter()
+            .collect(),
+            price_bounds: Default::default(),
+            pancake: None,
+            evm: None,
+            chainlink: None,
+            substrate: None,
+            graphql: None,
+            http: Default::default(),
+        };
+
+        assert_eq!(offchain_storage::get_config(), Ok(expected.clone()));
+        for key in [
+            storage_keys::RESOURCE_TYPE,
+            storage_keys::PRICE_PERIODICITY,
+            storage_keys::CUSTOM_QUERY,
+            storage_keys::SOURCE_ASSETS,
+        ] {
+            assert_eq!(
+                sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, key),
+                None
+            );
+        }
+
+        // migrated document is used afterwards
+        assert_eq!(offchain_storage::get_config(), Ok(expected.clone()));
+        assert_eq!(expected.price_strategy("btc"), Some(PriceStrategy::Price));
+        assert_eq!(expected.price_strategy("dai"), Some(PriceStrategy::Reverse));
+        assert_eq!(expected.price_strategy("usdt"), None);
+    });
+}
+
+#[test]
+fn offchain_config_errors() {
+    new_offchain_test_ext().execute_with(|| {
+        assert_eq!(offchain_storage::get_config(), Err(ConfigError::NotFound));
+
+        set_offchain_value(storage_keys::PRICE_PERIODICITY, "every block");
+        assert_eq!(
+            offchain_storage::get_config(),
+            Err(ConfigError::InvalidLegacyValue("oracle::price_periodicity"))
+        );
+
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":11,"source_type":"custom","price_periodicity":1}"#,
+        );
+        assert_eq!(
+            offchain_storage::get_config(),
+            Err(ConfigError::UnsupportedVersion(11))
+        );
+
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":1,"source_type":"custom","price_periodicity":0}"#,
+        );
+        assert_eq!(
+            offchain_storage::get_config(),
+            Err(ConfigError::ZeroPricePeriodicity)
+        );
+
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":1,"source_type":"custom","price_periodicity":1}"#,
+        );
+        assert_eq!(
+            offchain_storage::get_config(),
+            Err(ConfigError::MissingCustomQuery)
+        );
+
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":1,"source_type":"custom","price_periodicity":1,"custom_query":"q","source_assets":{"btc":"inverse"}}"#,
+        );
+        assert!(matches!(
+            offchain_storage::get_config(),
+            Err(ConfigError::Malformed(_))
+        ));
+
+        let config = OracleOffchainConfig {
+            version: CONFIG_VERSION,
+            source_type: "custom".into(),
+            price_periodicity: 1,
+            custom_query: Some("q".into()),
+            ..Default::default()
+        };
+        assert_ok!(offchain_storage::set_config(&config));
+        assert_eq!(offchain_storage::get_config(), Ok(config));
+    });
+}
+
+#[test]
+fn offchain_config_migrated_from_older_versions() {
+    new_offchain_test_ext().execute_with(|| {
+        // graphql headers are moved to http options in version 7
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":6,"source_type":"graphql","price_periodicity":1,"graphql":{"url":"https://api.thegraph.com/subgraphs/name/uniswap/uniswap-v3","query":"{ bundle(id: 1) { ethPriceUSD } }","price_path":"data.bundle.ethPriceUSD","headers":{"authorization":"Bearer secret"}}}"#,
+        );
+        let config = offchain_storage::get_config().unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(
+            config.http_options("graphql").headers,
+            vec![("authorization".into(), "Bearer secret".into())]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            config.graphql.unwrap().price_path,
+            "data.bundle.ethPriceUSD"
+        );
+
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":1,"source_type":"custom","price_periodicity":1,"custom_query":"q"}"#,
+        );
+        assert_eq!(
+            offchain_storage::get_config(),
+            Ok(OracleOffchainConfig {
+                version: CONFIG_VERSION,
+                source_type: "custom".into(),
+                price_periodicity: 1,
+                custom_query: Some("q".into()),
+                ..Default::default()
+            })
+        );
+
+        // fields added after the document version
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":1,"source_type":"custom","price_periodicity":1,"custom_query":"q","max_price_age":60}"#,
+        );
+        assert_eq!(
+            offchain_storage::get_config(),
+            Err(ConfigError::Malformed(
+                "unknown field `max_price_age` in version 1".into()
+            ))
+        );
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":7,"source_type":"custom","price_periodicity":1,"custom_query":"q","http":{"custom":{"retries":2}}}"#,
+        );
+        assert_eq!(
+            offchain_storage::get_config(),
+            Err(ConfigError::Malformed(
+                "unknown field `retries` in version 7".into()
+            ))
+        );
+
+        // typed config always has the latest layout
+        let config = OracleOffchainConfig {
+            version: 1,
+            source_type: "custom".into(),
+            price_periodicity: 1,
+            custom_query: Some("q".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            offchain_storage::set_config(&config),
+            Err(ConfigError::UnsupportedVersion(1))
+        );
+    });
+}
+
+#[test]
+fn feeder_status_reports_last_submissions() {
+    new_test_ext().execute_with(|| {
+        let account_id_1 = Sign { 0: [1; 32] };
+        let account_id_2 = Sign { 0: [2; 32] };
+        Whitelist::add_to_whitelist(&account_id_1);
+        Whitelist::add_to_whitelist(&account_id_2);
+
+        System::set_block_number(1);
+        set_price_ok(account_id_1, asset::EQ, 10_000., 1);
+        set_price_ok(account_id_1, asset::BTC, 20_000., 1);
+        set_price_ok(account_id_2, asset::BTC, 30_000., 1);
+
+        assert_eq!(
+            Oracle::feeder_status(&account_id_1),
+            vec![
+                (
+                    asset::EQ,
+                    FeederSubmission {
+                        block_number: 1,
+                        timestamp: 0,
+                        price: FixedI64::saturating_from_integer(10_000),
+                        is_stale: false,
+                    }
+                ),
+                (
+                    asset::BTC,
+                    FeederSubmission {
+                        block_number: 1,
+                        timestamp: 0,
+                        price: FixedI64::saturating_from_integer(20_000),
+                        is_stale: false,
+                    }
+                ),
+            ]
+        );
+
+        Timestamp::set_timestamp(2000);
+
+        let status = Oracle::feeder_status(&account_id_2);
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].0, asset::BTC);
+        assert!(status[0].1.is_stale);
+    });
+}
+
+#[test]
+fn price_points_of_feeder() {
+    new_test_ext().execute_with(|| {
+        let account_id_1 = Sign { 0: [1; 32] };
+        let account_id_2 = Sign { 0: [2; 32] };
+        let account_id_3 = Sign { 0: [3; 32] };
+        Whitelist::add_to_whitelist(&account_id_1);
+        Whitelist::add_to_whitelist(&account_id_2);
+
+        System::set_block_number(3);
+        Timestamp::set_timestamp(12_000);
+        set_price_ok(account_id_1, asset::EQ, 100., 3);
+        set_price_ok(account_id_2, asset::EQ, 110., 3);
+
+        assert_eq!(
+            Oracle::price_points_of(&asset::EQ, &account_id_1),
+            Some(PricePoint {
+                block_number: 3,
+                timestamp: 12,
+                price: FixedI64::saturating_from_integer(100),
+                account_id: account_id_1,
+            })
+        );
+        assert_eq!(
+            Oracle::price_points_of(&asset::EQ, &account_id_2).map(|pp| pp.price),
+            Some(FixedI64::saturating_from_integer(110))
+        );
+        assert_eq!(Oracle::price_points_of(&asset::EQ, &account_id_3), None);
+        assert_eq!(Oracle::price_points_of(&asset::BTC, &account_id_1), None);
+
+        let price_data = Oracle::price_points(asset::EQ).unwrap();
+        let view = Oracle::price_data_view(&asset::EQ).unwrap();
+        assert_eq!(view.block_number, 3);
+        assert_eq!(view.timestamp, 12);
+        assert_eq!(view.price, price_data.price);
+        assert_eq!(
+            view.price_points
+                .into_iter()
+                .map(|pp| (pp.account_id, pp.price))
+                .collect::<Vec<_>>(),
+            vec![
+                (account_id_1, FixedI64::saturating_from_integer(100)),
+                (account_id_2, FixedI64::saturating_from_integer(110)),
+            ]
+        );
+        assert_eq!(Oracle::price_data_view(&asset::BTC), None);
+    });
+}
+
+#[test]
+fn set_price_more_than_max_feeders_evicts_oldest() {
+    new_observation_test_ext().execute_with(|| {
+        let now = 10_000;
+        Timestamp::set_timestamp(now * 1000);
+        System::set_block_number(1);
+        let max_feeders = <Test as Config>::MaxFeeders::get() as u8;
+        // observed ahead of the block time within the tolerance, none of them expires
+        for i in 1..=max_feeders {
+            let account_id = Sign { 0: [i; 32] };
+            Whitelist::add_to_whitelist(&account_id);
+            assert_ok!(set_observed_price(
+                account_id,
+                asset::EQ,
+                100. + i as f64,
+                1,
+                now + i as u64
+            ));
+        }
+
+        let account_id = Sign {
+            0: [max_feeders + 1; 32],
+        };
+        Whitelist::add_to_whitelist(&account_id);
+        assert_ok!(set_observed_price(
+            account_id,
+            asset::EQ,
+            100.,
+            1,
+            now + max_feeders as u64 + 1
+        ));
+        assert_eq!(
+            Oracle::price_points(asset::EQ).unwrap().price_points.len(),
+            max_feeders as usize
+        );
+        assert_eq!(
+            Oracle::price_points_of(&asset::EQ, &Sign { 0: [1; 32] }),
+            None
+        );
+        assert!(Oracle::price_points_of(&asset::EQ, &account_id).is_some());
+        check_price(asset::EQ, 104.5);
+
+        // observed before all stored points, it doesn't evict a fresher one
+        let account_id = Sign {
+            0: [max_feeders + 2; 32],
+        };
+        Whitelist::add_to_whitelist(&account_id);
+        assert_err!(
+            set_observed_price(account_id, asset::EQ, 100., 1, now),
+            Error::<Test>::PriceTimeout
+        );
+        assert_eq!(
+            Oracle::price_points_of(&asset::EQ, &Sign { 0: [2; 32] }).map(|pp| pp.timestamp),
+            Some(now + 2)
+        );
+    });
+}
+
+#[test]
+fn migration_to_v1_keeps_most_recent_price_points() {
+    use frame_support::traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion};
+
+    new_test_ext().execute_with(|| {
+        StorageVersion::new(0).put::<Oracle>();
+
+        // 10 price points sorted by price, older points have higher prices
+        let price_points: Vec<_> = (1..=10u64)
+            .rev()
+            .map(|i| PricePoint {
+                block_number: i,
+                timestamp: i,
+                price: FixedI64::saturating_from_integer((11 - i) * 100),
+                account_id: Sign { 0: [i as u8; 32] },
+            })
+            .collect();
+        let old_price_data = (
+            10u64,
+            20u64,
+            FixedI64::saturating_from_integer(550),
+            price_points,
+        );
+        frame_support::storage::unhashed::put(
+            &<PricePoints<Test>>::hashed_key_for(asset::BTC),
+            &old_price_data,
+        );
+        let eq_price_data = Oracle::price_points(asset::EQ);
+        // undecodable entries are left as is
+        let eth_key = <PricePoints<Test>>::hashed_key_for(asset::ETH);
+        frame_support::storage::unhashed::put_raw(&eth_key, &[1, 2, 3]);
+
+        migrations::v1::MigrateToV1::<Test>::on_runtime_upgrade();
+
+        assert_eq!(Oracle::on_chain_storage_version(), StorageVersion::new(1));
+        assert_eq!(Oracle::price_points(asset::EQ), eq_price_data);
+        assert_eq!(
+            frame_support::storage::unhashed::get_raw(&eth_key),
+            Some(vec![1, 2, 3])
+        );
+
+        // timestamp of the latest kept price point
+        let price_data = Oracle::price_points(asset::BTC).unwrap();
+        assert_eq!(price_data.block_number, 10);
+        assert_eq!(price_data.timestamp, 10);
+        assert_eq!(
+            price_data
+                .price_points
+                .iter()
+                .map(|pp| pp.timestamp)
+                .collect::<Vec<_>>(),
+            vec![10, 9, 8, 7, 6, 5, 4, 3]
+        );
+        assert_eq!(price_data.price, FixedI64::saturating_from_integer(450));
+
+        // repeated upgrade is a no-op
+        migrations::v1::MigrateToV1::<Test>::on_runtime_upgrade();
+        assert_eq!(Oracle::price_points(asset::BTC), Some(price_data));
+    });
+}
+
+/// Runs hooks of the block, prices set before are handled as fed in this block
+fn run_block_hooks(block_number: u64) {
+    use frame_support::traits::Hooks;
+
+    Oracle::on_initialize(block_number);
+    Oracle::on_finalize(block_number);
+}
+
+fn new_price_events_count() -> usize {
+    System::events()
+        .into_iter()
+        .filter(|record| {
+            matches!(
+                record.event,
+                mock::Event::Oracle(crate::Event::NewPrice(..))
+            )
+        })
+        .count()
+}
+
+#[test]
+fn special_prices_written_on_change_or_refresh() {
+    new_test_ext().execute_with(|| {
+        let refresh_interval = <Test as Config>::PriceRefreshInterval::get();
+        let mut time = 10_000;
+        System::set_block_number(1);
+        Timestamp::set_timestamp(time * 1000);
+
+        run_block_hooks(1);
+        check_price(asset::EQD, 1.);
+        assert_eq!(Oracle::price_points(asset::EQD).unwrap().timestamp, time);
+        assert_eq!(new_price_events_count(), 1);
+
+        // price is not changed, nothing is written until refresh interval passes
+        System::reset_events();
+        time_move(&mut time, refresh_interval - 1);
+        run_block_hooks(2);
+        assert_eq!(Oracle::price_points(asset::EQD).unwrap().timestamp, 10_000);
+        assert_eq!(new_price_events_count(), 0);
+
+        time_move(&mut time, 1);
+        run_block_hooks(3);
+        assert_eq!(Oracle::price_points(asset::EQD).unwrap().timestamp, time);
+        assert_eq!(new_price_events_count(), 1);
+    });
+}
+
+#[test]
+fn correlated_prices_updated_with_base_median() {
+    new_test_ext().execute_with(|| {
+        AssetGetterMock::add_asset(asset::XDOT);
+        let account_id = Sign { 0: [1; 32] };
+        Whitelist::add_to_whitelist(&account_id);
+        let mut time = 10_000;
+        System::set_block_number(1);
+        Timestamp::set_timestamp(time * 1000);
+
+        set_price_ok(account_id, asset::DOT, 10., 1);
+        run_block_hooks(2);
+        check_price(asset::XDOT, 10.);
+        assert!(!<UpdatedAssets<Test>>::contains_key(asset::DOT));
+
+        // base median is not changed
+        System::reset_events();
+        time_move(&mut time, 6);
+        run_block_hooks(3);
+        assert_eq!(Oracle::price_points(asset::XDOT).unwrap().timestamp, 10_000);
+        assert_eq!(new_price_events_count(), 0);
+
+        set_price_ok(account_id, asset::DOT, 12., 3);
+        assert!(<UpdatedAssets<Test>>::contains_key(asset::DOT));
+        run_block_hooks(4);
+        check_price(asset::XDOT, 12.);
+        assert_eq!(Oracle::price_points(asset::XDOT).unwrap().timestamp, time);
+    });
+}
+
+#[test]
+fn correlated_prices_updated_in_the_same_block() {
+    use frame_support::traits::Hooks;
+
+    new_test_ext().execute_with(|| {
+        AssetGetterMock::add_asset(asset::XDOT);
+        let account_id = Sign { 0: [1; 32] };
+        Whitelist::add_to_whitelist(&account_id);
+        System::set_block_number(1);
+        Timestamp::set_timestamp(10_000_000);
+
+        set_price_ok(account_id, asset::DOT, 10., 1);
+        Oracle::on_finalize(1);
+        check_price(asset::XDOT, 10.);
+        assert!(!<UpdatedAssets<Test>>::contains_key(asset::DOT));
+    });
+}
+
+#[test]
+fn derived_prices_keep_base_observation_time() {
+    new_observation_test_ext().execute_with(|| {
+        AssetGetterMock::add_asset(asset::XDOT);
+        AssetGetterMock::add_asset(asset::LP_POOL);
+        LpPriceProviderMock::set_pool_state(
+            asset::LP_POOL,
+            LpPoolState {
+                assets: vec![asset::EQD, asset::DOT],
+                reserves: vec![1_000_000, 100_000],
+                invariant: 2_100_000,
+                lp_supply: 2_000_000,
+            },
+        );
+        let refresh_interval = <Test as Config>::PriceRefreshInterval::get();
+        let account_id = Sign { 0: [1; 32] };
+        Whitelist::add_to_whitelist(&account_id);
+        let mut time = 10_000;
+        System::set_block_number(1);
+        Timestamp::set_timestamp(time * 1000);
+
+        assert_ok!(set_observed_price(
+            account_id,
+            asset::DOT,
+            10.,
+            1,
+            time - 30
+        ));
+        run_block_hooks(1);
+        assert_eq!(Oracle::price_points(asset::EQD).unwrap().timestamp, time);
+        assert_eq!(
+            Oracle::price_points(asset::XDOT).unwrap().timestamp,
+            time - 30
+        );
+        assert_eq!(
+            Oracle::price_points(asset::LP_POOL).unwrap().timestamp,
+            time - 30
+        );
+
+        // base is not observed again, nothing to refresh
+        time_move(&mut time, refresh_interval);
+        run_block_hooks(2);
+        assert_eq!(
+            Oracle::price_points(asset::XDOT).unwrap().timestamp,
+            10_000 - 30
+        );
+
+        // the same price observed later refreshes derived prices
+        let block_number = System::block_number();
+        assert_ok!(set_observed_price(
+            account_id,
+            asset::DOT,
+            10.,
+            block_number,
+            time - 10
+        ));
+        run_block_hooks(3);
+        check_price(asset::XDOT, 10.);
+        assert_eq!(
+            Oracle::price_points(asset::XDOT).unwrap().timestamp,
+            time - 10
+        );
+        assert_eq!(
+            Oracle::price_points(asset::LP_POOL).unwrap().timestamp,
+            time - 10
+        );
+    });
+}
+
+#[test]
+fn correlated_prices_multi_hop_and_composite() {
+    new_test_ext().execute_with(|| {
+        // HXDOT goes before XDOT in assets list, but depends on it
+        AssetGetterMock::add_asset(asset::HXDOT);
+        AssetGetterMock::add_asset(asset::XDOT);
+        AssetGetterMock::add_asset(asset::CMP);
+        let account_id = Sign { 0: [1; 32] };
+        Whitelist::add_to_whitelist(&account_id);
+        System::set_block_number(1);
+        Timestamp::set_timestamp(10_000_000);
+        assert_ok!(Oracle::validate_correlations());
+
+        set_price_ok(account_id, asset::DOT, 10., 1);
+        set_price_ok(account_id, asset::ETH, 3_000., 1);
+        set_price_ok(account_id, asset::BTC, 40_000., 1);
+
+        assert_err!(
+            set_price(account_id, asset::CMP, 1., 1),
+            Error::<Test>::WrongCurrency
+        );
+
+        run_block_hooks(2);
+        check_price(asset::XDOT, 10.);
+        check_price(asset::HXDOT, 5.);
+        check_price(asset::CMP, 1.5);
+    });
+}
+
+#[test]
+fn correlation_cycles_rejected() {
+    new_test_ext().execute_with(|| {
+        AssetGetterMock::add_asset(asset::HXDOT);
+        AssetGetterMock::add_asset(asset::XDOT);
+        PriceCorrelation::set_cycle(true);
+        System::set_block_number(1);
+        Timestamp::set_timestamp(10_000_000);
+
+        assert!(matches!(
+            Oracle::validate_correlations(),
+            Err(CorrelationError::Cycle(_))
+        ));
+
+        run_block_hooks(2);
+        assert!(Oracle::get_price(asset::XDOT).is_err());
+        assert!(Oracle::get_price(asset::HXDOT).is_err());
+    });
+}
+
+#[test]
+fn derived_prices_keep_order_on_runtime_cycle() {
+    new_test_ext().execute_with(|| {
+        AssetGetterMock::add_asset(asset::HXDOT);
+        AssetGetterMock::add_asset(asset::XDOT);
+        AssetGetterMock::add_asset(asset::LDOT);
+        let account_id = Sign { 0: [1; 32] };
+        Whitelist::add_to_whitelist(&account_id);
+        System::set_block_number(1);
+        Timestamp::set_timestamp(10_000_000);
+        set_price_ok(account_id, asset::DOT, 10., 1);
+        RateProviderMock::set_rate(asset::LDOT, FixedI64::saturating_from_rational(11, 10));
+
+        run_block_hooks(1);
+        check_price(asset::LDOT, 11.);
+        let order = <DerivedPriceOrder<Test>>::get();
+        assert_eq!(
+            order.1.clone().into_inner(),
+            vec![asset::LDOT, asset::XDOT, asset::HXDOT]
+        );
+
+        // DOT -> HXDOT -> XDOT -> DOT, other derived prices are still updated
+        PriceCorrelation::set_cycle(true);
+        RateProviderMock::set_rate(asset::LDOT, FixedI64::saturating_from_rational(12, 10));
+        run_block_hooks(2);
+        check_price(asset::LDOT, 12.);
+        check_price(asset::XDOT, 10.);
+        check_price(asset::HXDOT, 5.);
+        assert_eq!(<DerivedPriceOrder<Test>>::get(), order);
+    });
+}
+
+#[test]
+fn correlations_sorted_by_dependencies() {
+    assert_eq!(
+        correlation::sort_by_dependencies(vec![
+            (asset::SLP_POOL, vec![asset::LP_POOL]),
+            (asset::HXDOT, vec![asset::XDOT]),
+            (asset::CMP, vec![asset::HXDOT, asset::BTC]),
+            (asset::LP_POOL, vec![asset::EQD, asset::HXDOT]),
+            (asset::XDOT, vec![asset::DOT]),
+        ]),
+        Ok(vec![
+            asset::XDOT,
+            asset::HXDOT,
+            asset::CMP,
+            asset::LP_POOL,
+            asset::SLP_POOL
+        ])
+    );
+
+    assert_eq!(
+        correlation::sort_by_dependencies(vec![(asset::XDOT, vec![asset::XDOT])]),
+        Err(CorrelationError::Cycle(asset::XDOT))
+    );
+    assert_eq!(
+        correlation::sort_by_dependencies(vec![
+            (asset::CMP, vec![asset::DOT]),
+            (asset::HXDOT, vec![asset::XDOT]),
+            (asset::XDOT, vec![asset::HXDOT]),
+        ]),
+        Err(CorrelationError::Cycle(asset::HXDOT))
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invalid price correlations")]
+fn genesis_with_correlation_cycle_should_panic() {
+    PriceCorrelation::set_cycle(true);
+    AssetGetterMock::add_asset(asset::HXDOT);
+    AssetGetterMock::add_asset(asset::XDOT);
+
+    new_test_ext();
+}
+
+#[test]
+fn derivative_prices_follow_rate_every_block() {
+    new_test_ext().execute_with(|| {
+        AssetGetterMock::add_asset(asset::LDOT);
+        let account_id = Sign { 0: [1; 32] };
+        Whitelist::add_to_whitelist(&account_id);
+        System::set_block_number(1);
+        Timestamp::set_timestamp(10_000_000);
+        set_price_ok(account_id, asset::DOT, 10., 1);
+
+        // there is no rate yet
+        run_block_hooks(2);
+        assert!(Oracle::get_price(asset::LDOT).is_err());
+
+        RateProviderMock::set_rate(asset::LDOT, FixedI64::saturating_from_rational(11, 10));
+        run_block_hooks(3);
+        check_price(asset::LDOT, 11.);
+
+        // base median is not changed, but rate is
+        RateProviderMock::set_rate(asset::LDOT, FixedI64::saturating_from_rational(12, 10));
+        run_block_hooks(4);
+        check_price(asset::LDOT, 12.);
+
+        // stored rate overrides provider
+        assert_ok!(Oracle::set_rate(
+            frame_system::RawOrigin::Root.into(),
+            asset::LDOT,
+            Some(FixedI64::saturating_from_rational(15, 10))
+        ));
+        run_block_hooks(5);
+        check_price(asset::LDOT, 15.);
+
+        assert_ok!(Oracle::set_rate(
+            frame_system::RawOrigin::Root.into(),
+            asset::LDOT,
+            None
+        ));
+        assert_eq!(Oracle::rates(asset::LDOT), None);
+        run_block_hooks(6);
+        check_price(asset::LDOT, 12.);
+    });
+}
+
+#[test]
+fn set_rate_errors() {
+    new_test_ext().execute_with(|| {
+        AssetGetterMock::add_asset(asset::LDOT);
+        let account_id = Sign { 0: [1; 32] };
+
+        assert_err!(
+            Oracle::set_rate(
+                frame_system::RawOrigin::Signed(account_id).into(),
+                asset::LDOT,
+                Some(FixedI64::saturating_from_integer(1))
+            ),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_err!(
+            Oracle::set_rate(
+                frame_system::RawOrigin::Root.into(),
+                asset::LDOT,
+                Some(FixedI64::saturating_from_integer(0))
+            ),
+            Error::<Test>::InvalidRate
+        );
+        assert_err!(
+            Oracle::set_rate(
+                frame_system::RawOrigin::Root.into(),
+                asset::CMP,
+                Some(FixedI64::saturating_from_integer(1))
+            ),
+            Error::<Test>::CurrencyNotFound
+        );
+    });
+}
+
+#[test]
+fn lp_price_calculated_from_pool_state() {
+    new_test_ext().execute_with(|| {
+        AssetGetterMock::add_asset(asset::LP_POOL);
+        LpPriceProviderMock::set_pool_state(
+            asset::LP_POOL,
+            LpPoolState {
+                assets: vec![asset::EQD, asset::DOT],
+                reserves: vec![1_000_000, 100_000],
+                invariant: 2_100_000,
+                lp_supply: 2_000_000,
+            },
+        );
+        let account_id = Sign { 0: [1; 32] };
+        Whitelist::add_to_whitelist(&account_id);
+        System::set_block_number(1);
+        Timestamp::set_timestamp(10_000_000);
+
+        assert_err!(
+            set_price(account_id, asset::LP_POOL, 1., 1),
+            Error::<Test>::WrongCurrency
+        );
+
+        // there is no DOT price yet
+        run_block_hooks(1);
+        assert!(Oracle::get_price(asset::LP_POOL).is_err());
+
+        // virtual price 1.05 times the cheapest asset price (EQD = 1)
+        set_price_ok(account_id, asset::DOT, 10., 1);
+        run_block_hooks(2);
+        check_price(asset::LP_POOL, 1.05);
+
+        // cheapest asset is changed
+        System::set_block_number(2);
+        set_price_ok(account_id, asset::DOT, 0.5, 2);
+        run_block_hooks(3);
+        check_price(asset::LP_POOL, 0.525);
+    });
+}
+
+#[test]
+fn correlated_price_depends_on_lp_token() {
+    new_test_ext().execute_with(|| {
+        // SLP_POOL goes before LP_POOL in assets list, but depends on it
+        AssetGetterMock::add_asset(asset::SLP_POOL);
+        AssetGetterMock::add_asset(asset::LP_POOL);
+        LpPriceProviderMock::set_pool_state(
+            asset::LP_POOL,
+            LpPoolState {
+                assets: vec![asset::EQD, asset::DOT],
+                reserves: vec![1_000_000, 100_000],
+                invariant: 2_100_000,
+                lp_supply: 2_000_000,
+            },
+        );
+        let account_id = Sign { 0: [1; 32] };
+        Whitelist::add_to_whitelist(&account_id);
+        System::set_block_number(1);
+        Timestamp::set_timestamp(10_000_000);
+        assert_eq!(
+            Oracle::validate_correlations(),
+            Ok(vec![asset::LP_POOL, asset::SLP_POOL])
+        );
+
+        set_price_ok(account_id, asset::DOT, 10., 1);
+        run_block_hooks(1);
+        check_price(asset::LP_POOL, 1.05);
+        check_price(asset::SLP_POOL, 2.1);
+
+        System::set_block_number(2);
+        set_price_ok(account_id, asset::DOT, 0.5, 2);
+        run_block_hooks(2);
+        check_price(asset::LP_POOL, 0.525);
+        check_price(asset::SLP_POOL, 1.05);
+    });
+}
+
+#[test]
+fn lp_price_not_set_for_invalid_pool_state() {
+    new_test_ext().execute_with(|| {
+        AssetGetterMock::add_asset(asset::LP_POOL);
+        let account_id = Sign { 0: [1; 32] };
+        Whitelist::add_to_whitelist(&account_id);
+        System::set_block_number(1);
+        Timestamp::set_timestamp(10_000_000);
+        set_price_ok(account_id, asset::DOT, 10., 1);
+
+        // empty reserve
+        LpPriceProviderMock::set_pool_state(
+            asset::LP_POOL,
+            LpPoolState {
+                assets: vec![asset::EQD, asset::DOT],
+                reserves: vec![1_000_000, 0],
+                invariant: 2_100_000,
+                lp_supply: 2_000_000,
+            },
+        );
+        run_block_hooks(2);
+        assert!(Oracle::get_price(asset::LP_POOL).is_err());
+
+        // no LP tokens issued
+        LpPriceProviderMock::set_pool_state(
+            asset::LP_POOL,
+            LpPoolState {
+                assets: vec![asset::EQD, asset::DOT],
+                reserves: vec![1_000_000, 100_000],
+                invariant: 2_100_000,
+                lp_supply: 0,
+            },
+        );
+        run_block_hooks(3);
+        assert!(Oracle::get_price(asset::LP_POOL).is_err());
+    });
+}
+
+#[test]
+fn source_settings_validation() {
+    new_offchain_test_ext().execute_with(|| {
+        for (document, error) in [
+            (
+                r#"{"version":10,"source_type":"pancake","price_periodicity":1}"#,
+                ConfigError::MissingPancakeSettings,
+            ),
+            (
+                r#"{"version":10,"source_type":"pancake","price_periodicity":1,"pancake":{"node_url":"https://bsc-dataseed.binance.org/","pools":{"bnb-busd":{"contract":"0x58f876857a02d6762e0101bb5c46a8c1ed44dc16","token_0":"bnb"}}}}"#,
+                ConfigError::MissingPancakeSettings,
+            ),
+            (
+                r#"{"version":10,"source_type":"evm","price_periodicity":1}"#,
+                ConfigError::MissingEvmSettings,
+            ),
+            (
+                r#"{"version":10,"source_type":"chainlink","price_periodicity":1,"chainlink":{"node_url":"https://eth.node","max_round_age":0,"aggregators":{"eth":"0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419"}}}"#,
+                ConfigError::MissingChainlinkSettings,
+            ),
+            (
+                r#"{"version":10,"source_type":"substrate","price_periodicity":1}"#,
+                ConfigError::MissingSubstrateSettings,
+            ),
+            (
+                r#"{"version":10,"source_type":"graphql","price_periodicity":1,"graphql":{"url":"https://api.thegraph.com/subgraphs/name/uniswap/uniswap-v3","query":"{ bundle(id: 1) { ethPriceUSD } }"}}"#,
+                ConfigError::MissingGraphqlSettings,
+            ),
+        ] {
+            set_offchain_value(storage_keys::CONFIG, document);
+            assert_eq!(offchain_storage::get_config(), Err(error));
+        }
+
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":10,"source_type":"substrate","price_periodicity":1,"substrate":{"node_url":"https://rpc.polkadot.io","feeds":{"dot":{"key":"0x26aa","decoder":"u32"}}}}"#,
+        );
+        assert!(matches!(
+            offchain_storage::get_config(),
+            Err(ConfigError::Malformed(_))
+        ));
+
+        let feed = EvmFeed {
+            contract: "0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419".into(),
+            function: "latestAnswer()".into(),
+            outputs: vec!["int256".into()],
+            output_index: 0,
+            decimals: 8,
+        };
+        for invalid_feed in [
+            EvmFeed {
+                output_index: 1,
+                ..feed.clone()
+            },
+            EvmFeed {
+                outputs: vec!["bytes32".into()],
+                ..feed.clone()
+            },
+            EvmFeed {
+                function: "latestAnswer".into(),
+                ..feed.clone()
+            },
+            EvmFeed {
+                decimals: 39,
+                ..feed.clone()
+            },
+        ] {
+            let config = OracleOffchainConfig {
+                version: CONFIG_VERSION,
+                source_type: "evm".into(),
+                price_periodicity: 1,
+                evm: Some(EvmSettings {
+                    node_url: "https://eth.node".into(),
+                    feeds: vec![("eth".into(), invalid_feed)].into_iter().collect(),
+                }),
+                ..Default::default()
+            };
+            assert_eq!(
+                offchain_storage::set_config(&config),
+                Err(ConfigError::InvalidEvmFeed("eth".into()))
+            );
+        }
+
+        let feed = SubstrateFeed {
+            key: "0x26aa394eea5630e07c48ae0c9558cef7{$}".into(),
+            decoder: SubstrateDecoder::Reserves,
+            decimals: 10,
+            quote_decimals: 6,
+            ..Default::default()
+        };
+        for invalid_feed in [
+            SubstrateFeed {
+                key: "0xzz".into(),
+                ..feed.clone()
+            },
+            SubstrateFeed {
+                key: "".into(),
+                ..feed.clone()
+            },
+            SubstrateFeed {
+                method: SubstrateMethod::Call,
+                ..feed.clone()
+            },
+            SubstrateFeed {
+                quote_decimals: 39,
+                ..feed.clone()
+            },
+        ] {
+            let config = OracleOffchainConfig {
+                version: CONFIG_VERSION,
+                source_type: "substrate".into(),
+                price_periodicity: 1,
+                substrate: Some(SubstrateSettings {
+                    node_url: "https://rpc.polkadot.io".into(),
+                    feeds: vec![("dot".into(), invalid_feed)].into_iter().collect(),
+                }),
+                ..Default::default()
+            };
+            assert_eq!(
+                offchain_storage::set_config(&config),
+                Err(ConfigError::InvalidSubstrateFeed("dot".into()))
+            );
+        }
+    });
+}
+
+/// JSON-RPC response of a node with a string `result`
+fn rpc_result(result: &str) -> String {
+    format!(r#"{{"jsonrpc":"2.0","id":1,"result":"{}"}}"#, result)
+}
+
+/// JSON-RPC error response of a node
+const RPC_ERROR: &str =
+    r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32000,"message":"execution reverted"}}"#;
+
+/// Hex encoded ABI words of `values`
+fn abi_words(values: &[u128]) -> String {
+    values.iter().fold("0x".into(), |words, value| {
+        format!("{}{:064x}", words, value)
+    })
+}
+
+#[test]
+fn pancake_source_errors() {
+    let url = "https://bsc-dataseed.binance.org/";
+    let address = |address: &str| format!("0x{:0>64}", address.trim_start_matches("0x"));
+    let pool = PancakePool {
+        contract: "0x58f876857a02d6762e0101bb5c46a8c1ed44dc16".into(),
+        token_0: "btc".into(),
+        token_1: "eth".into(),
+    };
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "pancake".into(),
+        price_periodicity: 1,
+        pancake: Some(PancakeSettings {
+            node_url: url.into(),
+            pools: vec![("lpt0".into(), pool.clone()), ("xlpt0".into(), pool)]
+                .into_iter()
+                .collect(),
+        }),
+        ..Default::default()
+    };
+
+    new_offchain_test_ext().execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&config));
+        assert_eq!(offchain_storage::get_config(), Ok(config.clone()));
+
+        let btc = "0x7130d2a12b9bcbfae4f2634d864a1ee1ce3ead9c";
+        let eth = "0x2170ed0880ac9a755fd29b2688956bd959f933f8";
+        let token = |amount: u128| amount * 1_000_000_000_000_000_000;
+        let pool_state = [
+            rpc_result("0x10"),
+            rpc_result(&abi_words(&[token(1_000), token(400_000), 0])),
+            rpc_result(&abi_words(&[token(10_000)])),
+            rpc_result(&abi_words(&[18])),
+            rpc_result(&address(btc)),
+            rpc_result(&abi_words(&[18])),
+            rpc_result(&address(eth)),
+            rpc_result(&abi_words(&[18])),
+        ];
+        // decimals of a pool token don't fit calculations, the rest of the pool is not requested
+        for result in &pool_state[..5] {
+            HttpMock::expect(HttpMethod::Post, url, Ok(result));
+        }
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result(&abi_words(&[37]))));
+        // pool tokens have no prices on chain
+        for result in &pool_state {
+            HttpMock::expect(HttpMethod::Post, url, Ok(result));
+        }
+
+        let source = PancakePriceSource::<Asset, (), Oracle, HttpMock>::new(
+            &config,
+            vec![
+                (asset::LP_CURVE, ()),
+                (asset::LP_XDOT, ()),
+                (asset::BTC, ()),
+                (asset::ETH, ()),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![
+                (asset::LP_CURVE, Err("Invalid contract value")),
+                (asset::LP_XDOT, Err("No pool token price")),
+            ]
+        );
+        assert!(HttpMock::is_done());
+    });
+}
+
+#[test]
+fn pancake_fair_lp_price() {
+    use sp_core::U512;
+
+    let amount = |value: u128| U512::from(value) * U512::exp10(18);
+
+    // 1000 BNB and 400 000 BUSD, BNB costs 400 BUSD, 10 000 LP tokens
+    assert_eq!(
+        fair_lp_price(
+            amount(1_000),
+            amount(400_000),
+            amount(400),
+            amount(1),
+            amount(10_000)
+        ),
+        Some(80 * 1_000_000_000_000_000_000)
+    );
+
+    // reserves are skewed by a swap with the same invariant,
+    // (4000 * 400 + 100 000) / 10 000 = 170 by spot reserves
+    assert_eq!(
+        fair_lp_price(
+            amount(4_000),
+            amount(100_000),
+            amount(400),
+            amount(1),
+            amount(10_000)
+        ),
+        Some(80 * 1_000_000_000_000_000_000)
+    );
+
+    assert_eq!(
+        fair_lp_price(
+            amount(1_000),
+            amount(400_000),
+            amount(400),
+            amount(1),
+            U512::zero()
+        ),
+        None
+    );
+}
+
+#[test]
+fn evm_abi_encoding() {
+    assert_eq!(evm::selector("decimals()"), [0x31, 0x3c, 0xe5, 0x67]);
+    assert_eq!(evm::selector("getReserves()"), [0x09, 0x02, 0xf1, 0xac]);
+
+    let holder = "0x58f876857a02d6762e0101bb5c46a8c1ed44dc16";
+    assert_eq!(
+        evm::to_hex(&evm::encode_call(
+            "balanceOf(address)",
+            &[AbiValue::address(holder).unwrap()]
+        )),
+        "0x70a08231\
+         00000000000000000000000058f876857a02d6762e0101bb5c46a8c1ed44dc16"
+    );
+    assert_eq!(
+        AbiValue::address("0x58f876"),
+        Err(PriceSourceError::AbiError)
+    );
+    assert_eq!(evm::from_hex("0x0g"), Err(PriceSourceError::AbiError));
+
+    assert_eq!(AbiType::parse("uint80"), Some(AbiType::Uint(80)));
+    assert_eq!(AbiType::parse("int"), Some(AbiType::Int(256)));
+    assert_eq!(AbiType::parse("address"), Some(AbiType::Address));
+    assert_eq!(AbiType::parse("uint7"), None);
+    assert_eq!(AbiType::parse("bytes"), None);
+}
+
+#[test]
+fn evm_abi_decoding() {
+    use sp_core::U256;
+
+    let word = |value: u128| format!("{:064x}", value);
+    let latest_round_data = [
+        AbiType::Uint(80),
+        AbiType::Int(256),
+        AbiType::Uint(256),
+        AbiType::Uint(256),
+        AbiType::Uint(80),
+    ];
+
+    // (roundId, answer, startedAt, updatedAt, answeredInRound)
+    let output = evm::from_hex(&format!(
+        "0x{}{}{}{}{}",
+        word(7),
+        word(2_000_00000000),
+        word(1_650_000_000),
+        word(1_650_000_100),
+        word(7)
+    ))
+    .unwrap();
+    let values = evm::decode(&latest_round_data, &output).unwrap();
+    assert_eq!(values.len(), 5);
+    assert_eq!(values[1].as_uint(), Some(U256::from(2_000_00000000u128)));
+    assert_eq!(values[3], AbiValue::Uint(U256::from(1_650_000_100u128)));
+
+    // negative answer
+    let output = evm::from_hex(&format!(
+        "0x{}{}{}{}{}",
+        word(7),
+        "f".repeat(64),
+        word(0),
+        word(0),
+        word(7)
+    ))
+    .unwrap();
+    let values = evm::decode(&latest_round_data, &output).unwrap();
+    assert_eq!(
+        values[1],
+        AbiValue::Int {
+            negative: true,
+            abs: U256::one()
+        }
+    );
+    assert_eq!(values[1].as_uint(), None);
+
+    // value doesn't fit the type
+    assert_eq!(
+        evm::decode(&[AbiType::Uint(8)], &evm::from_hex(&word(256)).unwrap()),
+        Err(PriceSourceError::AbiError)
+    );
+    assert_eq!(
+        evm::decode(&[AbiType::Int(8)], &evm::from_hex(&word(128)).unwrap()),
+        Err(PriceSourceError::AbiError)
+    );
+    // output is too short
+    assert_eq!(
+        evm::decode(&latest_round_data, &evm::from_hex(&word(7)).unwrap()),
+        Err(PriceSourceError::AbiError)
+    );
+
+    let address = evm::decode(
+        &[AbiType::Address],
+        &evm::from_hex("0x00000000000000000000000058f876857a02d6762e0101bb5c46a8c1ed44dc16")
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        address[0].as_address(),
+        Some("0x58f876857a02d6762e0101bb5c46a8c1ed44dc16".into())
+    );
+}
+
+#[test]
+fn evm_source_errors() {
+    let url = "https://eth.node";
+    let feed = EvmFeed {
+        contract: "0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419".into(),
+        function: "latestAnswer()".into(),
+        outputs: vec!["int256".into()],
+        output_index: 0,
+        decimals: 8,
+    };
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "evm".into(),
+        price_periodicity: 1,
+        evm: Some(EvmSettings {
+            node_url: url.into(),
+            feeds: vec![
+                ("btc".into(), feed.clone()),
+                ("eth".into(), feed.clone()),
+                ("dot".into(), feed),
+            ]
+            .into_iter()
+            .collect(),
+        }),
+        ..Default::default()
+    };
+
+    new_offchain_test_ext().execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&config));
+        assert_eq!(offchain_storage::get_config(), Ok(config.clone()));
+
+        let source = EvmPriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ()), (asset::DOT, ())],
+        )
+        .unwrap();
+
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result("0x10")));
+        // call is reverted
+        HttpMock::expect(HttpMethod::Post, url, Ok(RPC_ERROR));
+        // negative answer
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(&rpc_result(&format!("0x{}", "f".repeat(64)))),
+        );
+        // output is shorter than the outputs of the feed
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result("0x01")));
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![
+                (asset::BTC, Err("Json rpc error")),
+                (asset::ETH, Err("Invalid contract value")),
+                (asset::DOT, Err("Abi error")),
+            ]
+        );
+        assert!(HttpMock::is_done());
+
+        // feeds are not called without a block of the node
+        HttpMock::expect(HttpMethod::Post, url, Err(RequestError::IoError));
+        assert_eq!(source.get_prices::<FixedI64>(), vec![]);
+        assert!(HttpMock::is_done());
+    });
+}
+
+#[test]
+fn evm_source_requests_share_one_deadline() {
+    let url = "https://eth.node";
+    let feed = EvmFeed {
+        contract: "0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419".into(),
+        function: "latestAnswer()".into(),
+        outputs: vec!["int256".into()],
+        output_index: 0,
+        decimals: 8,
+    };
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "evm".into(),
+        price_periodicity: 1,
+        evm: Some(EvmSettings {
+            node_url: url.into(),
+            feeds: vec![("btc".into(), feed.clone()), ("eth".into(), feed)]
+                .into_iter()
+                .collect(),
+        }),
+        http: vec![(
+            "evm".into(),
+            HttpOptions {
+                retries: 1,
+                backoff_ms: 15_000,
+                ..Default::default()
+            },
+        )]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+    let unavailable = || RequestError::Status {
+        code: 503,
+        body: "unavailable".into(),
+    };
+
+    new_offchain_test_ext().execute_with(|| {
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result("0x10")));
+        HttpMock::expect(HttpMethod::Post, url, Err(unavailable()));
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(&rpc_result(&abi_words(&[30_000_00000000]))),
+        );
+        // a retry after the backoff doesn't fit the deadline of the run
+        HttpMock::expect(HttpMethod::Post, url, Err(unavailable()));
+
+        let source = EvmPriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![
+                (asset::BTC, Ok(FixedI64::saturating_from_integer(30_000))),
+                (asset::ETH, Err("Http status error")),
+            ]
+        );
+        assert!(HttpMock::is_done());
+        assert_eq!(sp_io::offchain::timestamp().unix_millis(), 15_000);
+    });
+}
+
+#[test]
+fn chainlink_round_checks() {
+    use sp_core::U256;
+
+    let round = RoundData {
+        round_id: U256::from(10),
+        answer: AbiValue::Int {
+            negative: false,
+            abs: U256::from(2_000_00000000u128),
+        },
+        started_at: U256::from(1_000),
+        updated_at: U256::from(1_000),
+        answered_in_round: U256::from(10),
+    };
+    assert_eq!(
+        round.checked_answer(1_600, 600),
+        Ok(U256::from(2_000_00000000u128))
+    );
+
+    assert_eq!(
+        round.checked_answer(1_601, 600),
+        Err(PriceSourceError::StaleRound)
+    );
+    assert_eq!(
+        RoundData {
+            answered_in_round: U256::from(9),
+            ..round.clone()
+        }
+        .checked_answer(1_000, 600),
+        Err(PriceSourceError::IncompleteRound)
+    );
+    assert_eq!(
+        RoundData {
+            updated_at: U256::zero(),
+            ..round.clone()
+        }
+        .checked_answer(1_000, 600),
+        Err(PriceSourceError::IncompleteRound)
+    );
+    assert_eq!(
+        RoundData {
+            answer: AbiValue::Int {
+                negative: true,
+                abs: U256::one(),
+            },
+            ..round.clone()
+        }
+        .checked_answer(1_000, 600),
+        Err(PriceSourceError::InvalidContractValue)
+    );
+    assert_eq!(
+        RoundData {
+            answer: AbiValue::Int {
+                negative: false,
+                abs: U256::zero(),
+            },
+            ..round
+        }
+        .checked_answer(1_000, 600),
+        Err(PriceSourceError::InvalidContractValue)
+    );
+}
+
+#[test]
+fn chainlink_source_errors() {
+    let url = "https://eth.node";
+    let aggregator = "0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419";
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "chainlink".into(),
+        price_periodicity: 1,
+        chainlink: Some(ChainlinkSettings {
+            node_url: url.into(),
+            max_round_age: 3600,
+            aggregators: vec![
+                ("btc".into(), aggregator.into()),
+                ("eth".into(), aggregator.into()),
+                ("dot".into(), aggregator.into()),
+            ]
+            .into_iter()
+            .collect(),
+        }),
+        ..Default::default()
+    };
+
+    let now: u64 = 1_650_000_000;
+    let mut test = OffchainWorkerTest::new(false);
+    test.offchain.write().timestamp = OffchainTimestamp::from_unix_millis(now * 1000);
+    test.execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&config));
+        assert_eq!(offchain_storage::get_config(), Ok(config.clone()));
+
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result("0x10")));
+        // answer doesn't fit with its decimals
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result(&abi_words(&[39]))));
+        // (roundId, answer, startedAt, updatedAt, answeredInRound) is older than `max_round_age`
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result(&abi_words(&[8]))));
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(&rpc_result(&abi_words(&[
+                7,
+                2_000_00000000,
+                (now - 3601).into(),
+                (now - 3601).into(),
+                7,
+            ]))),
+        );
+        // answer is carried over from a previous round
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result(&abi_words(&[8]))));
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(&rpc_result(&abi_words(&[
+                8,
+                7_00000000,
+                now.into(),
+                now.into(),
+                7,
+            ]))),
+        );
+
+        let source = ChainlinkPriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ()), (asset::DOT, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![
+                (asset::BTC, Err("Invalid contract value")),
+                (asset::ETH, Err("Stale round")),
+                (asset::DOT, Err("Incomplete round")),
+            ]
+        );
+        assert!(HttpMock::is_done());
+    });
+}
+
+#[test]
+fn chainlink_prices_observed_at_round_update() {
+    let url = "https://eth.node";
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "chainlink".into(),
+        price_periodicity: 1,
+        source_assets: vec![
+            ("btc".into(), PriceStrategy::Reverse),
+            ("eth".into(), PriceStrategy::Price),
+        ]
+        .into_iter()
+        .collect(),
+        chainlink: Some(ChainlinkSettings {
+            node_url: url.into(),
+            max_round_age: 3600,
+            aggregators: vec![
+                (
+                    "btc".into(),
+                    "0xf4030086522a5beea4988f8ca5b36dbc97bee88c".into(),
+                ),
+                (
+                    "eth".into(),
+                    "0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419".into(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        }),
+        ..Default::default()
+    };
+
+    let now: u64 = 1_650_000_000;
+    let mut test = OffchainWorkerTest::new(false);
+    test.offchain.write().timestamp = OffchainTimestamp::from_unix_millis(now * 1000);
+    test.execute_with(|| {
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result("0x10")));
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result(&abi_words(&[8]))));
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(&rpc_result(&abi_words(&[
+                7,
+                20_000_00000000,
+                (now - 600).into(),
+                (now - 120).into(),
+                7,
+            ]))),
+        );
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result(&abi_words(&[8]))));
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(&rpc_result(&abi_words(&[
+                3,
+                2_000_00000000,
+                (now - 600).into(),
+                (now - 60).into(),
+                3,
+            ]))),
+        );
+
+        let source = ChainlinkPriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_observed_prices::<FixedI64>(),
+            vec![
+                (
+                    asset::BTC,
+                    Ok((
+                        FixedI64::saturating_from_rational(1, 20_000),
+                        Some(now - 120)
+                    ))
+                ),
+                (
+                    asset::ETH,
+                    Ok((FixedI64::saturating_from_integer(2_000), Some(now - 60)))
+                ),
+            ]
+        );
+        assert!(HttpMock::is_done());
+    });
+}
+
+#[test]
+fn substrate_feed_decoding() {
+    use codec::Encode;
+
+    assert_eq!(feed_key("0x26aa{$}", "dot"), "0x26aa646f74");
+    assert_eq!(feed_key("26aa", "dot"), "0x26aa");
+
+    let feed = SubstrateFeed {
+        decoder: SubstrateDecoder::U128,
+        decimals: 12,
+        ..Default::default()
+    };
+    assert_eq!(
+        decode_price::<FixedI64>(&feed, &7_500_000_000_000u128.encode()),
+        Ok(FixedI64::saturating_from_rational(75, 10))
+    );
+    // value is too short
+    assert_eq!(
+        decode_price::<FixedI64>(&feed, &7_500u64.encode()),
+        Err(PriceSourceError::ScaleDecodeError)
+    );
+
+    // `Some(u64)` returned by a runtime api
+    let feed = SubstrateFeed {
+        method: SubstrateMethod::Call,
+        function: "OracleApi_get_price".into(),
+        decoder: SubstrateDecoder::U64,
+        offset: 1,
+        decimals: 9,
+        ..Default::default()
+    };
+    assert_eq!(
+        decode_price::<FixedI64>(&feed, &Some(2_500_000_000u64).encode()),
+        Ok(FixedI64::saturating_from_rational(25, 10))
+    );
+    assert_eq!(
+        decode_price::<FixedI64>(&feed, &[]),
+        Err(PriceSourceError::ScaleDecodeError)
+    );
+
+    // 1000 DOT (10 decimals) and 6500 USDT (6 decimals) in a pool
+    let feed = SubstrateFeed {
+        decoder: SubstrateDecoder::Reserves,
+        decimals: 10,
+        quote_decimals: 6,
+        ..Default::default()
+    };
+    assert_eq!(
+        decode_price::<FixedI64>(&feed, &(10_000_000_000_000u128, 6_500_000_000u128).encode()),
+        Ok(FixedI64::saturating_from_rational(65, 10))
+    );
+    assert_eq!(
+        decode_price::<FixedI64>(&feed, &(0u128, 6_500_000_000u128).encode()),
+        Err(PriceSourceError::InvalidContractValue)
+    );
+}
+
+#[test]
+fn substrate_source_errors() {
+    let url = "https://rpc.polkadot.io";
+    let feed = SubstrateFeed {
+        key: "0x26aa394eea5630e07c48ae0c9558cef7{$}".into(),
+        decoder: SubstrateDecoder::U128,
+        decimals: 10,
+        ..Default::default()
+    };
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "substrate".into(),
+        price_periodicity: 1,
+        substrate: Some(SubstrateSettings {
+            node_url: url.into(),
+            feeds: vec![
+                ("btc".into(), feed.clone()),
+                (
+                    "eth".into(),
+                    SubstrateFeed {
+                        method: SubstrateMethod::Call,
+                        function: "OracleApi_get_price".into(),
+                        ..feed.clone()
+                    },
+                ),
+                ("dot".into(), feed),
+            ]
+            .into_iter()
+            .collect(),
+        }),
+        ..Default::default()
+    };
+
+    new_offchain_test_ext().execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&config));
+        assert_eq!(offchain_storage::get_config(), Ok(config.clone()));
+
+        // storage has no value at the key
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(r#"{"jsonrpc":"2.0","id":1,"result":null}"#),
+        );
+        // runtime api call fails
+        HttpMock::expect(HttpMethod::Post, url, Ok(RPC_ERROR));
+        // value is shorter than the decoder expects
+        HttpMock::expect(HttpMethod::Post, url, Ok(&rpc_result("0x0100")));
+
+        let source = SubstratePriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ()), (asset::DOT, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![
+                (asset::BTC, Err("No remote value")),
+                (asset::ETH, Err("Json rpc error")),
+                (asset::DOT, Err("Scale decode error")),
+            ]
+        );
+
+        assert!(HttpMock::is_done());
+        assert_eq!(
+            HttpMock::requests()[1].body,
+            br#"{"jsonrpc":"2.0","method":"state_call","params":["OracleApi_get_price","0x26aa394eea5630e07c48ae0c9558cef7657468"],"id":1}"#.to_vec()
+        );
+    });
+}
+
+#[test]
+fn graphql_request_and_response() {
+    let settings = GraphqlSettings {
+        url: "https://api.thegraph.com/subgraphs/name/uniswap/uniswap-v3".into(),
+        query: "query($symbol: String!) { tokens(where: {symbol: $symbol}) { derivedUSD } }".into(),
+        variables: vec![("symbol".into(), "{$}".into())].into_iter().collect(),
+        price_path: "data.tokens[0].derivedUSD".into(),
+        uppercase: true,
+    };
+
+    assert_eq!(
+        GraphqlPriceSource::<Asset, ()>::request_body(&settings, "WETH"),
+        Ok(r#"{"query":"query($symbol: String!) { tokens(where: {symbol: $symbol}) { derivedUSD } }","variables":{"symbol":"WETH"}}"#.into())
+    );
+
+    let response = r#"{"data":{"tokens":[{"derivedUSD":"1834.25"}]}}"#;
+    assert_eq!(
+        JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(
+            response,
+            &settings.price_path
+        ),
+        Ok((FixedI64::saturating_from_rational(183425, 100), None))
+    );
+}
+
+#[test]
+fn graphql_source_errors() {
+    let url = "https://api.thegraph.com/subgraphs/name/uniswap/uniswap-v3";
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "graphql".into(),
+        price_periodicity: 1,
+        source_assets: vec![
+            ("btc".into(), PriceStrategy::Price),
+            ("eth".into(), PriceStrategy::Price),
+            ("dot".into(), PriceStrategy::Reverse),
+        ]
+        .into_iter()
+        .collect(),
+        graphql: Some(GraphqlSettings {
+            url: url.into(),
+            query: "query($symbol: String!) { tokens(where: {symbol: $symbol}) { derivedUSD } }"
+                .into(),
+            variables: vec![("symbol".into(), "{$}".into())].into_iter().collect(),
+            price_path: "data.tokens[0].derivedUSD".into(),
+            uppercase: true,
+        }),
+        ..Default::default()
+    };
+
+    new_offchain_test_ext().execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&config));
+        assert_eq!(offchain_storage::get_config(), Ok(config.clone()));
+
+        // query errors are returned without data
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(r#"{"errors":[{"message":"indexing_error"}]}"#),
+        );
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Err(RequestError::Status {
+                code: 502,
+                body: "bad gateway".into(),
+            }),
+        );
+        // zero price can't be reversed
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(r#"{"data":{"tokens":[{"derivedUSD":"0"}]}}"#),
+        );
+
+        let source = GraphqlPriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ()), (asset::DOT, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![
+                (asset::BTC, Err("Json parse error")),
+                (asset::ETH, Err("Http status error")),
+                (asset::DOT, Err("Json price conversion error")),
+            ]
+        );
+        assert!(HttpMock::is_done());
+    });
+}
+
+#[test]
+fn http_options_keep_secrets_out_of_logs() {
+    let options = HttpOptions {
+        headers: vec![("x-cg-pro-api-key".into(), "cg-secret".into())]
+            .into_iter()
+            .collect(),
+        query: vec![("api_key".into(), "key/with+chars".into())]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+
+    let url = http_client::with_query(
+        "https://pro-api.coingecko.com/api/v3/simple/price?ids=eth",
+        &options,
+    );
+    assert_eq!(
+        url,
+        "https://pro-api.coingecko.com/api/v3/simple/price?ids=eth&api_key=key%2Fwith%2Bchars"
+    );
+    assert_eq!(
+        http_client::with_query("https://min-api.cryptocompare.com/data/price", &options),
+        "https://min-api.cryptocompare.com/data/price?api_key=key%2Fwith%2Bchars"
+    );
+    // query parameters are redacted in sent urls
+    assert_eq!(
+        options.redact(&url),
+        "https://pro-api.coingecko.com/api/v3/simple/price?ids=eth&api_key=***"
+    );
+
+    assert_eq!(
+        options.redact("https://rpc.example.com/cg-secret?api_key=key/with+chars"),
+        "https://rpc.example.com/***?api_key=***"
+    );
+    let debug = format!("{:?}", options);
+    assert!(debug.contains("x-cg-pro-api-key") && debug.contains("api_key"));
+    assert!(!debug.contains("cg-secret") && !debug.contains("key/with+chars"));
+}
+
+#[test]
+fn http_options_in_config() {
+    new_offchain_test_ext().execute_with(|| {
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":10,"source_type":"custom","price_periodicity":1,"custom_query":"json(https://pro-api.coingecko.com/api/v3/simple/price?ids={$}&vs_currencies=usd).{$}.usd","http":{"custom":{"headers":{"x-cg-pro-api-key":"cg-secret"}}}}"#,
+        );
+        let config = offchain_storage::get_config().unwrap();
+        assert_eq!(
+            config.http_options("custom").headers.get("x-cg-pro-api-key"),
+            Some(&"cg-secret".into())
+        );
+        assert_eq!(config.http_options("evm"), HttpOptions::default());
+
+        set_offchain_value(
+            storage_keys::CONFIG,
+            r#"{"version":10,"source_type":"custom","price_periodicity":1,"custom_query":"json(https://pro-api.coingecko.com/api/v3/simple/price?ids={$}&vs_currencies=usd).{$}.usd","http":{"custom":{"headers":{"x-cg pro":"cg-secret"}}}}"#,
+        );
+        assert_eq!(
+            offchain_storage::get_config(),
+            Err(ConfigError::InvalidHttpOptions("custom".into()))
+        );
+    });
+}
+
+#[test]
+fn http_retries_with_backoff() {
+    use http_client::RequestError;
+
+    let options = HttpOptions {
+        retries: 2,
+        backoff_ms: 300,
+        ..Default::default()
+    };
+    let status = |code| RequestError::Status {
+        code,
+        body: "".into(),
+    };
+
+    assert_eq!(
+        http_client::retry_delay(&options, 0, &RequestError::DeadlineReached),
+        Some(300)
+    );
+    assert_eq!(
+        http_client::retry_delay(&options, 1, &RequestError::IoError),
+        Some(600)
+    );
+    assert_eq!(
+        http_client::retry_delay(&options, 1, &status(503)),
+        Some(600)
+    );
+    // retries are exhausted
+    assert_eq!(http_client::retry_delay(&options, 2, &status(503)), None);
+    // client errors are not retried
+    assert_eq!(http_client::retry_delay(&options, 0, &status(404)), None);
+    assert_eq!(
+        http_client::retry_delay(&options, 0, &RequestError::NotUtf8),
+        None
+    );
+    // rate limits are retried after `Retry-After`
+    assert_eq!(
+        http_client::retry_delay(
+            &options,
+            0,
+            &RequestError::RateLimited {
+                retry_after: Some(2)
+            }
+        ),
+        Some(2_000)
+    );
+    assert_eq!(
+        http_client::retry_delay(
+            &options,
+            1,
+            &RequestError::RateLimited { retry_after: None }
+        ),
+        Some(600)
+    );
+    // no retries by default
+    assert_eq!(
+        http_client::retry_delay(&HttpOptions::default(), 0, &RequestError::IoError),
+        None
+    );
+
+    assert_eq!(http_client::parse_retry_after(" 120 "), Some(120));
+    assert_eq!(
+        http_client::parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+        None
+    );
+
+    let body = [b'a'; 1000];
+    assert_eq!(http_client::body_snippet(&body).len(), 256);
+    assert_eq!(http_client::body_snippet(&[0xff, b'o', b'k']), "\u{fffd}ok");
+
+    assert_eq!(
+        PriceSourceError::from(RequestError::RateLimited { retry_after: None }),
+        PriceSourceError::RateLimited
+    );
+    assert_eq!(
+        PriceSourceError::from(status(500)),
+        PriceSourceError::HttpStatus {
+            code: 500,
+            body: "".into()
+        }
+    );
+    assert_eq!(
+        PriceSourceError::from(RequestError::NotUtf8),
+        PriceSourceError::HttpNotUtf8
+    );
+    assert_eq!(
+        PriceSourceError::from(RequestError::DeadlineReached),
+        PriceSourceError::HttpDeadlineReached
+    );
+    assert_eq!(
+        PriceSourceError::from(RequestError::IoError),
+        PriceSourceError::HttpError
+    );
+}
+
+#[test]
+fn http_options_limits() {
+    let options = HttpOptions::default();
+    assert_eq!(options.timeout(), DEFAULT_HTTP_TIMEOUT_MS);
+    assert_eq!(options.backoff(), DEFAULT_HTTP_BACKOFF_MS);
+    assert!(options.is_valid());
+
+    assert!(HttpOptions {
+        timeout_ms: MAX_HTTP_REQUEST_TIME_MS,
+        retries: MAX_HTTP_RETRIES,
+        ..Default::default()
+    }
+    .is_valid());
+    assert!(!HttpOptions {
+        retries: MAX_HTTP_RETRIES + 1,
+        ..Default::default()
+    }
+    .is_valid());
+    assert!(!HttpOptions {
+        timeout_ms: MAX_HTTP_REQUEST_TIME_MS + 1,
+        ..Default::default()
+    }
+    .is_valid());
+}
+
+#[test]
+fn custom_source_fetches_prices_in_parallel() {
+    let request = |symbol: &str, price: &str| PendingRequest {
+        method: "GET".into(),
+        uri: format!(
+            "https://api.binance.com/api/v3/ticker/price?symbol={}USDT",
+            symbol
+        ),
+        response: Some(
+            format!(r#"{{"symbol":"{}USDT","price":"{}"}}"#, symbol, price).into_bytes(),
+        ),
+        sent: true,
+        ..Default::default()
+    };
+
+    new_offchain_test_ext_with_http(vec![request("BTC", "30000.5"), request("ETH", "1800.25")])
+        .execute_with(|| {
+            let config = OracleOffchainConfig {
+                version: CONFIG_VERSION,
+                source_type: "custom".into(),
+                price_periodicity: 1,
+                custom_query: Some(
+                    "json(https://api.binance.com/api/v3/ticker/price?symbol={$}USDT).price".into(),
+                ),
+                source_assets: vec![
+                    ("btc".into(), PriceStrategy::Price),
+                    ("eth".into(), PriceStrategy::Reverse),
+                ]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+            let source = JsonPriceSource::<Asset, ()>::new(
+                &config,
+                vec![(asset::BTC, ()), (asset::EQ, ()), (asset::ETH, ())],
+            )
+            .unwrap();
+
+            assert_eq!(
+                source.get_prices::<FixedI64>(),
+                vec![
+                    (
+                        asset::BTC,
+                        Ok(FixedI64::saturating_from_rational(300005, 10))
+                    ),
+                    (
+                        asset::ETH,
+                        FixedI64::saturating_from_rational(180025, 100)
+                            .reciprocal()
+                            .ok_or("")
+                    ),
+                ]
+            );
+        });
+}
+
+#[test]
+fn custom_source_fetches_all_prices_with_bulk_query() {
+    let config = |query: &str| OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "custom".into(),
+        price_periodicity: 1,
+        custom_query: Some(query.into()),
+        source_assets: vec![
+            ("btc".into(), PriceStrategy::Price),
+            ("eth".into(), PriceStrategy::Price),
+            ("dot".into(), PriceStrategy::Price),
+        ]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+    let assets = vec![
+        (asset::BTC, ()),
+        (asset::EQ, ()),
+        (asset::ETH, ()),
+        (asset::DOT, ()),
+    ];
+
+    new_offchain_test_ext_with_http(vec![PendingRequest {
+        method: "GET".into(),
+        uri: "https://api.coingecko.com/api/v3/simple/price?ids=btc,eth,dot&vs_currencies=usd"
+            .into(),
+        response: Some(br#"{"btc":{"usd":30000.5},"eth":{"usd":"1800.25"}}"#.to_vec()),
+        sent: true,
+        ..Default::default()
+    }])
+    .execute_with(|| {
+        let source = JsonPriceSource::<Asset, ()>::new(
+            &config(
+                "json(https://api.coingecko.com/api/v3/simple/price?ids={*}&vs_currencies=usd).{$}.usd",
+            ),
+            assets.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![
+                (
+                    asset::BTC,
+                    Ok(FixedI64::saturating_from_rational(300005, 10))
+                ),
+                (
+                    asset::ETH,
+                    Ok(FixedI64::saturating_from_rational(180025, 100))
+                ),
+                (asset::DOT, Err("Json parse error")),
+            ]
+        );
+
+        // a bulk query without a symbol in the path isn't sent
+        let source = JsonPriceSource::<Asset, ()>::new(
+            &config("json(https://api.binance.com/api/v3/ticker/price).price"),
+            assets.clone(),
+        )
+        .unwrap();
+        assert!(source
+            .get_prices::<FixedI64>()
+            .into_iter()
+            .all(|(_, price)| price == Err("Wrong url pattern")));
+    });
+
+    assert_eq!(
+        JsonPriceSource::<Asset, ()>::parse_query(
+            "json(https://api.coingecko.com/api/v3/simple/price?ids={*}&vs_currencies=usd).{$}.usd"
+        ),
+        Ok((
+            "https://api.coingecko.com/api/v3/simple/price?ids={*}&vs_currencies=usd",
+            "{$}.usd"
+        ))
+    );
+    assert_eq!(
+        asset::BTC.get_url_symbol("https://api.kraken.com/0/public/Ticker?pair={*}USD"),
+        Ok("XXBTZ".into())
+    );
+}
+
+#[test]
+fn graphql_source_with_http_mock() {
+    let url = "https://api.thegraph.com/subgraphs/name/uniswap/uniswap-v3";
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "graphql".into(),
+        price_periodicity: 1,
+        source_assets: vec![("eth".into(), PriceStrategy::Price)]
+            .into_iter()
+            .collect(),
+        graphql: Some(GraphqlSettings {
+            url: url.into(),
+            query: "query($symbol: String!) { tokens(where: {symbol: $symbol}) { derivedUSD } }"
+                .into(),
+            variables: vec![("symbol".into(), "{$}".into())].into_iter().collect(),
+            price_path: "data.tokens[0].derivedUSD".into(),
+            uppercase: true,
+        }),
+        http: vec![(
+            "graphql".into(),
+            HttpOptions {
+                headers: vec![("authorization".into(), "Bearer key".into())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+        )]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+
+    new_offchain_test_ext().execute_with(|| {
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(r#"{"data":{"tokens":[{"derivedUSD":"1834.25"}]}}"#),
+        );
+
+        let source = GraphqlPriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![(
+                asset::ETH,
+                Ok(FixedI64::saturating_from_rational(183425, 100))
+            )]
+        );
+
+        assert!(HttpMock::is_done());
+        assert_eq!(
+            HttpMock::requests(),
+            vec![HttpRequest {
+                method: HttpMethod::Post,
+                url: url.into(),
+                headers: vec![
+                    ("Content-type".into(), "application/json".into()),
+                    ("authorization".into(), "Bearer key".into()),
+                ],
+                body: br#"{"query":"query($symbol: String!) { tokens(where: {symbol: $symbol}) { derivedUSD } }","variables":{"symbol":"ETH"}}"#.to_vec(),
+            }]
+        );
+    });
+}
+
+#[test]
+fn graphql_source_extracts_timestamps() {
+    let url = "https://api.thegraph.com/subgraphs/name/uniswap/uniswap-v2";
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "graphql".into(),
+        price_periodicity: 1,
+        graphql: Some(GraphqlSettings {
+            url: url.into(),
+            query: "query($symbol: String!) { _meta { block { timestamp } } tokens(where: {symbol: $symbol}) { derivedUSD } }"
+                .into(),
+            variables: vec![("symbol".into(), "{$}".into())].into_iter().collect(),
+            price_path: "data.tokens[0].derivedUSD@data._meta.block.timestamp".into(),
+            uppercase: true,
+        }),
+        ..Default::default()
+    };
+
+    new_offchain_test_ext().execute_with(|| {
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(r#"{"data":{"_meta":{"block":{"timestamp":1651406400}},"tokens":[{"derivedUSD":"30000.5"}]}}"#),
+        );
+        HttpMock::expect(
+            HttpMethod::Post,
+            url,
+            Ok(r#"{"data":{"_meta":{"block":null},"tokens":[{"derivedUSD":"1800.25"}]}}"#),
+        );
+
+        let source = GraphqlPriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_observed_prices::<FixedI64>(),
+            vec![
+                (
+                    asset::BTC,
+                    Ok((
+                        FixedI64::saturating_from_rational(300005, 10),
+                        Some(1651406400)
+                    ))
+                ),
+                (asset::ETH, Err("Json parse error")),
+            ]
+        );
+        assert!(HttpMock::is_done());
+    });
+}
+
+#[test]
+fn custom_source_requires_query() {
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "custom".into(),
+        price_periodicity: 1,
+        ..Default::default()
+    };
+    assert_eq!(
+        JsonPriceSource::<Asset, (), HttpMock>::new(&config, vec![(asset::BTC, ())]).err(),
+        Some("No custom_query in config")
+    );
+}
+
+#[test]
+fn custom_source_retries_with_http_mock() {
+    let btc_url = "https://api.binance.com/api/v3/ticker/price?symbol=BTCUSDT&api_key=key";
+    let eth_url = "https://api.binance.com/api/v3/ticker/price?symbol=ETHUSDT&api_key=key";
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "custom".into(),
+        price_periodicity: 1,
+        custom_query: Some(
+            "json(https://api.binance.com/api/v3/ticker/price?symbol={$}USDT).price".into(),
+        ),
+        http: vec![(
+            "custom".into(),
+            HttpOptions {
+                query: vec![("api_key".into(), "key".into())].into_iter().collect(),
+                retries: 2,
+                ..Default::default()
+            },
+        )]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+
+    new_offchain_test_ext().execute_with(|| {
+        HttpMock::expect(
+            HttpMethod::Get,
+            btc_url,
+            Err(RequestError::Status {
+                code: 503,
+                body: "unavailable".into(),
+            }),
+        );
+        // retry after is longer than a request may take
+        HttpMock::expect(
+            HttpMethod::Get,
+            eth_url,
+            Err(RequestError::RateLimited {
+                retry_after: Some(60),
+            }),
+        );
+        HttpMock::expect(HttpMethod::Get, btc_url, Ok(r#"{"price":"30000.5"}"#));
+
+        let source = JsonPriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![
+                (
+                    asset::BTC,
+                    Ok(FixedI64::saturating_from_rational(300005, 10))
+                ),
+                (asset::ETH, Err("Rate limited")),
+            ]
+        );
+
+        assert!(HttpMock::is_done());
+        assert_eq!(
+            HttpMock::requests()
+                .into_iter()
+                .map(|request| request.url)
+                .collect::<Vec<_>>(),
+            vec![btc_url, eth_url, btc_url]
+        );
+    });
+}
+
+fn binance_request(symbol: &str, price: &str) -> PendingRequest {
+    PendingRequest {
+        method: "GET".into(),
+        uri: format!(
+            "https://api.binance.com/api/v3/ticker/price?symbol={}USDT",
+            symbol
+        ),
+        response: Some(
+            format!(r#"{{"symbol":"{}USDT","price":"{}"}}"#, symbol, price).into_bytes(),
+        ),
+        sent: true,
+        ..Default::default()
+    }
+}
+
+fn binance_config(price_periodicity: u32, assets: &[&str]) -> OracleOffchainConfig {
+    OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "custom".into(),
+        price_periodicity,
+        custom_query: Some(
+            "json(https://api.binance.com/api/v3/ticker/price?symbol={$}USDT).price".into(),
+        ),
+        source_assets: assets
+            .iter()
+            .map(|asset| (asset.to_string(), PriceStrategy::Price))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn offchain_worker_submits_prices_every_period() {
+    let mut test = OffchainWorkerTest::new(true);
+    test.execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&binance_config(3, &["btc"])));
+    });
+
+    assert_eq!(test.run_offchain_worker(1), vec![]);
+    assert_eq!(test.run_offchain_worker(2), vec![]);
+
+    test.expect_request(binance_request("BTC", "30000.5"));
+    let transactions = test.run_offchain_worker(3);
+    assert_eq!(transactions.len(), 1);
+    let (payload, _) = &transactions[0];
+    assert_eq!(payload.public, test.public);
+    assert_eq!(payload.asset, asset::BTC);
+    assert_eq!(
+        payload.price,
+        FixedI64::saturating_from_rational(300005, 10)
+    );
+    assert_eq!(payload.block_number, 3);
+    // the source has no timestamps
+    assert_eq!(payload.timestamp, 0);
+
+    test.apply(transactions);
+    test.execute_with(|| {
+        assert_eq!(
+            Oracle::get_price(asset::BTC),
+            Ok(FixedI64::saturating_from_rational(300005, 10))
+        );
+        assert_eq!(offchain_storage::get_counter(), Some(0));
+    });
+
+    // next period starts over
+    assert_eq!(test.run_offchain_worker(4), vec![]);
+    assert_eq!(test.run_offchain_worker(5), vec![]);
+    test.expect_request(binance_request("BTC", "30100"));
+    assert_eq!(test.run_offchain_worker(6).len(), 1);
+}
+
+#[test]
+fn offchain_worker_continues_stored_counter() {
+    let mut test = OffchainWorkerTest::new(true);
+    test.execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&binance_config(3, &["btc"])));
+    });
+    test.set_storage(storage_keys::COUNTER, b"2");
+
+    test.expect_request(binance_request("BTC", "30000.5"));
+    assert_eq!(test.run_offchain_worker(1).len(), 1);
+
+    // counter above the period is reset without prices
+    test.set_storage(storage_keys::COUNTER, b"5");
+    assert_eq!(test.run_offchain_worker(2), vec![]);
+    test.execute_with(|| assert_eq!(offchain_storage::get_counter(), Some(0)));
+}
+
+#[test]
+fn offchain_worker_filters_source_assets() {
+    let mut test = OffchainWorkerTest::new(true);
+    test.execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&binance_config(
+            1,
+            &["btc", "eth", "xrp"]
+        )));
+    });
+
+    // only known assets from `source_assets` are requested
+    test.expect_request(binance_request("BTC", "30000.5"));
+    test.expect_request(binance_request("ETH", "1800.25"));
+    let transactions = test.run_offchain_worker(1);
+    assert_eq!(
+        transactions
+            .iter()
+            .map(|(payload, _)| (payload.asset, payload.price))
+            .collect::<Vec<_>>(),
+        vec![
+            (asset::BTC, FixedI64::saturating_from_rational(300005, 10)),
+            (asset::ETH, FixedI64::saturating_from_rational(180025, 100)),
+        ]
+    );
+
+    test.apply(transactions);
+    test.execute_with(|| {
+        assert_eq!(
+            Oracle::get_price(asset::ETH),
+            Ok(FixedI64::saturating_from_rational(180025, 100))
+        );
+        assert!(Oracle::get_price(asset::DOT).is_err());
+    });
+}
+
+#[test]
+fn offchain_worker_selects_source_by_type() {
+    let mut test = OffchainWorkerTest::new(true);
+
+    // legacy settings are migrated on the first run
+    test.set_storage(storage_keys::RESOURCE_TYPE, b"custom");
+    test.set_storage(storage_keys::PRICE_PERIODICITY, b"1");
+    test.set_storage(
+        storage_keys::CUSTOM_QUERY,
+        b"json(https://api.binance.com/api/v3/ticker/price?symbol={$}USDT).price",
+    );
+    test.set_storage(storage_keys::SOURCE_ASSETS, b"DOT");
+    test.expect_request(binance_request("DOT", "7.5"));
+    let transactions = test.run_offchain_worker(1);
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].0.asset, asset::DOT);
+
+    // unknown source submits nothing, unexpected requests would fail the test
+    test.execute_with(|| {
+        let config = OracleOffchainConfig {
+            source_type: "unknown".into(),
+            ..binance_config(1, &["dot"])
+        };
+        assert_ok!(offchain_storage::set_config(&config));
+    });
+    assert_eq!(test.run_offchain_worker(2), vec![]);
+}
+
+#[test]
+fn offchain_worker_skips_not_whitelisted_key() {
+    let mut test = OffchainWorkerTest::new(false);
+    test.execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&binance_config(1, &["btc"])));
+    });
+
+    assert_eq!(test.run_offchain_worker(1), vec![]);
+    test.execute_with(|| assert_eq!(offchain_storage::get_counter(), None));
+
+    Whitelist::add_to_whitelist(&test.public);
+    test.expect_request(binance_request("BTC", "30000.5"));
+    assert_eq!(test.run_offchain_worker(2).len(), 1);
+}
+
+#[test]
+fn decimal_parsing() {
+    assert_eq!(
+        parse_decimal::<FixedI64>("30000.5"),
+        Ok(FixedI64::saturating_from_rational(300005, 10))
+    );
+    assert_eq!(parse_decimal::<FixedI64>("+2"), Ok(FixedI64::from(2)));
+    assert_eq!(
+        parse_decimal::<FixedI64>("-1.5"),
+        Ok(FixedI64::saturating_from_rational(-15, 10))
+    );
+    assert_eq!(
+        parse_decimal::<FixedI64>(".5"),
+        Ok(FixedI64::saturating_from_rational(1, 2))
+    );
+    assert_eq!(parse_decimal::<FixedI64>("7."), Ok(FixedI64::from(7)));
+    assert_eq!(
+        parse_decimal::<FixedI64>("0.000000001"),
+        Ok(FixedI64::from_inner(1))
+    );
+    // digits beyond the accuracy are truncated
+    assert_eq!(
+        parse_decimal::<FixedI64>("0.0000000019"),
+        Ok(FixedI64::from_inner(1))
+    );
+    assert_eq!(
+        parse_decimal::<FixedU128>("123456789.123456789123456789999"),
+        Ok(FixedU128::from_inner(123456789_123456789123456789))
+    );
+
+    for invalid in [
+        "",
+        ".",
+        "-",
+        "+-1",
+        "1.2.3",
+        "1,5",
+        " 1",
+        "0x10",
+        "NaN",
+        "inf",
+        "-Infinity",
+        "1e",
+        "e5",
+        "1e+",
+        "1e5.5",
+        "1e5e3",
+    ] {
+        assert_eq!(
+            parse_decimal::<FixedI64>(invalid),
+            Err(DecimalError::Invalid),
+            "{}",
+            invalid
+        );
+    }
+
+    // scientific notation
+    assert_eq!(
+        parse_decimal::<FixedI64>("1.5e-7"),
+        Ok(FixedI64::from_inner(150))
+    );
+    assert_eq!(parse_decimal::<FixedI64>("25E+2"), Ok(FixedI64::from(2500)));
+    assert_eq!(
+        parse_decimal::<FixedI64>("-0.0031e3"),
+        Ok(FixedI64::saturating_from_rational(-31, 10))
+    );
+    assert_eq!(
+        parse_decimal::<FixedU128>("1e-18"),
+        Ok(FixedU128::from_inner(1))
+    );
+    assert_eq!(parse_decimal::<FixedU128>("1e-19"), Ok(FixedU128::zero()));
+    assert_eq!(parse_decimal::<FixedI64>("0e1000000"), Ok(FixedI64::zero()));
+    assert_eq!(
+        parse_decimal::<FixedI64>("1e-99999999999999999999999"),
+        Ok(FixedI64::zero())
+    );
+
+    // out of range
+    for overflow in ["10000000000", "1e10", "-1e10", "1e99999999999999999999999"] {
+        assert_eq!(
+            parse_decimal::<FixedI64>(overflow),
+            Err(DecimalError::Overflow),
+            "{}",
+            overflow
+        );
+    }
+    assert_eq!(
+        parse_decimal::<FixedU128>("-1"),
+        Err(DecimalError::Overflow)
+    );
+    assert_eq!(
+        parse_decimal::<FixedU128>("340282366920938463463.374607431768211455"),
+        Ok(FixedU128::from_inner(u128::MAX))
+    );
+    assert_eq!(
+        parse_decimal::<FixedU128>("340282366920938463463.3746074317682114559"),
+        Ok(FixedU128::from_inner(u128::MAX))
+    );
+    assert_eq!(
+        parse_decimal::<FixedU128>("340282366920938463463.374607431768211456"),
+        Err(DecimalError::Overflow)
+    );
+}
+
+#[test]
+fn json_price_sanity_checks() {
+    let price = |body: &str| {
+        JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedI64>(body, "price")
+            .map(|(price, _)| price)
+    };
+
+    // no float rounding
+    assert_eq!(
+        price(r#"{"price":"0.123456789"}"#),
+        Ok(FixedI64::from_inner(123456789))
+    );
+    assert_eq!(price(r#"{"price":42}"#), Ok(FixedI64::from(42)));
+    assert_eq!(price(r#"{"price":"0"}"#), Ok(FixedI64::zero()));
+
+    assert_eq!(
+        price(r#"{"price":"-1"}"#),
+        Err(PriceSourceError::NegativePrice)
+    );
+    assert_eq!(
+        price(r#"{"price":-2.5}"#),
+        Err(PriceSourceError::NegativePrice)
+    );
+    for not_finite in ["NaN", "inf", "-inf", "Infinity"] {
+        assert_eq!(
+            price(&format!(r#"{{"price":"{}"}}"#, not_finite)),
+            Err(PriceSourceError::JsonPriceConversionError)
+        );
+    }
+    // doesn't fit `FixedI64`
+    assert_eq!(
+        price(r#"{"price":"100000000000"}"#),
+        Err(PriceSourceError::OverflowError)
+    );
+    assert_eq!(
+        price(r#"{"price":"3.5e-7"}"#),
+        Ok(FixedI64::from_inner(350))
+    );
+    assert_eq!(price(r#"{"price":1e-7}"#), Ok(FixedI64::from_inner(100)));
+
+    // json numbers keep digits `f64` can't hold
+    let precise_price = |body: &str| {
+        JsonPriceSource::<Asset, ()>::fetch_observation_from_json::<FixedU128>(body, "price")
+            .map(|(price, _)| price)
+    };
+    assert_eq!(
+        precise_price(r#"{"price":0.123456789012345678}"#),
+        Ok(FixedU128::from_inner(123_456_789_012_345_678))
+    );
+    assert_eq!(
+        precise_price(r#"{"price":12345678901234567891}"#),
+        Ok(FixedU128::saturating_from_integer(
+            12_345_678_901_234_567_891u128
+        ))
+    );
+}
+
+#[test]
+fn price_bounds_in_config() {
+    let bounds = |min: Option<&str>, max: Option<&str>| PriceBounds {
+        min: min.map(Into::into),
+        max: max.map(Into::into),
+    };
+    let config = |bounds: PriceBounds| OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "custom".into(),
+        price_periodicity: 1,
+        custom_query: Some(
+            "json(https://api.binance.com/api/v3/ticker/price?symbol={$}USDT).price".into(),
+        ),
+        price_bounds: vec![("btc".into(), bounds)].into_iter().collect(),
+        ..Default::default()
+    };
+
+    assert_ok!(config(bounds(Some("10000"), Some("100000.5"))).validate());
+    assert_ok!(config(bounds(Some("1"), Some("1"))).validate());
+    assert_ok!(config(bounds(None, Some("100000"))).validate());
+    assert_ok!(config(bounds(None, None)).validate());
+    for invalid in [
+        bounds(Some("2"), Some("1")),
+        bounds(Some("-1"), None),
+        bounds(None, Some("1e5")),
+        bounds(Some("NaN"), None),
+    ] {
+        assert_eq!(
+            config(invalid).validate(),
+            Err(ConfigError::InvalidPriceBounds("btc".into()))
+        );
+    }
+
+    let config = config(bounds(Some("10000"), Some("100000")));
+    assert_eq!(
+        OracleOffchainConfig::from_json(&config.to_json().unwrap()),
+        Ok(config.clone())
+    );
+    assert!(config.is_price_in_bounds("BTC", FixedI64::from(10000)));
+    assert!(config.is_price_in_bounds("btc", FixedI64::from(100000)));
+    assert!(!config.is_price_in_bounds("btc", FixedI64::saturating_from_rational(99999, 10)));
+    assert!(!config.is_price_in_bounds("btc", FixedI64::from(100001)));
+    // other assets are not limited
+    assert!(config.is_price_in_bounds("eth", FixedI64::from(1)));
+
+    // a bound out of the range of the price type
+    let config = OracleOffchainConfig {
+        price_bounds: vec![
+            ("btc".into(), bounds(None, Some("100000000000"))),
+            ("eth".into(), bounds(Some("100000000000"), None)),
+        ]
+        .into_iter()
+        .collect(),
+        ..config
+    };
+    assert!(config.is_price_in_bounds("btc", FixedI64::from(100001)));
+    assert!(!config.is_price_in_bounds("eth", FixedI64::from(100001)));
+}
+
+#[test]
+fn custom_source_rejects_prices_out_of_bounds() {
+    let btc_url = "https://api.binance.com/api/v3/ticker/price?symbol=BTCUSDT";
+    let eth_url = "https://api.binance.com/api/v3/ticker/price?symbol=ETHUSDT";
+    let config = OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "custom".into(),
+        price_periodicity: 1,
+        custom_query: Some(
+            "json(https://api.binance.com/api/v3/ticker/price?symbol={$}USDT).price".into(),
+        ),
+        price_bounds: vec![(
+            "btc".into(),
+            PriceBounds {
+                min: Some("10000".into()),
+                max: None,
+            },
+        )]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+
+    new_offchain_test_ext().execute_with(|| {
+        HttpMock::expect(HttpMethod::Get, btc_url, Ok(r#"{"price":"3.0005"}"#));
+        HttpMock::expect(HttpMethod::Get, eth_url, Ok(r#"{"price":"-1800.25"}"#));
+
+        let source = JsonPriceSource::<Asset, (), HttpMock>::new(
+            &config,
+            vec![(asset::BTC, ()), (asset::ETH, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_prices::<FixedI64>(),
+            vec![
+                (asset::BTC, Err("Price out of bounds")),
+                (asset::ETH, Err("Negative price")),
+            ]
+        );
+        assert!(HttpMock::is_done());
+    });
+}
+
+#[test]
+fn timestamp_parsing() {
+    for (value, expected) in [
+        ("1651406400", Some(1651406400)),
+        ("1651406400.75", Some(1651406400)),
+        ("1651406400123", Some(1651406400)),
+        ("1651406400123456", Some(1651406400)),
+        ("1651406400123456789", Some(1651406400)),
+        (" 1651406400 ", Some(1651406400)),
+        ("2022-05-01T12:00:00Z", Some(1651406400)),
+        ("2022-05-01t12:00:00.123456z", Some(1651406400)),
+        ("2022-05-01 14:30:00+02:30", Some(1651406400)),
+        ("2022-05-01T10:00:00-02:00", Some(1651406400)),
+        ("2000-02-29T23:59:59Z", Some(951868799)),
+        ("", None),
+        (".5", None),
+        ("1.2.3", None),
+        ("-1651406400", None),
+        ("now", None),
+        ("2022-05-01T12:00:00", None),
+        ("2022-05-01T12:00:00.Z", None),
+        ("2022-13-01T12:00:00Z", None),
+        ("2022-05-01T24:00:00Z", None),
+        ("2022-05-01T12:00:00+0200", None),
+        ("1969-12-31T23:59:59Z", None),
+    ] {
+        assert_eq!(parse_timestamp(value), expected, "{}", value);
+    }
+}
+
+#[test]
+fn custom_source_extracts_timestamps() {
+    let config = |query: &str| OracleOffchainConfig {
+        version: CONFIG_VERSION,
+        source_type: "custom".into(),
+        price_periodicity: 1,
+        custom_query: Some(query.into()),
+        ..Default::default()
+    };
+    let btc_url = "https://api.binance.com/api/v3/ticker/24hr?symbol=BTCUSDT";
+    let eth_url = "https://api.binance.com/api/v3/ticker/24hr?symbol=ETHUSDT";
+    let dot_url = "https://api.binance.com/api/v3/ticker/24hr?symbol=DOTUSDT";
+
+    new_offchain_test_ext().execute_with(|| {
+        HttpMock::expect(
+            HttpMethod::Get,
+            btc_url,
+            Ok(r#"{"lastPrice":"30000.5","closeTime":1651406400123}"#),
+        );
+        HttpMock::expect(HttpMethod::Get, eth_url, Ok(r#"{"lastPrice":"1800.25"}"#));
+        HttpMock::expect(
+            HttpMethod::Get,
+            dot_url,
+            Ok(r#"{"lastPrice":"7.5","closeTime":"yesterday"}"#),
+        );
+
+        let source = JsonPriceSource::<Asset, (), HttpMock>::new(
+            &config(
+                "json(https://api.binance.com/api/v3/ticker/24hr?symbol={$}USDT).lastPrice@closeTime",
+            ),
+            vec![(asset::BTC, ()), (asset::ETH, ()), (asset::DOT, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_observed_prices::<FixedI64>(),
+            vec![
+                (
+                    asset::BTC,
+                    Ok((
+                        FixedI64::saturating_from_rational(300005, 10),
+                        Some(1651406400)
+                    ))
+                ),
+                (asset::ETH, Err("Json parse error")),
+                (asset::DOT, Err("Invalid timestamp")),
+            ]
+        );
+        assert!(HttpMock::is_done());
+
+        // bulk query
+        let url = "https://api.coingecko.com/api/v3/simple/price?ids=btc,eth&vs_currencies=usd&include_last_updated_at=true";
+        HttpMock::expect(
+            HttpMethod::Get,
+            url,
+            Ok(r#"{"btc":{"usd":30000.5,"last_updated_at":1651406400},"eth":{"usd":1800.25,"last_updated_at":1651406280}}"#),
+        );
+        let source = JsonPriceSource::<Asset, (), HttpMock>::new(
+            &config("json(https://api.coingecko.com/api/v3/simple/price?ids={*}&vs_currencies=usd&include_last_updated_at=true).{$}.usd@{$}.last_updated_at"),
+            vec![(asset::BTC, ()), (asset::ETH, ())],
+        )
+        .unwrap();
+        assert_eq!(
+            source.get_observed_prices::<FixedI64>(),
+            vec![
+                (
+                    asset::BTC,
+                    Ok((
+                        FixedI64::saturating_from_rational(300005, 10),
+                        Some(1651406400)
+                    ))
+                ),
+                (
+                    asset::ETH,
+                    Ok((
+                        FixedI64::saturating_from_rational(180025, 100),
+                        Some(1651406280)
+                    ))
+                ),
+            ]
+        );
+    });
+}
+
+#[test]
+fn offchain_worker_skips_stale_prices() {
+    let query =
+        "json(https://api.binance.com/api/v3/ticker/24hr?symbol={$}USDT).lastPrice@closeTime";
+    let request = PendingRequest {
+        method: "GET".into(),
+        uri: "https://api.binance.com/api/v3/ticker/24hr?symbol=BTCUSDT".into(),
+        response: Some(br#"{"lastPrice":"30000.5","closeTime":1651406400123}"#.to_vec()),
+        sent: true,
+        ..Default::default()
+    };
+    let mut test = OffchainWorkerTest::new(true);
+    test.offchain.write().timestamp = OffchainTimestamp::from_unix_millis(1_651_406_520_000);
+
+    let mut config = OracleOffchainConfig {
+        custom_query: Some(query.into()),
+        max_price_age: 60,
+        ..binance_config(1, &["btc"])
+    };
+    assert!(config.is_price_fresh(Some(1651406460), 1651406520));
+    assert!(!config.is_price_fresh(Some(1651406459), 1651406520));
+    assert!(config.is_price_fresh(Some(1651406600), 1651406520));
+    assert!(config.is_price_fresh(None, 1651406520));
+
+    test.execute_with(|| assert_ok!(offchain_storage::set_config(&config)));
+    test.expect_request(request.clone());
+    assert_eq!(test.run_offchain_worker(1), vec![]);
+
+    config.max_price_age = 120;
+    test.execute_with(|| assert_ok!(offchain_storage::set_config(&config)));
+    test.expect_request(request.clone());
+    assert_eq!(test.run_offchain_worker(2).len(), 1);
+
+    // not checked
+    config.max_price_age = 0;
+    assert!(config.is_price_fresh(Some(0), 1651406520));
+    test.execute_with(|| assert_ok!(offchain_storage::set_config(&config)));
+    test.expect_request(request);
+    assert_eq!(test.run_offchain_worker(3).len(), 1);
+}
+
+#[test]
+fn observed_prices_are_checked_against_block_time() {
+    new_observation_test_ext().execute_with(|| {
+        let account_id_1 = Sign { 0: [0; 32] };
+        let now = 10_000;
+        Timestamp::set_timestamp(now * 1000);
+        System::set_block_number(1);
+        Whitelist::add_to_whitelist(&account_id_1);
+
+        assert_err!(
+            set_observed_price(account_id_1, asset::BTC, 30_000., 1, now + 61),
+            Error::<Test>::InvalidPriceTimestamp
+        );
+        assert_err!(
+            set_observed_price(account_id_1, asset::BTC, 30_000., 1, now - 61),
+            Error::<Test>::InvalidPriceTimestamp
+        );
+        assert_ok!(set_observed_price(
+            account_id_1,
+            asset::BTC,
+            30_000.,
+            1,
+            now - 60
+        ));
+
+        let price_data = Oracle::price_points(asset::BTC).unwrap();
+        assert_eq!(price_data.timestamp, now - 60);
+        assert_eq!(price_data.price_points[0].timestamp, now - 60);
+        assert_eq!(
+            Oracle::price_points_of(&asset::BTC, &account_id_1)
+                .unwrap()
+                .timestamp,
+            now - 60
+        );
+
+        // legacy transactions are observed at the block time
+        System::set_block_number(2);
+        assert_ok!(set_price(account_id_1, asset::BTC, 30_100., 2));
+        assert_eq!(Oracle::price_points(asset::BTC).unwrap().timestamp, now);
+    });
+}
+
+#[test]
+fn price_points_expire_by_observation_time() {
+    new_observation_test_ext().execute_with(|| {
+        let account_id_1 = Sign { 0: [0; 32] };
+        let account_id_2 = Sign { 0: [1; 32] };
+        let account_id_3 = Sign { 0: [2; 32] };
+        let mut time = 10_000;
+        Timestamp::set_timestamp(time * 1000);
+        System::set_block_number(1);
+        Whitelist::add_to_whitelist(&account_id_1);
+        Whitelist::add_to_whitelist(&account_id_2);
+        Whitelist::add_to_whitelist(&account_id_3);
+
+        // observed 60 seconds before the block, it's older than `PriceTimeout`
+        // when the next price is added 61 seconds later
+        assert_ok!(set_observed_price(
+            account_id_1,
+            asset::BTC,
+            10_000.,
+            1,
+            time - 60
+        ));
+        time_move(&mut time, 61);
+        let block_number = System::block_number();
+        assert_ok!(set_observed_price(
+            account_id_2,
+            asset::BTC,
+            20_000.,
+            block_number,
+            time
+        ));
+        assert_ok!(set_observed_price(
+            account_id_3,
+            asset::BTC,
+            30_000.,
+            block_number,
+            time
+        ));
+        check_price(asset::BTC, 25_000.);
+        assert_eq!(Oracle::price_points(asset::BTC).unwrap().timestamp, time);
+
+        // the median is as old as its latest observation
+        assert_ok!(set_observed_price(
+            account_id_1,
+            asset::ETH,
+            1_800.,
+            block_number,
+            time - 60
+        ));
+        time_move(&mut time, MedianPriceTimeout::get() - 61);
+        check_price(asset::ETH, 1_800.);
+        time_move(&mut time, 1);
+        assert_err!(Oracle::get_price(asset::ETH), Error::<Test>::PriceTimeout);
+    });
+}
+
+#[test]
+fn offchain_worker_sends_observation_timestamps() {
+    let now = 1_651_406_520;
+    let mut test = OffchainWorkerTest::new(true);
+    test.offchain.write().timestamp = OffchainTimestamp::from_unix_millis(now * 1000);
+    test.execute_with(|| {
+        Timestamp::set_timestamp(now * 1000);
+        let config = OracleOffchainConfig {
+            custom_query: Some(
+                "json(https://api.binance.com/api/v3/ticker/24hr?symbol={$}USDT).lastPrice@closeTime"
+                    .into(),
+            ),
+            ..binance_config(1, &["btc", "eth"])
+        };
+        assert_ok!(offchain_storage::set_config(&config));
+    });
+
+    test.expect_request(PendingRequest {
+        method: "GET".into(),
+        uri: "https://api.binance.com/api/v3/ticker/24hr?symbol=BTCUSDT".into(),
+        response: Some(br#"{"lastPrice":"30000.5","closeTime":1651406490000}"#.to_vec()),
+        sent: true,
+        ..Default::default()
+    });
+    test.expect_request(PendingRequest {
+        method: "GET".into(),
+        uri: "https://api.binance.com/api/v3/ticker/24hr?symbol=ETHUSDT".into(),
+        response: Some(br#"{"lastPrice":"1800.25","closeTime":"2022-05-01T12:00:00Z"}"#.to_vec()),
+        sent: true,
+        ..Default::default()
+    });
+    let transactions = test.run_offchain_worker(1);
+    assert_eq!(
+        transactions
+            .iter()
+            .map(|(payload, _)| (payload.asset, payload.timestamp))
+            .collect::<Vec<_>>(),
+        vec![(asset::BTC, now - 30), (asset::ETH, now - 120)]
+    );
+
+    // ETH price was observed before the tolerance
+    let (btc, eth) = (transactions[0].clone(), transactions[1].clone());
+    test.execute_with(|| {
+        let call = crate::Call::set_price_unsigned_v2 {
+            payload: eth.0,
+            signature: eth.1,
+        };
+        assert_eq!(
+            Oracle::validate_unsigned(TransactionSource::External, &call),
+            Err(InvalidTransaction::Stale.into())
+        );
+    });
+    test.apply(vec![btc]);
+    test.execute_with(|| {
+        assert_eq!(
+            Oracle::price_points(asset::BTC).unwrap().timestamp,
+            now - 30
+        );
+    });
+
+    // prices without timestamps are observed by the offchain worker clock
+    test.execute_with(|| {
+        assert_ok!(offchain_storage::set_config(&binance_config(1, &["btc"])));
+    });
+    test.offchain.write().timestamp = OffchainTimestamp::from_unix_millis((now + 90) * 1000);
+    test.expect_request(binance_request("BTC", "30000.5"));
+    let transactions = test.run_offchain_worker(2);
+    assert_eq!(transactions[0].0.timestamp, now + 90);
+    test.execute_with(|| {
+        let call = crate::Call::set_price_unsigned_v2 {
+            payload: transactions[0].0.clone(),
+            signature: transactions[0].1.clone(),
+        };
+        assert_eq!(
+            Oracle::validate_unsigned(TransactionSource::External, &call),
+            Err(InvalidTransaction::Future.into())
+        );
+    });
+}
@@ -1,11 +1,75 @@
+//! Weights for eq_oracle
+//!
+//! The pallet is not benchmarked on the reference hardware yet, so `SubstrateWeight`
+//! charges only storage accesses counted from the code, execution time is not measured.
+//! Replace this file with the benchmark CLI output before a release:
+//! ```bash
+//! ./target/release/node benchmark pallet --chain=dev --steps=50 --repeat=20 \
+//!     --pallet=eq_oracle --extrinsic=* --execution=wasm --wasm-execution=compiled \
+//!     --heap-pages=4096 --output=./pallets/eq-oracle/src/weights.rs
+//! ```
+
 #![allow(unused_parens)]
 #![allow(unused_imports)]
 
 use frame_support::{traits::Get, weights::Weight};
 use sp_std::marker::PhantomData;
 
+/// Weight functions needed for eq_oracle.
 pub trait WeightInfo {
+    /// `b` - amount of price points already stored for the asset
     fn set_price(b: u32) -> Weight;
+    /// `b` - amount of price points already stored for the asset
+    fn set_price_unsigned(b: u32) -> Weight;
+    /// `b` - amount of price points already stored for the asset
+    fn set_price_unsigned_v2(b: u32) -> Weight;
+    /// Validation of an unsigned price transaction, it is repeated before dispatch
+    fn validate_unsigned() -> Weight;
+    /// `on_initialize` and `on_finalize` of a block.
+    /// `a` - amount of assets, `s` - amount of written special prices,
+    /// `c` - amount of correlated assets, `l` - amount of LP tokens
+    fn on_initialize(a: u32, s: u32, c: u32, l: u32) -> Weight;
+    /// `a` - amount of assets
+    fn filter_prices_from(a: u32) -> Weight;
+    fn set_rate() -> Weight;
+}
+
+/// Weights for eq_oracle using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+    fn set_price(_b: u32) -> Weight {
+        T::DbWeight::get().reads_writes(4 as Weight, 2 as Weight)
+    }
+    fn set_price_unsigned(_b: u32) -> Weight {
+        T::DbWeight::get().reads_writes(4 as Weight, 2 as Weight)
+    }
+    fn set_price_unsigned_v2(_b: u32) -> Weight {
+        T::DbWeight::get().reads_writes(4 as Weight, 2 as Weight)
+    }
+    fn validate_unsigned() -> Weight {
+        T::DbWeight::get().reads(3 as Weight)
+    }
+    fn on_initialize(a: u32, s: u32, c: u32, l: u32) -> Weight {
+        T::DbWeight::get()
+            .reads(3 as Weight)
+            .saturating_add(T::DbWeight::get().reads((2 as Weight).saturating_mul(a as Weight)))
+            .saturating_add(T::DbWeight::get().reads((3 as Weight).saturating_mul(c as Weight)))
+            .saturating_add(T::DbWeight::get().reads((4 as Weight).saturating_mul(l as Weight)))
+            .saturating_add(T::DbWeight::get().writes(1 as Weight))
+            .saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(a as Weight)))
+            .saturating_add(T::DbWeight::get().writes((2 as Weight).saturating_mul(s as Weight)))
+            .saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(c as Weight)))
+            .saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(l as Weight)))
+    }
+    fn filter_prices_from(a: u32) -> Weight {
+        T::DbWeight::get()
+            .reads(1 as Weight)
+            .saturating_add(T::DbWeight::get().reads((1 as Weight).saturating_mul(a as Weight)))
+            .saturating_add(T::DbWeight::get().writes((2 as Weight).saturating_mul(a as Weight)))
+    }
+    fn set_rate() -> Weight {
+        T::DbWeight::get().reads_writes(1 as Weight, 1 as Weight)
+    }
 }
 
 // for tests
@@ -13,4 +77,22 @@ impl crate::WeightInfo for () {
     fn set_price(_b: u32) -> Weight {
         0 as Weight
     }
+    fn set_price_unsigned(_b: u32) -> Weight {
+        0 as Weight
+    }
+    fn set_price_unsigned_v2(_b: u32) -> Weight {
+        0 as Weight
+    }
+    fn validate_unsigned() -> Weight {
+        0 as Weight
+    }
+    fn on_initialize(_a: u32, _s: u32, _c: u32, _l: u32) -> Weight {
+        0 as Weight
+    }
+    fn filter_prices_from(_a: u32) -> Weight {
+        0 as Weight
+    }
+    fn set_rate() -> Weight {
+        0 as Weight
+    }
 }